@@ -0,0 +1,263 @@
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::process::ExitCode;
+use std::time::Instant;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use hello_wasm::maze::read_maze;
+use hello_wasm::maze::pathfinding::{Links, Walked};
+use hello_wasm::maze::pathfinding::analysis::analyse;
+use hello_wasm::maze::pathfinding::dead_end_filling::fill;
+use hello_wasm::maze::pathfinding::distances::Distances;
+use hello_wasm::maze::pathfinding::evolution::Probe;
+use hello_wasm::maze::pathfinding::generate::{generate, to_text};
+use hello_wasm::maze::pathfinding::grid::{Grid, Scratch};
+use hello_wasm::maze::pathfinding::hierarchical::Hierarchy;
+use hello_wasm::maze::pathfinding::keys::{self, Locks};
+use hello_wasm::maze::pathfinding::pledge::pledge;
+use hello_wasm::maze::pathfinding::timed::{plan, Schedule};
+use hello_wasm::maze::pathfinding::tremaux::tremaux;
+use hello_wasm::maze::pathfinding::wall_follower::{follow_wall, Hand};
+
+// the maze game's pathfinding from the command line, for making levels in scripts and
+// checking what the solvers do without a browser
+
+const USAGE: &str = "\
+usage:
+  maze generate <width> <height> [--seed N] [--loops PERCENT]
+  maze solve <file> [--algorithm ALGORITHM] [--from X,Y] [--to X,Y]
+  maze analyse <file> [--from X,Y] [--to X,Y]
+  maze bench <file> [--runs N] [--from X,Y] [--to X,Y]
+
+<file> is maze text as the game reads it, - for standard input. without --from and --to
+the route goes from the first open cell to the last one. ALGORITHM is one of BFS, DFS,
+AStar (the default), BestFirst, WallFollowerLeft, WallFollowerRight, Pledge, Tremaux,
+KeysAndDoors, SpaceTime, HPAStar or DeadEndFilling, bench runs them all";
+
+// every solver of the game, named as find_path knows them
+const ALGORITHMS: [&str; 12] = [
+    "BFS", "DFS", "AStar", "BestFirst", "WallFollowerLeft", "WallFollowerRight",
+    "Pledge", "Tremaux", "KeysAndDoors", "SpaceTime", "HPAStar", "DeadEndFilling",
+];
+
+type Cell = (usize, usize);
+
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("generate") => generate_command(&args[1..]),
+        Some("solve") => solve_command(&args[1..]),
+        Some("analyse") | Some("analyze") => analyse_command(&args[1..]),
+        Some("bench") => bench_command(&args[1..]),
+        _ => Err(USAGE.to_string()),
+    };
+    match result {
+        Ok(output) => {
+            print!("{}", output);
+            ExitCode::SUCCESS
+        },
+        Err(message) => {
+            eprintln!("{}", message);
+            ExitCode::FAILURE
+        },
+    }
+}
+
+
+// the value after `--name`, if it was given
+fn option<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter().position(|arg| arg == name).and_then(|at| args.get(at + 1)).map(String::as_str)
+}
+
+fn number(text: &str) -> Result<usize, String> {
+    text.parse().map_err(|_| format!("Not a number: {}", text))
+}
+
+fn cell(text: &str) -> Result<Cell, String> {
+    let (x, y) = text.split_once(',').ok_or_else(|| format!("Not a cell: {}, expected X,Y", text))?;
+    Ok((number(x.trim())?, number(y.trim())?))
+}
+
+
+struct Maze {
+    grid: Grid,
+    links: Links,
+    portal_labels: HashMap<Cell, char>,
+    locks: Locks,
+    schedule: Schedule,
+    start: Cell,
+    target: Cell,
+}
+
+fn load(args: &[String]) -> Result<Maze, String> {
+    let path = args.first().ok_or_else(|| USAGE.to_string())?;
+    let mut text = String::new();
+    if path == "-" {
+        std::io::stdin().read_to_string(&mut text).map_err(|error| error.to_string())?;
+    } else {
+        text = std::fs::read_to_string(path).map_err(|error| format!("{}: {}", path, error))?;
+    }
+    let file = read_maze(&text)?;
+    let grid = file.grid;
+
+    let open: Vec<Cell> = grid.open_cells().collect();
+    let is_open = |cell: Cell| open.contains(&cell);
+    let start = match option(args, "--from") {
+        Some(text) => cell(text)?,
+        None => *open.first().ok_or("The maze has no open cells!")?,
+    };
+    let target = match option(args, "--to") {
+        Some(text) => cell(text)?,
+        None => *open.last().ok_or("The maze has no open cells!")?,
+    };
+    if !is_open(start) || !is_open(target) {
+        return Err("The start and the target must be open cells!".to_string());
+    }
+    Ok(Maze { grid, links: file.links, portal_labels: file.portal_labels, locks: file.locks, schedule: file.schedule, start, target })
+}
+
+// (cells expanded, path from start to target), where what counts as expanded is what the
+// solver colours on the canvas on its way: the cells a search found, the steps a walker took,
+// the dead ends filled, or the states a planner expanded
+fn solve(maze: &Maze, algorithm: &str) -> Result<Option<(usize, Vec<Cell>)>, String> {
+    let (grid, links, start, target) = (&maze.grid, &maze.links, maze.start, maze.target);
+    let walked = |walked: Walked| walked.path.ok().map(|path| (walked.steps.len(), path));
+
+    let mut scratch = Scratch::default();
+    Ok(match algorithm {
+        "WallFollowerLeft" => walked(follow_wall(grid, links, &mut scratch, Hand::Left, start, target)),
+        "WallFollowerRight" => walked(follow_wall(grid, links, &mut scratch, Hand::Right, start, target)),
+        "Pledge" => walked(pledge(grid, links, start, target)),
+        "Tremaux" => walked(tremaux(grid, links, &mut scratch, start, target)),
+        "DeadEndFilling" => {
+            let (filled, path) = fill(grid.clone(), links, &mut scratch, start, target);
+            path.map(|path| (filled.len(), path))
+        },
+        "KeysAndDoors" => {
+            let mut reached = 0;
+            let route = keys::search(grid, links, &maze.locks, start, target, |_| reached += 1);
+            route.map(|route| (reached, route.into_iter().map(|(cell, _)| cell).collect()))
+        },
+        "SpaceTime" => {
+            let mut expanded = 0;
+            plan(grid, links, &maze.schedule, start, target, |_| expanded += 1)?.map(|route| (expanded, route))
+        },
+        "HPAStar" => {
+            let mut hierarchy = Hierarchy::default();
+            hierarchy.sync(grid, links);
+            let (path, expanded) = hierarchy.find(&mut scratch, start, target);
+            path.map(|path| (expanded.len(), path))
+        },
+        _ => {
+            let probe = Probe::from_name(algorithm).ok_or_else(|| format!("Unknown algorithm {}!", algorithm))?;
+            probe.search(grid, links, &mut scratch, start, target)
+        },
+    })
+}
+
+// what the maze text has at `cell` besides a wall or a path: a portal's digit, a stair
+// (`>` up, `<` down), a key's or a door's letter
+fn symbol(maze: &Maze, cell: Cell) -> Option<char> {
+    if let Some(&digit) = maze.portal_labels.get(&cell) {
+        return Some(digit);
+    }
+    if let Some(other_end) = maze.links.floors.partner(cell) {
+        return Some(if other_end.0 > cell.0 { '>' } else { '<' });
+    }
+    maze.locks.keys.get(&cell).map(|&key| (b'a' + key) as char)
+        .or_else(|| maze.locks.doors.get(&cell).map(|&door| (b'A' + door) as char))
+}
+
+// walls as `*`, the path as `.`, the start and target as S and T, and portals, stairs,
+// keys and doors as they are written in maze text
+fn render(maze: &Maze, path: &[Cell]) -> String {
+    let on_path: HashSet<Cell> = path.iter().copied().collect();
+    (0..maze.grid.height()).map(|y| {
+        (0..maze.grid.width()).map(|x| match ((x, y), symbol(maze, (x, y))) {
+            (position, _) if position == maze.start => 'S',
+            (position, _) if position == maze.target => 'T',
+            (_, Some(symbol)) => symbol,
+            (position, None) if !maze.grid.is_path(position) => '*',
+            (position, None) if on_path.contains(&position) => '.',
+            _ => ' ',
+        }).collect::<String>() + "\n"
+    }).collect()
+}
+
+
+fn generate_command(args: &[String]) -> Result<String, String> {
+    let width = number(args.first().ok_or_else(|| USAGE.to_string())?)?;
+    let height = number(args.get(1).ok_or_else(|| USAGE.to_string())?)?;
+    let loops = option(args, "--loops").map(number).transpose()?.unwrap_or(0);
+    let mut rng = match option(args, "--seed") {
+        Some(seed) => StdRng::seed_from_u64(number(seed)? as u64),
+        None => StdRng::from_entropy(),
+    };
+    Ok(to_text(&generate(width, height, loops, &mut rng)))
+}
+
+fn solve_command(args: &[String]) -> Result<String, String> {
+    let maze = load(args)?;
+    let algorithm = option(args, "--algorithm").unwrap_or("AStar");
+    match solve(&maze, algorithm)? {
+        Some((expanded, path)) => Ok(format!(
+            "{}{}: path of {} moves, {} cells expanded\n",
+            render(&maze, &path), algorithm, path.len() - 1, expanded,
+        )),
+        None => Err(format!("{}{}: target not reachable!", render(&maze, &[]), algorithm)),
+    }
+}
+
+fn analyse_command(args: &[String]) -> Result<String, String> {
+    let maze = load(args)?;
+    let analysis = analyse(&maze.grid, &maze.links, maze.start, maze.target);
+    let mut output = String::new();
+    output += &format!("size        {} x {} ({} cells, {:?})\n", maze.grid.width(), maze.grid.height(), analysis.cells, maze.links.topology);
+    output += &format!("open        {}\n", analysis.open);
+    output += &format!("dead ends   {}\n", analysis.dead_ends);
+    output += &format!("corridors   {}\n", analysis.corridors);
+    output += &format!("junctions   {}\n", analysis.junctions);
+    output += &format!("isolated    {}\n", analysis.isolated);
+    output += &format!("regions     {} (largest {})\n", analysis.regions, analysis.largest_region);
+    output += &format!("portals     {}\n", maze.links.portals.pairs.len() / 2);
+    output += &format!("floors      {} ({} stairs)\n", maze.links.floors.count.max(1), maze.links.floors.stairs.len() / 2);
+    output += &format!("reachable   {} of {} from {:?}\n", analysis.reachable, analysis.open, maze.start);
+    output += &match analysis.shortest {
+        Some(cost) => format!("shortest    {} to {:?}\n", cost, maze.target),
+        None => format!("shortest    none, {:?} is not reachable\n", maze.target),
+    };
+    // all pairs distances only for mazes small enough to hold the table
+    if let Ok(distances) = Distances::new(&maze.grid, &maze.links) {
+        let centre = distances.centre();
+        if let Some(&cell) = centre.first() {
+            let radius = distances.eccentricity_of(cell).unwrap_or(0);
+            output += &format!("centre      {:?} of {} cells, radius {}\n", cell, centre.len(), radius);
+        }
+        if let Some((from, to, distance)) = distances.diameter() {
+            output += &format!("diameter    {} from {:?} to {:?}\n", distance, from, to);
+        }
+    }
+    Ok(output)
+}
+
+fn bench_command(args: &[String]) -> Result<String, String> {
+    let maze = load(args)?;
+    let runs = option(args, "--runs").map(number).transpose()?.unwrap_or(10).max(1);
+    let mut output = format!("{:<17} {:>8} {:>10} {:>12}\n", "algorithm", "moves", "expanded", "ms per run");
+    for algorithm in ALGORITHMS {
+        let began = Instant::now();
+        let mut found = Ok(None);
+        for _ in 0..runs {
+            found = solve(&maze, algorithm);
+        }
+        let ms = began.elapsed().as_secs_f64() * 1000.0 / runs as f64;
+        // a solver that cannot run on this maze says why instead of stopping the others
+        output += &match found {
+            Ok(Some((expanded, path))) => format!("{:<17} {:>8} {:>10} {:>12.3}\n", algorithm, path.len() - 1, expanded, ms),
+            Ok(None) => format!("{:<17} {:>8} {:>10} {:>12.3}\n", algorithm, "-", "-", ms),
+            Err(message) => format!("{:<17} {}\n", algorithm, message),
+        };
+    }
+    Ok(output)
+}
@@ -0,0 +1,146 @@
+use wasm_bindgen::prelude::*;
+use rand::Rng;
+use crate::maze::MazeState;
+use crate::maze::pathfinding::cooperative::{first_conflict, is_taken, plan_whca, Agent};
+
+// several computer agents moving at the same time, each with its own goal and colour,
+// planned together so that they never bump into each other
+
+const AGENT_COLORS: [&str; 8] = ["crimson", "royalblue", "darkorange", "mediumseagreen", "purple", "teal", "goldenrod", "deeppink"];
+
+
+#[wasm_bindgen]
+impl MazeState {
+
+    // adds an agent walking from (start_x, start_y) to (goal_x, goal_y), returns its number
+    pub fn add_agent(&mut self, start_x: usize, start_y: usize, goal_x: usize, goal_y: usize, color: &str) -> Result<usize, JsValue> {
+        let start = (start_x, start_y);
+        let goal = (goal_x, goal_y);
+        for (x, y) in [start, goal] {
+            if !self.maze_grid.is_path((x, y)) {
+                return Err(JsValue::from_str("Agents must start and end on open cells!"));
+            }
+        }
+        if is_taken(&self.agents, start, goal) {
+            return Err(JsValue::from_str("Another agent already starts or ends there!"));
+        }
+
+        self.agents.push(Agent { start, goal, color: color.to_string() });
+        self.fill_cell(start.0, start.1, color);
+        self.draw_agent_goals();
+        Ok(self.agents.len() - 1)
+    }
+
+    // adds `count` agents with random starts and goals, coloured from a fixed palette
+    pub fn add_random_agents(&mut self, count: usize) -> Result<usize, JsValue> {
+        let mut rng = rand::thread_rng();
+        for _ in 0..count {
+            let taken: Vec<(usize, usize)> = self.agents.iter().flat_map(|agent| [agent.start, agent.goal]).collect();
+            let mut pick = || -> Result<(usize, usize), JsValue> {
+                for _ in 0..50 {
+                    let x = rng.gen_range(0..self.maze_grid.width());
+                    let y = rng.gen_range(0..self.maze_grid.height());
+                    if self.maze_grid.is_path((x, y)) && !taken.contains(&(x, y)) {
+                        return Ok((x, y));
+                    }
+                }
+                Err(JsValue::from_str("Failed to find a suitable position!"))
+            };
+            let start = pick()?;
+            let goal = pick()?;
+            let color = AGENT_COLORS[self.agents.len() % AGENT_COLORS.len()];
+            self.add_agent(start.0, start.1, goal.0, goal.1, color)?;
+        }
+        Ok(self.agents.len())
+    }
+
+    pub fn clear_agents(&mut self) -> Result<(), JsValue> {
+        self.agents.clear();
+        self.agent_routes.clear();
+        self.clear_visualization()
+    }
+
+    // plans every agent together with windowed cooperative a* (`window` ticks of lookahead),
+    // then plays the plan back one tick at a time with all agents moving at once
+    pub async fn plan_agents(&mut self, window: usize, delay_ms: i32) -> Result<JsValue, JsValue> {
+        if self.agents.is_empty() {
+            return Err(JsValue::from_str("No agents placed!"));
+        }
+        let open_cells = self.maze_grid.open_cells().count();
+        let routes = plan_whca(&self.maze_grid, &self.links, &self.agents, window, open_cells * 4)
+            .map_err(|message| JsValue::from_str(&message))?;
+        let conflict = first_conflict(&routes);
+        self.agent_routes = routes;
+
+        self.clear_visualization()?;
+        let ticks = self.agent_routes.iter().map(|route| route.len()).max().unwrap_or(1);
+        for tick in 0..ticks {
+            for index in 0..self.agents.len() {
+                let route = &self.agent_routes[index];
+                let cell = route[tick.min(route.len() - 1)];
+                if tick > 0 {
+                    // leave a faint trail behind in the agent's colour
+                    let previous = route[(tick - 1).min(route.len() - 1)];
+                    if previous != cell {
+                        self.set_fill_alpha(0.35);
+                        let color = self.agents[index].color.clone();
+                        self.fill_cell(previous.0, previous.1, &color);
+                        self.set_fill_alpha(1.0);
+                    }
+                }
+                let color = self.agents[index].color.clone();
+                self.fill_cell(cell.0, cell.1, &color);
+            }
+            self.draw_agent_goals();
+            if delay_ms > 0 {
+                crate::maze::sleep(delay_ms).await?;
+            }
+        }
+
+        let total: usize = self.agent_routes.iter()
+            .map(|route| route.windows(2).filter(|pair| pair[0] != pair[1]).count())
+            .sum();
+        match conflict {
+            Some(tick) => Ok(JsValue::from_str(&format!("Agents collided at tick {}! --> the planner boxed an agent in", tick))),
+            None => Ok(JsValue::from_str(&format!("Path found! --> {} agents, {} ticks, {} moves in total", self.agents.len(), ticks - 1, total))),
+        }
+    }
+
+    // the planned positions tick by tick: [tick][agent][x, y] flattened, one route per agent
+    // padded to the same length, so index = (tick * agents + agent) * 2
+    pub fn get_agent_routes(&self) -> Vec<usize> {
+        let ticks = self.agent_routes.iter().map(|route| route.len()).max().unwrap_or(0);
+        let mut flat = Vec::with_capacity(ticks * self.agent_routes.len() * 2);
+        for tick in 0..ticks {
+            for route in self.agent_routes.iter() {
+                let (x, y) = route[tick.min(route.len() - 1)];
+                flat.push(x);
+                flat.push(y);
+            }
+        }
+        flat
+    }
+}
+
+
+impl MazeState {
+    // every agent on its start cell, with its goal marked
+    pub(super) fn draw_agents(&mut self) {
+        for index in 0..self.agents.len() {
+            let (x, y) = self.agents[index].start;
+            let color = self.agents[index].color.clone();
+            self.fill_cell(x, y, &color);
+        }
+        self.draw_agent_goals();
+    }
+
+    // each agent's goal as an outlined cell in its colour
+    fn draw_agent_goals(&mut self) {
+        self.pen().set_line_width((self.block_size / 4.0).max(1.0));
+        for index in 0..self.agents.len() {
+            let (x, y) = self.agents[index].goal;
+            self.pen().set_stroke_style_str(&self.agents[index].color);
+            self.outline_cell(x, y);
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use wasm_bindgen::prelude::*;
+use crate::maze::MazeState;
+use crate::maze::pathfinding::hierarchical::Hierarchy;
+
+// the cluster graph of the hierarchical solver: its size, and an overlay showing the
+// clusters, their entrances and the abstract edges between them
+
+
+#[wasm_bindgen]
+impl MazeState {
+
+    // clusters of `size` x `size` cells, the graph is built again on the next search
+    pub fn set_cluster_size(&mut self, size: usize) -> Result<(), JsValue> {
+        if size < 2 {
+            return Err(JsValue::from_str("Clusters need to be at least 2 cells across!"));
+        }
+        self.hierarchy = Hierarchy::new(size);
+        Ok(())
+    }
+
+    pub fn get_cluster_size(&self) -> usize {
+        self.hierarchy.size
+    }
+
+    // shades every other cluster, marks the entrances and draws the abstract edges,
+    // returns [clusters rebuilt to draw it, entrances, edges]
+    pub fn draw_clusters(&mut self) -> Vec<usize> {
+        let rebuilt = self.sync_hierarchy();
+        let (cluster_color, entrance_color) = (self.theme.cluster.clone(), self.theme.entrance.clone());
+
+        self.set_fill_alpha(0.15);
+        for y in 0..self.maze_grid.height() {
+            for x in 0..self.maze_grid.width() {
+                let (cx, cy) = self.hierarchy.cluster_of((x, y));
+                if (cx + cy).is_multiple_of(2) {
+                    self.fill_cell(x, y, &cluster_color);
+                }
+            }
+        }
+        self.set_fill_alpha(1.0);
+
+        let mut edges: Vec<((usize, usize), (usize, usize))> = self.hierarchy.nodes()
+            .flat_map(|(from, out)| out.iter().map(move |&(to, _)| (from, to)))
+            .filter(|(from, to)| from < to) // every edge is there both ways
+            .collect();
+        edges.sort();
+        self.pen().set_stroke_style_str(&cluster_color);
+        self.pen().set_line_width((self.block_size / 8.0).max(1.0));
+        for &(from, to) in edges.iter() {
+            let (x0, y0) = self.cell_centre(from.0, from.1);
+            let (x1, y1) = self.cell_centre(to.0, to.1);
+            self.pen().begin_path();
+            self.pen().move_to(x0, y0);
+            self.pen().line_to(x1, y1);
+            self.context_over(&[(x0, y0), (x1, y1)], self.block_size / 2.0).stroke();
+        }
+
+        let mut entrances: Vec<(usize, usize)> = self.hierarchy.nodes().map(|(entrance, _)| entrance).collect();
+        entrances.sort();
+        for &(x, y) in entrances.iter() {
+            self.fill_cell(x, y, &entrance_color);
+        }
+        vec![rebuilt, entrances.len(), edges.len()]
+    }
+}
+
+
+impl MazeState {
+    // the graph follows the grid as it is now, see Hierarchy::sync
+    pub(super) fn sync_hierarchy(&mut self) -> usize {
+        self.hierarchy.sync(&self.maze_grid, &self.links)
+    }
+}
@@ -0,0 +1,103 @@
+use wasm_bindgen::prelude::*;
+use crate::maze::MazeState;
+use crate::maze::pathfinding::UNREACHABLE;
+
+// the all pairs distances of the maze on the canvas, for level design: where its centre is,
+// which two cells are furthest apart, and how far every cell is from the one furthest from it.
+// the table is kept in the state and only worked out again after the maze changes. it grows
+// with the square of the open cells, so it is refused for big mazes
+
+
+#[wasm_bindgen]
+impl MazeState {
+
+    // the open cells as cell indices (y * width + x over the widest row), in the order of the
+    // rows and columns of all_pairs_distances
+    pub fn distance_cells(&mut self) -> Result<Vec<u32>, JsValue> {
+        self.sync_distances()?;
+        let width = self.distances.width();
+        Ok(self.distances.cells.iter().map(|&(x, y)| (y * width + x) as u32).collect())
+    }
+
+    // the distance from every open cell to every other one, a row per cell in the order of
+    // distance_cells, 4294967295 (u32::MAX) between cells that cannot reach each other.
+    // js sees a Uint32Array. this copies the whole table out of wasm memory, up to 16 MB at
+    // MAX_OPEN_CELLS; distances_from_cell copies a single row
+    pub fn all_pairs_distances(&mut self) -> Result<Vec<u32>, JsValue> {
+        self.sync_distances()?;
+        Ok(self.distances.table.clone())
+    }
+
+    // one row of all_pairs_distances: from (x, y) to every cell of distance_cells
+    pub fn distances_from_cell(&mut self, x: usize, y: usize) -> Result<Vec<u32>, JsValue> {
+        self.sync_distances()?;
+        let slot = self.distances.slot((x, y)).ok_or_else(|| JsValue::from_str("Not an open cell!"))?;
+        Ok(self.distances.row(slot).to_vec())
+    }
+
+    // the eccentricity of every cell, row by row over the widest row, 4294967295 on walls
+    pub fn eccentricities(&mut self) -> Result<Vec<u32>, JsValue> {
+        self.sync_distances()?;
+        let mut flat = vec![UNREACHABLE; self.maze_grid.cell_count()];
+        for (&cell, &eccentricity) in self.distances.cells.iter().zip(self.distances.eccentricity.iter()) {
+            flat[self.maze_grid.index(cell)] = eccentricity;
+        }
+        Ok(flat)
+    }
+
+    // the centre cells as [x0, y0, x1, y1, ...], the cells of least eccentricity in the
+    // largest connected region
+    pub fn maze_centre(&mut self) -> Result<Vec<usize>, JsValue> {
+        self.sync_distances()?;
+        Ok(self.distances.centre().into_iter().flat_map(|(x, y)| [x, y]).collect())
+    }
+
+    // [x1, y1, x2, y2, distance] for the two cells furthest apart, empty for a maze without
+    // open cells
+    pub fn maze_diameter(&mut self) -> Result<Vec<usize>, JsValue> {
+        self.sync_distances()?;
+        Ok(self.distances.diameter()
+            .map(|((x1, y1), (x2, y2), distance)| vec![x1, y1, x2, y2, distance as usize])
+            .unwrap_or_default())
+    }
+
+    // paints every open cell by its eccentricity, hot colours at the centre fading to cold ones
+    // at the edges of the maze, with the centre and the diameter endpoints in the path colour.
+    // the scale runs over the largest region, like the centre, so a cell walled in on its own
+    // (eccentricity 0) does not squash it; cells of smaller regions are clamped onto it
+    pub fn draw_eccentricity_heatmap(&mut self) -> Result<(), JsValue> {
+        self.sync_distances()?;
+        let eccentricity = &self.distances.eccentricity;
+        let in_largest = self.distances.in_largest_region();
+        let scaled = || eccentricity.iter().zip(in_largest.iter()).filter(|&(_, &inside)| inside).map(|(&value, _)| value);
+        let least = scaled().min().unwrap_or(0);
+        let spread = (scaled().max().unwrap_or(0) - least).max(1);
+        let mut colors: Vec<((usize, usize), String)> = self.distances.cells.iter().zip(eccentricity.iter())
+            .map(|(&cell, &value)| {
+                let hue = 240.0 * (value.clamp(least, least + spread) - least) as f64 / spread as f64;
+                (cell, format!("hsl({:.0}, 85%, 55%)", hue))
+            })
+            .collect();
+        let marked = self.distances.centre().into_iter()
+            .chain(self.distances.diameter().into_iter().flat_map(|(from, to, _)| [from, to]));
+        colors.extend(marked.map(|cell| (cell, self.theme.final_path.clone())));
+
+        self.draw_maze()?;
+        for ((x, y), color) in colors {
+            self.fill_cell(x, y, &color);
+        }
+        self.fill_cell(self.computer_player.0, self.computer_player.1, &self.theme.computer.clone());
+        self.fill_cell(self.target.0, self.target.1, &self.theme.target.clone());
+        Ok(())
+    }
+}
+
+
+impl MazeState {
+    // works the table out again if maze_grid changed since the last time
+    fn sync_distances(&mut self) -> Result<(), JsValue> {
+        self.distances.sync(&self.maze_grid, &self.links)
+            .map(|_| ())
+            .map_err(|message| JsValue::from_str(&message))
+    }
+}
@@ -0,0 +1,45 @@
+use wasm_bindgen::prelude::*;
+use crate::maze::MazeState;
+use crate::maze::pathfinding::evolution::{Challenge, Fitness, Population, Probe};
+
+// breeding a harder version of the current maze for one solver, see pathfinding::evolution
+
+
+#[wasm_bindgen]
+impl MazeState {
+
+    // evolves the maze for `generations` generations of `population` layouts, scoring each by how
+    // hard it is for `solver` ("BFS", "DFS", "AStar" or "BestFirst") to get from the computer player
+    // to the target, by "NodesExpanded" or "PathLength". the best layout found replaces the maze,
+    // drawn after every generation, and comes back as maze text
+    pub async fn evolve_maze(&mut self, solver: &str, fitness: &str, generations: usize, population: usize, delay_ms: i32) -> Result<JsValue, JsValue> {
+        let probe = Probe::from_name(solver).ok_or_else(|| JsValue::from_str("Invalid algorithm name!"))?;
+        let fitness = Fitness::from_name(fitness).ok_or_else(|| JsValue::from_str("Invalid fitness!"))?;
+        let challenge = Challenge { probe, fitness, start: self.computer_player, target: self.target };
+
+        let mut rng = rand::thread_rng();
+        let Some(mut population) = Population::seed(&self.original_maze_grid, &self.links, self.fixed_cells(), challenge, population, &mut rng) else {
+            return Ok(JsValue::from_str("Target not reachable!"));
+        };
+        self.evolution_history = vec![population.best().1];
+        for _ in 0..generations {
+            population.step(&mut rng);
+            self.evolution_history.push(population.best().1);
+            if delay_ms > 0 {
+                self.original_maze_grid = population.best().0.clone();
+                self.clear_visualization()?;
+                crate::maze::sleep(delay_ms).await?;
+            }
+        }
+
+        self.original_maze_grid = population.best().0.clone();
+        self.clear_visualization()?;
+        Ok(JsValue::from_str(&self.maze_text(&self.original_maze_grid)))
+    }
+
+    // the best fitness of every generation of the last evolve_maze, the starting maze first
+    pub fn get_evolution_history(&self) -> Vec<u32> {
+        self.evolution_history.iter().map(|&fitness| fitness as u32).collect()
+    }
+}
+
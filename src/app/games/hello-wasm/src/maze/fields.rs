@@ -0,0 +1,169 @@
+use wasm_bindgen::prelude::*;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use crate::maze::MazeState;
+use crate::maze::pathfinding::{Links, UNREACHABLE};
+use crate::maze::pathfinding::grid::Grid;
+
+// whole-maze fields towards one goal: a dijkstra distance map, and the flow field derived from
+// it (the best next step from every cell). one search serves any number of agents heading
+// for the same goal, instead of one a* per agent
+
+
+// dijkstra from the goal outwards, the cost of every cell (by grid index) to reach it.
+// portal links work both ways, so searching backwards from the goal over them is safe
+pub fn distance_field(grid: &Grid, links: &Links, goal: (usize, usize)) -> Vec<u32> {
+    let mut distance = vec![UNREACHABLE; grid.cell_count()];
+    let mut pq = BinaryHeap::new();
+    distance[grid.index(goal)] = 0;
+    pq.push(Reverse((0, grid.index(goal))));
+
+    while let Some(Reverse((cost, current))) = pq.pop() {
+        if cost > distance[current] {
+            continue;
+        }
+        for (next, step_cost) in grid.neighbours(links, current) {
+            let next_cost = cost + step_cost as u32;
+            if next_cost < distance[next] {
+                distance[next] = next_cost;
+                pq.push(Reverse((next_cost, next)));
+            }
+        }
+    }
+    distance
+}
+
+
+// for every cell the grid index of the neighbour that is closest to the goal (counting the
+// cost of getting there), None on the goal itself, on walls and where the goal cannot be reached
+pub fn flow_field(grid: &Grid, links: &Links, distance: &[u32]) -> Vec<Option<usize>> {
+    distance.iter().enumerate()
+        .map(|(index, &cost)| {
+            if cost == 0 || cost == UNREACHABLE {
+                return None;
+            }
+            grid.neighbours(links, index)
+                .min_by_key(|&(next, step_cost)| distance[next].saturating_add(step_cost as u32))
+                .map(|(next, _)| next)
+        })
+        .collect()
+}
+
+
+#[wasm_bindgen]
+impl MazeState {
+
+    // distance from every cell to (target_x, target_y), row by row, 4294967295 (u32::MAX)
+    // on walls and cells that cannot reach it. js sees a Uint32Array
+    pub fn compute_distance_field(&self, target_x: usize, target_y: usize) -> Result<Vec<u32>, JsValue> {
+        let goal = self.field_goal(target_x, target_y)?;
+        Ok(distance_field(&self.maze_grid, &self.links, goal))
+    }
+
+    // the best next step from every cell towards (target_x, target_y) as a cell index
+    // (y * width + x), row by row, -1 where there is none. js sees an Int32Array
+    pub fn compute_flow_field(&self, target_x: usize, target_y: usize) -> Result<Vec<i32>, JsValue> {
+        let goal = self.field_goal(target_x, target_y)?;
+        let distance = distance_field(&self.maze_grid, &self.links, goal);
+        Ok(flow_field(&self.maze_grid, &self.links, &distance)
+            .into_iter()
+            .map(|next| next.map_or(-1, |index| index as i32))
+            .collect())
+    }
+
+    // paints every reachable cell by its distance to (target_x, target_y),
+    // hot colours close to it fading to cold ones far away
+    pub fn draw_distance_heatmap(&mut self, target_x: usize, target_y: usize) -> Result<(), JsValue> {
+        let goal = self.field_goal(target_x, target_y)?;
+        let distance = distance_field(&self.maze_grid, &self.links, goal);
+        let furthest = distance.iter().filter(|&&cost| cost != UNREACHABLE).max().copied().unwrap_or(0).max(1);
+
+        self.draw_maze()?;
+        for (index, &cost) in distance.iter().enumerate() {
+            if cost != UNREACHABLE {
+                let (x, y) = self.maze_grid.cell(index);
+                let hue = 240.0 * cost as f64 / furthest as f64;
+                self.fill_cell(x, y, &format!("hsl({:.0}, 85%, 55%)", hue));
+            }
+        }
+        self.fill_cell(goal.0, goal.1, &self.theme.target.clone());
+        Ok(())
+    }
+}
+
+
+impl MazeState {
+    fn field_goal(&self, x: usize, y: usize) -> Result<(usize, usize), JsValue> {
+        if !self.maze_grid.is_path((x, y)) {
+            return Err(JsValue::from_str("The goal must be an open cell!"));
+        }
+        Ok((x, y))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maze::read_maze;
+    use crate::maze::pathfinding::bfs::distances_from;
+    use crate::maze::pathfinding::generate::generate;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    // every arrow leads to a neighbour exactly one move's cost closer to the goal
+    fn assert_downhill(grid: &Grid, links: &Links, goal: (usize, usize)) {
+        let distance = distance_field(grid, links, goal);
+        let flow = flow_field(grid, links, &distance);
+        for (index, next) in flow.iter().enumerate() {
+            let cost = distance[index];
+            match next {
+                None => assert!(cost == 0 || cost == UNREACHABLE, "no arrow at {:?}", grid.cell(index)),
+                Some(next) => {
+                    let step = grid.neighbours(links, index).find(|&(cell, _)| cell == *next).map(|(_, cost)| cost as u32);
+                    assert_eq!(step.map(|step| distance[*next] + step), Some(cost), "arrow at {:?}", grid.cell(index));
+                },
+            }
+        }
+    }
+
+    #[test]
+    fn flow_points_downhill() {
+        for seed in 0..4 {
+            let grid = generate(17, 13, 25, &mut StdRng::seed_from_u64(seed));
+            assert_downhill(&grid, &Links::default(), (1, 1));
+            assert_downhill(&grid, &Links::default(), (15, 11));
+        }
+    }
+
+    #[test]
+    fn flow_takes_a_portal_only_when_it_pays() {
+        let mut file = read_maze("\
+*********
+*1     1*
+*********
+").expect("valid maze text");
+        file.links.portals.cost = 3;
+        let distance = distance_field(&file.grid, &file.links, (7, 1));
+        assert_eq!(distance[file.grid.index((1, 1))], 3);
+        assert_eq!(distance[file.grid.index((2, 1))], 4);
+        assert_eq!(distance[file.grid.index((4, 1))], 3);
+        assert_downhill(&file.grid, &file.links, (7, 1));
+        let flow = flow_field(&file.grid, &file.links, &distance);
+        assert_eq!(flow[file.grid.index((1, 1))], Some(file.grid.index((7, 1))));
+        // from (2, 1) the jump is a move cheaper than walking, from (3, 1) it is not
+        assert_eq!(flow[file.grid.index((2, 1))], Some(file.grid.index((1, 1))));
+        assert_eq!(flow[file.grid.index((3, 1))], Some(file.grid.index((4, 1))));
+    }
+
+    #[test]
+    fn distances_match_breadth_first_without_portals() {
+        let grid = generate(17, 13, 25, &mut StdRng::seed_from_u64(9));
+        let field = distance_field(&grid, &Links::default(), (1, 1));
+        let bfs = distances_from(&grid, &Links::default(), (1, 1));
+        for index in 0..grid.cell_count() {
+            let expected = if bfs[index] == usize::MAX { UNREACHABLE } else { bfs[index] as u32 };
+            assert_eq!(field[index], expected);
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use wasm_bindgen::prelude::*;
+use std::collections::HashMap;
+use crate::maze::MazeState;
+use crate::maze::framebuffer::{parse_color, Pixels};
+use crate::maze::pathfinding::topology::Topology;
+use crate::maze::pathfinding::trace::Event;
+use crate::maze::theme::Theme;
+
+// the maze as a standalone picture for slides and write-ups: an SVG of one shape per cell,
+// or a PNG drawn off the canvas at any cell size. both show the walls, the players and,
+// if asked, the cells the last search visited and the path it found
+
+// the most pixels a PNG may have, 16 MB of RGBA
+const MAX_PNG_PIXELS: f64 = 4194304.0;
+
+
+// every cell that is drawn and its colour, later cells on top
+pub struct Figure {
+    pub topology: Topology,
+    pub size: f64, // the cell size
+    pub width: f64,
+    pub height: f64,
+    pub cells: Vec<((usize, usize), String)>,
+}
+
+impl Figure {
+    pub fn to_svg(&self) -> String {
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" shape-rendering=\"crispEdges\">\n",
+            w = number(self.width), h = number(self.height),
+        );
+        for &((x, y), ref color) in self.cells.iter() {
+            let color = escape(color);
+            if self.topology == Topology::Square {
+                svg += &format!(
+                    "<rect x=\"{}\" y=\"{}\" width=\"{s}\" height=\"{s}\" fill=\"{}\"/>\n",
+                    number(x as f64 * self.size), number(y as f64 * self.size), color, s = number(self.size),
+                );
+            } else {
+                let points: Vec<String> = self.topology.corners((x, y), self.size).iter()
+                    .map(|&(px, py)| format!("{},{}", number(px), number(py)))
+                    .collect();
+                svg += &format!("<polygon points=\"{}\" fill=\"{}\"/>\n", points.join(" "), color);
+            }
+        }
+        svg + "</svg>\n"
+    }
+
+    // an error names the first colour that cannot be drawn off the canvas
+    pub fn to_png(&self) -> Result<Vec<u8>, String> {
+        if self.width.ceil() * self.height.ceil() > MAX_PNG_PIXELS {
+            return Err("The figure is too large for a PNG!".to_string());
+        }
+        let (width, height) = (self.width.ceil() as usize, self.height.ceil() as usize);
+        let mut pixels = Pixels::new(width, height);
+        for ((x, y), color) in self.cells.iter() {
+            let rgba = parse_color(color).ok_or_else(|| format!("Unknown colour {}!", color))?;
+            pixels.fill_polygon(&self.topology.corners((*x, *y), self.size), rgba, 1.0, false);
+        }
+        Ok(crate::maze::png::encode(width as u32, height as u32, &pixels.into_data()))
+    }
+}
+
+// a colour as an attribute value, whatever the theme has in it
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+// at most two decimals and no trailing zeros
+fn number(value: f64) -> String {
+    format!("{}", (value * 100.0).round() / 100.0 + 0.0) // + 0.0 turns -0 into 0
+}
+
+
+#[wasm_bindgen]
+impl MazeState {
+
+    // `theme` is "Light", "Dark", or "" for the theme the canvas is drawn in
+    pub fn export_svg(&self, cell_size: f64, theme: &str, show_path: bool, show_visited: bool) -> Result<String, JsValue> {
+        Ok(self.figure(cell_size, theme, show_path, show_visited)?.to_svg())
+    }
+
+    // the same as PNG file bytes
+    pub fn export_png(&self, cell_size: f64, theme: &str, show_path: bool, show_visited: bool) -> Result<Vec<u8>, JsValue> {
+        self.figure(cell_size, theme, show_path, show_visited)?
+            .to_png()
+            .map_err(|message| JsValue::from_str(&message))
+    }
+}
+
+
+impl MazeState {
+    fn figure(&self, cell_size: f64, theme: &str, show_path: bool, show_visited: bool) -> Result<Figure, JsValue> {
+        if !(cell_size > 0.0 && cell_size.is_finite()) {
+            return Err(JsValue::from_str("Invalid cell size!"));
+        }
+        let theme = match theme {
+            "" => self.theme.clone(),
+            name => Theme::from_name(name).ok_or_else(|| JsValue::from_str("Unknown theme!"))?,
+        };
+        let grid = &self.original_maze_grid;
+        let (width, height) = self.links.topology.canvas_size(grid.width(), grid.height(), cell_size);
+
+        // one colour per cell, each layer painting over the one before
+        let mut colors: HashMap<(usize, usize), String> = HashMap::new();
+        let mut order = Vec::new();
+        for y in 0..grid.height() {
+            for x in 0..grid.width() {
+                let color = if grid.is_path((x, y)) { &theme.path } else { &theme.wall };
+                order.push((x, y));
+                colors.insert((x, y), color.clone());
+            }
+        }
+        let open = |cell: (usize, usize)| grid.is_path(cell);
+        if show_visited {
+            for event in self.trace.events.iter() {
+                if let Event::Visit(cell, _) = event && open(*cell) {
+                    colors.insert(*cell, theme.visited.clone());
+                }
+            }
+        }
+        if show_path {
+            for &cell in self.last_path.iter() {
+                if open(cell) {
+                    colors.insert(cell, theme.final_path.clone());
+                }
+            }
+        }
+        colors.insert(self.computer_player, theme.computer.clone());
+        colors.insert(self.target, theme.target.clone());
+
+        let cells = order.into_iter()
+            .filter_map(|cell| colors.remove(&cell).map(|color| (cell, color)))
+            .filter(|(_, color)| color != "transparent")
+            .collect();
+        Ok(Figure { topology: self.links.topology, size: cell_size, width, height, cells })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn figure(width: f64, height: f64, color: &str) -> Figure {
+        Figure { topology: Topology::Square, size: 10.0, width, height, cells: vec![((0, 0), color.to_string())] }
+    }
+
+    #[test]
+    fn svg_colours_are_escaped() {
+        let svg = figure(10.0, 10.0, "red\"/><script>&").to_svg();
+        assert!(svg.contains("fill=\"red&quot;/&gt;&lt;script&gt;&amp;\""), "{}", svg);
+        assert!(!svg.contains("<script"));
+    }
+
+    #[test]
+    fn png_size_is_capped_by_pixels() {
+        assert!(figure(2048.0, 2048.0, "red").to_png().is_ok());
+        assert!(figure(2048.0, 2049.0, "red").to_png().is_err());
+        // one long side is fine as long as the area is small
+        assert!(figure(20000.0, 10.0, "red").to_png().is_ok());
+    }
+}
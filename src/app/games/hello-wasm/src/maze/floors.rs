@@ -0,0 +1,54 @@
+use wasm_bindgen::prelude::*;
+use crate::maze::MazeState;
+
+// multi-storey mazes: the floors are drawn side by side, left to right from the ground floor,
+// with the stairs marked and every floor change of a path joined by an arc
+
+
+#[wasm_bindgen]
+impl MazeState {
+
+    pub fn get_floor_count(&self) -> usize {
+        self.links.floors.count
+    }
+
+    // how many columns apart the floors are drawn, the cell (x, y) of floor z being at (x + z * stride, y)
+    pub fn get_floor_stride(&self) -> usize {
+        self.links.floors.stride
+    }
+
+    // [x, y, floor] of a grid cell, for showing where the players are
+    pub fn locate_cell(&self, x: usize, y: usize) -> Vec<usize> {
+        let (x, y, z) = self.links.floors.locate((x, y));
+        vec![x, y, z]
+    }
+}
+
+
+impl MazeState {
+    pub(super) fn draw_stairs(&mut self) {
+        let mut stairs: Vec<((usize, usize), (usize, usize))> = self.links.floors.stairs.iter().map(|(&from, &to)| (from, to)).collect();
+        stairs.sort();
+        for (from, to) in stairs {
+            self.draw_stair(from, to);
+        }
+    }
+
+    pub(super) fn draw_stair(&mut self, (x, y): (usize, usize), other_end: (usize, usize)) {
+        let label = if other_end.0 > x { "▲" } else { "▼" };
+        self.label_cell(x, y, &self.theme.stair.clone(), label, &self.theme.light_ink.clone());
+    }
+
+    // the same arc as a portal jump, in the stair colour, wherever the path takes the stairs
+    pub(super) fn draw_floor_changes(&mut self, path: &[(usize, usize)]) {
+        for pair in path.windows(2) {
+            let (from, to) = (pair[0], pair[1]);
+            if self.links.floors.partner(from) != Some(to) {
+                continue;
+            }
+            self.draw_stair(from, to);
+            self.draw_stair(to, from);
+            self.draw_arc(from, to, &self.theme.stair.clone());
+        }
+    }
+}
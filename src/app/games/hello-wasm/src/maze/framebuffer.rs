@@ -0,0 +1,441 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::{Clamped, JsCast};
+use web_sys::{CanvasRenderingContext2d, ImageData};
+use crate::maze::MazeState;
+use crate::maze::pathfinding::topology::contains;
+
+// drawing cells into an RGBA buffer in wasm memory instead of one canvas call per cell.
+// the buffer mirrors the canvas pixel for pixel; a fill only writes the buffer and grows the
+// dirty rectangle, which reaches the canvas with a single putImageData on the next animation
+// frame, or straight away before anything is drawn on the canvas directly (labels, arcs,
+// outlines, which all go through context_over()). such a direct draw leaves the part of the
+// buffer it touched stale, and only that part is read back from the canvas before the next
+// fill, so a dirty rectangle never carries old pixels over a label.
+// js can also blit the buffer itself: get_framebuffer is a view of it in wasm memory, and
+// take_dirty_rect hands over the rectangle the next present would have copied
+
+
+// the rectangle [x0, x1) x [y0, y1) in canvas pixels
+type Rect = (usize, usize, usize, usize);
+
+
+pub struct Pixels {
+    width: usize,
+    height: usize,
+    data: Vec<u8>,
+    dirty: Option<Rect>,
+    stale: Vec<Rect>, // where the canvas has been drawn on since the buffer last matched it
+    scheduled: bool,  // an animation frame will present the dirty rectangle
+}
+
+impl Pixels {
+    // a blank image, for drawing off the canvas
+    pub fn new(width: usize, height: usize) -> Pixels {
+        let mut pixels = Pixels { width: 0, height: 0, data: Vec::new(), dirty: None, stale: Vec::new(), scheduled: false };
+        pixels.resize(width, height);
+        pixels
+    }
+
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    fn resize(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+        self.data = vec![0; width * height * 4];
+        self.dirty = None;
+        self.stale.clear();
+    }
+
+    // the pixels a direct draw between the points (and `margin` pixels round them) can reach
+    fn area(&self, points: &[(f64, f64)], margin: f64) -> Option<Rect> {
+        let left = points.iter().map(|point| point.0).fold(f64::INFINITY, f64::min) - margin;
+        let right = points.iter().map(|point| point.0).fold(f64::NEG_INFINITY, f64::max) + margin;
+        let top = points.iter().map(|point| point.1).fold(f64::INFINITY, f64::min) - margin;
+        let bottom = points.iter().map(|point| point.1).fold(f64::NEG_INFINITY, f64::max) + margin;
+        let (x0, y0) = (left.floor().max(0.0) as usize, top.floor().max(0.0) as usize);
+        let (x1, y1) = ((right.ceil().max(0.0) as usize).min(self.width), (bottom.ceil().max(0.0) as usize).min(self.height));
+        (x0 < x1 && y0 < y1).then_some((x0, y0, x1, y1))
+    }
+
+    // every pixel whose centre is inside the outline, blended over what is there or, with
+    // `replace`, written as it is (alpha included, which is how a cell is cleared)
+    pub fn fill_polygon(&mut self, corners: &[(f64, f64)], rgba: [u8; 4], alpha: f64, replace: bool) {
+        let left = corners.iter().map(|corner| corner.0).fold(f64::INFINITY, f64::min).floor().max(0.0) as usize;
+        let right = (corners.iter().map(|corner| corner.0).fold(f64::NEG_INFINITY, f64::max).ceil().max(0.0) as usize).min(self.width);
+        let top = corners.iter().map(|corner| corner.1).fold(f64::INFINITY, f64::min).floor().max(0.0) as usize;
+        let bottom = (corners.iter().map(|corner| corner.1).fold(f64::NEG_INFINITY, f64::max).ceil().max(0.0) as usize).min(self.height);
+        if left >= right || top >= bottom {
+            return;
+        }
+
+        let source = rgba[3] as f64 / 255.0 * alpha;
+        for y in top..bottom {
+            for x in left..right {
+                if !contains(corners, (x as f64 + 0.5, y as f64 + 0.5)) {
+                    continue;
+                }
+                let at = (y * self.width + x) * 4;
+                let pixel = &mut self.data[at..at + 4];
+                if replace {
+                    pixel.copy_from_slice(&[rgba[0], rgba[1], rgba[2], (source * 255.0).round() as u8]);
+                    continue;
+                }
+                // source over, on colours that are not premultiplied
+                let below = pixel[3] as f64 / 255.0;
+                let out = source + below * (1.0 - source);
+                if out > 0.0 {
+                    for channel in 0..3 {
+                        let blended = (rgba[channel] as f64 * source + pixel[channel] as f64 * below * (1.0 - source)) / out;
+                        pixel[channel] = blended.round() as u8;
+                    }
+                }
+                pixel[3] = (out * 255.0).round() as u8;
+            }
+        }
+
+        self.dirty = Some(match self.dirty {
+            Some((x0, y0, x1, y1)) => (x0.min(left), y0.min(top), x1.max(right), y1.max(bottom)),
+            None => (left, top, right, bottom),
+        });
+    }
+
+    // the pixels changed since the dirty rectangle was last taken
+    fn take_dirty(&mut self) -> Option<Rect> {
+        self.dirty.take()
+    }
+
+    // copies the dirty rectangle onto the canvas
+    fn present(&mut self, context: &CanvasRenderingContext2d) {
+        let Some((x0, y0, x1, y1)) = self.take_dirty() else {
+            return;
+        };
+        let mut rows = Vec::with_capacity((x1 - x0) * (y1 - y0) * 4);
+        for y in y0..y1 {
+            rows.extend_from_slice(&self.data[(y * self.width + x0) * 4..(y * self.width + x1) * 4]);
+        }
+        if let Ok(image) = ImageData::new_with_u8_clamped_array_and_sh(Clamped(&rows), (x1 - x0) as u32, (y1 - y0) as u32) {
+            let _ = context.put_image_data(&image, x0 as f64, y0 as f64);
+        }
+    }
+
+    // reads what was drawn on the canvas directly back into the buffer
+    fn pull(&mut self, context: &CanvasRenderingContext2d) {
+        for (x0, y0, x1, y1) in std::mem::take(&mut self.stale) {
+            let Ok(image) = context.get_image_data(x0 as f64, y0 as f64, (x1 - x0) as f64, (y1 - y0) as f64) else {
+                continue;
+            };
+            let data = image.data().0;
+            let stride = (x1 - x0) * 4;
+            if data.len() != stride * (y1 - y0) {
+                continue;
+            }
+            for (row, y) in (y0..y1).enumerate() {
+                let at = (y * self.width + x0) * 4;
+                self.data[at..at + stride].copy_from_slice(&data[row * stride..(row + 1) * stride]);
+            }
+        }
+    }
+}
+
+
+// the buffer is shared with the animation frame callback that presents it
+#[derive(Clone)]
+pub struct Framebuffer {
+    pub enabled: bool,
+    pub alpha: f64, // the canvas global alpha, applied to buffered fills the same way
+    pixels: Rc<RefCell<Pixels>>,
+}
+
+impl Default for Framebuffer {
+    fn default() -> Self {
+        let pixels = Pixels { width: 0, height: 0, data: Vec::new(), dirty: None, stale: Vec::new(), scheduled: false };
+        Framebuffer { enabled: false, alpha: 1.0, pixels: Rc::new(RefCell::new(pixels)) }
+    }
+}
+
+
+#[wasm_bindgen]
+impl MazeState {
+
+    // "Canvas" draws every cell with its own canvas call, "Framebuffer" through the pixel buffer
+    pub fn set_render_mode(&mut self, mode: &str) -> Result<(), JsValue> {
+        self.framebuffer.enabled = match mode {
+            "Canvas" => false,
+            "Framebuffer" => true,
+            _ => return Err(JsValue::from_str("Unknown render mode!")),
+        };
+        self.clear_visualization()
+    }
+
+    pub fn get_render_mode(&self) -> String {
+        if self.framebuffer.enabled { "Framebuffer" } else { "Canvas" }.to_string()
+    }
+
+    // the pixel buffer, RGBA row by row at the canvas size, as a view straight into wasm
+    // memory rather than a copy. it goes bad once wasm memory grows or the canvas is cleared,
+    // so take a fresh one for every blit
+    pub fn get_framebuffer(&self) -> js_sys::Uint8ClampedArray {
+        let pixels = self.framebuffer.pixels.borrow();
+        // safety: nothing in here allocates while the view is alive; js is told above not to keep it
+        unsafe { js_sys::Uint8ClampedArray::view(&pixels.data) }
+    }
+
+    // [x, y, width, height] of the pixels filled since the last present, [] when there are none.
+    // they are js's to put on the canvas from get_framebuffer now, the next present skips them
+    pub fn take_dirty_rect(&mut self) -> Vec<u32> {
+        match self.framebuffer.pixels.borrow_mut().take_dirty() {
+            Some((x0, y0, x1, y1)) => vec![x0 as u32, y0 as u32, (x1 - x0) as u32, (y1 - y0) as u32],
+            None => Vec::new(),
+        }
+    }
+}
+
+
+impl MazeState {
+    // the canvas for setting the pen, fill, font and path, which draws nothing by itself
+    pub(super) fn pen(&self) -> &CanvasRenderingContext2d {
+        &self.canvas_context
+    }
+
+    // the canvas for drawing on it directly between the points (in maze coordinates) and
+    // `margin` round them, with the buffered fills put on it first; only that part of the
+    // buffer goes stale
+    pub(super) fn context_over(&self, points: &[(f64, f64)], margin: f64) -> &CanvasRenderingContext2d {
+        if self.framebuffer.enabled {
+            let pixels_at: Vec<(f64, f64)> = points.iter().map(|&point| self.view.to_canvas(point)).collect();
+            let mut pixels = self.framebuffer.pixels.borrow_mut();
+            pixels.present(&self.canvas_context);
+            if let Some(area) = pixels.area(&pixels_at, margin * self.view.zoom) {
+                pixels.stale.push(area);
+            }
+        }
+        &self.canvas_context
+    }
+
+    // the same for drawing on one cell: its fill, outline and label stay within half a block of it
+    pub(super) fn cell_context(&self, x: usize, y: usize) -> &CanvasRenderingContext2d {
+        self.context_over(&self.links.topology.corners((x, y), self.block_size), self.block_size / 2.0)
+    }
+
+    // a blank buffer the size of the canvas, for when the canvas has just been cleared
+    pub(super) fn reset_framebuffer(&mut self) -> Result<(), JsValue> {
+        let canvas = self.canvas()?;
+        let mut pixels = self.framebuffer.pixels.borrow_mut();
+        pixels.resize(canvas.width() as usize, canvas.height() as usize);
+        Ok(())
+    }
+
+    // the canvas global alpha for the fills that follow, buffered or not
+    pub(super) fn set_fill_alpha(&mut self, alpha: f64) {
+        self.framebuffer.alpha = alpha;
+        self.canvas_context.set_global_alpha(alpha);
+    }
+
+    // fills the cell in the buffer, false if it has to be drawn on the canvas instead
+    // (buffering is off, or the colour is not one parse_color knows)
+    pub(super) fn buffer_cell(&mut self, x: usize, y: usize, color: &str, replace: bool) -> bool {
+        if !self.framebuffer.enabled {
+            return false;
+        }
+        let Some(rgba) = parse_color(color) else {
+            return false;
+        };
+        let corners: Vec<(f64, f64)> = self.links.topology.corners((x, y), self.block_size).iter()
+            .map(|&corner| self.view.to_canvas(corner))
+            .collect();
+
+        let mut pixels = self.framebuffer.pixels.borrow_mut();
+        if !pixels.stale.is_empty() {
+            pixels.pull(&self.canvas_context);
+        }
+        pixels.fill_polygon(&corners, rgba, self.framebuffer.alpha, replace);
+        if pixels.dirty.is_some() && !pixels.scheduled {
+            pixels.scheduled = self.schedule_present();
+        }
+        true
+    }
+
+    // presents the dirty rectangle on the next animation frame, false if it could not be asked for
+    fn schedule_present(&self) -> bool {
+        let Some(window) = web_sys::window() else {
+            return false;
+        };
+        let pixels = self.framebuffer.pixels.clone();
+        let context = self.canvas_context.clone();
+        let callback = Closure::once_into_js(move || {
+            let mut pixels = pixels.borrow_mut();
+            pixels.scheduled = false;
+            pixels.present(&context);
+        });
+        window.request_animation_frame(callback.unchecked_ref()).is_ok()
+    }
+}
+
+
+// the colours the maze draws with: "#rgb", "#rrggbb", "rgb(r, g, b)", "hsl(h, s%, l%)",
+// "transparent" and the named colours used across the game
+pub fn parse_color(color: &str) -> Option<[u8; 4]> {
+    let color = color.trim();
+    if let Some(hex) = color.strip_prefix('#') {
+        let digits: Vec<u8> = hex.chars().map(|digit| digit.to_digit(16).map(|value| value as u8)).collect::<Option<Vec<u8>>>()?;
+        return match digits.len() {
+            3 => Some([digits[0] * 17, digits[1] * 17, digits[2] * 17, 255]),
+            6 => Some([digits[0] * 16 + digits[1], digits[2] * 16 + digits[3], digits[4] * 16 + digits[5], 255]),
+            _ => None,
+        };
+    }
+    let arguments = |inner: &str| -> Option<Vec<f64>> {
+        inner.strip_suffix(')')?.split(',').map(|part| part.trim().trim_end_matches('%').parse::<f64>().ok()).collect()
+    };
+    if let Some(inner) = color.strip_prefix("rgb(") {
+        let values = arguments(inner)?;
+        return match values[..] {
+            [r, g, b] => Some([r.clamp(0.0, 255.0) as u8, g.clamp(0.0, 255.0) as u8, b.clamp(0.0, 255.0) as u8, 255]),
+            _ => None,
+        };
+    }
+    if let Some(inner) = color.strip_prefix("hsl(") {
+        let values = arguments(inner)?;
+        return match values[..] {
+            [h, s, l] => {
+                let (r, g, b) = hsl_to_rgb(h, s / 100.0, l / 100.0);
+                Some([r, g, b, 255])
+            },
+            _ => None,
+        };
+    }
+    let rgb = match color {
+        "transparent" => return Some([0, 0, 0, 0]),
+        "black" => [0, 0, 0],
+        "white" => [255, 255, 255],
+        "red" => [255, 0, 0],
+        "lime" => [0, 255, 0],
+        "green" => [0, 128, 0],
+        "blue" => [0, 0, 255],
+        "yellow" => [255, 255, 0],
+        "orange" => [255, 165, 0],
+        "darkorange" => [255, 140, 0],
+        "gold" => [255, 215, 0],
+        "goldenrod" => [218, 165, 32],
+        "crimson" => [220, 20, 60],
+        "deeppink" => [255, 20, 147],
+        "lightcoral" => [240, 128, 128],
+        "rosybrown" => [188, 143, 143],
+        "mistyrose" => [255, 228, 225],
+        "purple" => [128, 0, 128],
+        "darkviolet" => [148, 0, 211],
+        "mediumpurple" => [147, 112, 219],
+        "royalblue" => [65, 105, 225],
+        "steelblue" => [70, 130, 180],
+        "deepskyblue" => [0, 191, 255],
+        "teal" => [0, 128, 128],
+        "mediumseagreen" => [60, 179, 113],
+        "slategray" => [112, 128, 144],
+        "gray" => [128, 128, 128],
+        "dimgray" => [105, 105, 105],
+        "gainsboro" => [220, 220, 220],
+        _ => return None,
+    };
+    Some([rgb[0], rgb[1], rgb[2], 255])
+}
+
+fn hsl_to_rgb(hue: f64, saturation: f64, lightness: f64) -> (u8, u8, u8) {
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let sector = hue.rem_euclid(360.0) / 60.0;
+    let second = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+    let (r, g, b) = match sector as usize {
+        0 => (chroma, second, 0.0),
+        1 => (second, chroma, 0.0),
+        2 => (0.0, chroma, second),
+        3 => (0.0, second, chroma),
+        4 => (second, 0.0, chroma),
+        _ => (chroma, 0.0, second),
+    };
+    let base = lightness - chroma / 2.0;
+    let channel = |value: f64| ((value + base) * 255.0).round().clamp(0.0, 255.0) as u8;
+    (channel(r), channel(g), channel(b))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+
+    fn at(pixels: &Pixels, (x, y): (usize, usize)) -> [u8; 4] {
+        let at = (y * pixels.width + x) * 4;
+        pixels.data[at..at + 4].try_into().expect("four channels")
+    }
+
+    fn filled(pixels: &Pixels) -> usize {
+        pixels.data.chunks(4).filter(|pixel| pixel[3] > 0).count()
+    }
+
+    #[test]
+    fn fills_the_pixels_whose_centres_are_inside() {
+        let mut pixels = Pixels::new(8, 6);
+        pixels.fill_polygon(&[(1.0, 1.0), (4.0, 1.0), (4.0, 3.0), (1.0, 3.0)], RED, 1.0, false);
+        assert_eq!(filled(&pixels), 3 * 2);
+        assert_eq!(at(&pixels, (1, 1)), RED);
+        assert_eq!(at(&pixels, (3, 2)), RED);
+        assert_eq!(at(&pixels, (4, 1)), [0; 4]);
+
+        // a right triangle over a 4 x 4 square takes the pixels on and below its diagonal
+        let mut pixels = Pixels::new(8, 6);
+        pixels.fill_polygon(&[(0.0, 0.0), (4.0, 4.0), (0.0, 4.0)], RED, 1.0, false);
+        assert_eq!(filled(&pixels), 4 + 3 + 2 + 1);
+        assert_eq!(at(&pixels, (0, 0)), RED);
+        assert_eq!(at(&pixels, (1, 0)), [0; 4]);
+    }
+
+    #[test]
+    fn fills_are_clipped_to_the_buffer() {
+        let mut pixels = Pixels::new(4, 4);
+        pixels.fill_polygon(&[(-10.0, -10.0), (2.0, -10.0), (2.0, 2.0), (-10.0, 2.0)], RED, 1.0, false);
+        assert_eq!(filled(&pixels), 4);
+        assert_eq!(pixels.take_dirty(), Some((0, 0, 2, 2)));
+        // wholly outside draws nothing and dirties nothing
+        pixels.fill_polygon(&[(5.0, 5.0), (9.0, 5.0), (9.0, 9.0)], RED, 1.0, false);
+        assert_eq!(pixels.take_dirty(), None);
+    }
+
+    #[test]
+    fn blends_over_and_replaces() {
+        let square = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        let mut pixels = Pixels::new(1, 1);
+        pixels.fill_polygon(&square, [0, 0, 255, 255], 1.0, false);
+        pixels.fill_polygon(&square, RED, 0.5, false);
+        assert_eq!(at(&pixels, (0, 0)), [128, 0, 128, 255]);
+        // clearing writes the transparent colour as it is
+        pixels.fill_polygon(&square, [0, 0, 0, 0], 1.0, true);
+        assert_eq!(at(&pixels, (0, 0)), [0; 4]);
+        // half a colour over nothing stays the colour, half see-through
+        pixels.fill_polygon(&square, RED, 0.5, false);
+        assert_eq!(at(&pixels, (0, 0)), [255, 0, 0, 128]);
+    }
+
+    #[test]
+    fn dirty_rectangle_is_the_union_of_the_fills_until_taken() {
+        let mut pixels = Pixels::new(20, 20);
+        assert_eq!(pixels.take_dirty(), None);
+        pixels.fill_polygon(&[(2.0, 3.0), (4.0, 3.0), (4.0, 5.0), (2.0, 5.0)], RED, 1.0, false);
+        pixels.fill_polygon(&[(10.0, 1.0), (12.0, 1.0), (12.0, 4.0), (10.0, 4.0)], RED, 1.0, false);
+        assert_eq!(pixels.take_dirty(), Some((2, 1, 12, 5)));
+        assert_eq!(pixels.take_dirty(), None);
+        pixels.fill_polygon(&[(6.5, 6.5), (8.5, 6.5), (8.5, 8.5)], RED, 1.0, false);
+        assert_eq!(pixels.take_dirty(), Some((6, 6, 9, 9)));
+    }
+
+    #[test]
+    fn area_reaches_the_margin_round_the_points() {
+        let pixels = Pixels::new(10, 10);
+        assert_eq!(pixels.area(&[(3.0, 4.0), (5.5, 4.0)], 1.0), Some((2, 3, 7, 5)));
+        assert_eq!(pixels.area(&[(-5.0, -5.0)], 2.0), None);
+        assert_eq!(pixels.area(&[(8.0, 8.0)], 5.0), Some((3, 3, 10, 10)));
+    }
+}
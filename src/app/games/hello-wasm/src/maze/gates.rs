@@ -0,0 +1,74 @@
+use wasm_bindgen::prelude::*;
+use std::collections::HashSet;
+use crate::maze::MazeState;
+
+// drawing the gates and patrols of the schedule as they are at `self.tick`
+
+
+#[wasm_bindgen]
+impl MazeState {
+
+    // shows the gates and patrols as they are `tick` ticks after the start
+    pub fn set_tick(&mut self, tick: usize) -> Result<(), JsValue> {
+        self.tick = tick;
+        self.clear_visualization()
+    }
+
+    // after this many ticks the gates and patrols repeat themselves, undefined when that is
+    // too long for the space-time solver to plan around
+    pub fn get_schedule_cycle(&self) -> Option<usize> {
+        self.schedule.cycle()
+    }
+}
+
+
+impl MazeState {
+    // a shut gate is a solid block, an open one only its outline;
+    // each patrol is drawn where it stands, with its beat marked faintly
+    pub(super) fn draw_schedule(&mut self) {
+        if self.schedule.is_empty() {
+            return;
+        }
+        let tick = self.tick;
+        let (gate_color, patrol_color) = (self.theme.gate.clone(), self.theme.patrol.clone());
+
+        self.pen().set_line_width((self.block_size / 6.0).max(1.0));
+        self.pen().set_stroke_style_str(&gate_color);
+        for index in 0..self.schedule.gates.len() {
+            let gate = self.schedule.gates[index].clone();
+            let (x, y) = gate.cell;
+            if gate.is_open(tick) {
+                self.outline_cell(x, y);
+            } else {
+                self.fill_cell(x, y, &gate_color);
+            }
+        }
+
+        for index in 0..self.schedule.patrols.len() {
+            let patrol = self.schedule.patrols[index].clone();
+            self.set_fill_alpha(0.2);
+            for &(x, y) in patrol.route.iter() {
+                self.fill_cell(x, y, &patrol_color);
+            }
+            self.set_fill_alpha(1.0);
+            let (x, y) = patrol.position(tick);
+            self.fill_cell(x, y, &patrol_color);
+        }
+    }
+
+    // the gates and patrols moved on to `self.tick` without drawing the maze again: every cell
+    // they can be in goes back to the floor, or to the final path where `walked` went through it
+    pub(super) fn move_schedule(&mut self, walked: &HashSet<(usize, usize)>) {
+        let mut cells: Vec<(usize, usize)> = self.schedule.gates.iter().map(|gate| gate.cell).collect();
+        cells.extend(self.schedule.patrols.iter().flat_map(|patrol| patrol.route.iter().copied()));
+        for (x, y) in cells {
+            self.clear_cell(x, y);
+            if (x, y) == self.target {
+                self.fill_cell(x, y, &self.theme.target.clone());
+            } else if walked.contains(&(x, y)) && (x, y) != self.computer_player {
+                self.fill_cell(x, y, &self.theme.final_path.clone());
+            }
+        }
+        self.draw_schedule();
+    }
+}
@@ -0,0 +1,65 @@
+use crate::maze::MazeState;
+use crate::maze::pathfinding::keys::KeyState;
+
+// drawing the keys and doors, and the route of the key solver coloured by what it carries
+
+
+// every letter gets its own hue, the key bright and its door a darker shade of it
+fn key_color(key: u8, lightness: u8) -> String {
+    format!("hsl({}, 80%, {}%)", key as usize * 360 / 26, lightness)
+}
+
+
+impl MazeState {
+    pub(super) fn draw_locks(&mut self) {
+        let mut keys: Vec<((usize, usize), u8)> = self.locks.keys.iter().map(|(&cell, &key)| (cell, key)).collect();
+        let mut doors: Vec<((usize, usize), u8)> = self.locks.doors.iter().map(|(&cell, &door)| (cell, door)).collect();
+        keys.sort();
+        doors.sort();
+        for ((x, y), key) in keys {
+            self.label_cell(x, y, &key_color(key, 60), &((b'a' + key) as char).to_string(), &self.theme.ink.clone());
+        }
+        for ((x, y), door) in doors {
+            self.label_cell(x, y, &key_color(door, 30), &((b'A' + door) as char).to_string(), &self.theme.light_ink.clone());
+        }
+    }
+
+    // the route with every cell split into one stripe per key carried there, plain red while
+    // the agent has no keys; doors and the keys themselves stay visible on top
+    pub(super) fn draw_key_route(&mut self, route: &[KeyState]) {
+        self.last_path = route.iter().map(|&(cell, _)| cell).collect();
+        for &((x, y), carried) in route.iter() {
+            if (x, y) == self.computer_player || (x, y) == self.target || self.locks.doors.contains_key(&(x, y)) {
+                continue;
+            }
+            let held: Vec<u8> = (0..26).filter(|key| carried & (1 << key) != 0).collect();
+            if held.is_empty() {
+                self.path_cell(x, y);
+                continue;
+            }
+            // stripes across the cell's bounding box, clipped to its shape
+            let corners = self.links.topology.corners((x, y), self.block_size);
+            let left = corners.iter().map(|corner| corner.0).fold(f64::INFINITY, f64::min);
+            let right = corners.iter().map(|corner| corner.0).fold(f64::NEG_INFINITY, f64::max);
+            let top = corners.iter().map(|corner| corner.1).fold(f64::INFINITY, f64::min);
+            let bottom = corners.iter().map(|corner| corner.1).fold(f64::NEG_INFINITY, f64::max);
+            let stripe = (right - left) / held.len() as f64;
+            self.pen().save();
+            self.trace_cell(x, y);
+            self.pen().clip();
+            for (index, &key) in held.iter().enumerate() {
+                self.pen().set_fill_style_str(&key_color(key, 50));
+                self.cell_context(x, y).fill_rect(left + index as f64 * stripe, top, stripe, bottom - top);
+            }
+            self.pen().restore();
+        }
+
+        for &(cell, _) in route.iter() {
+            if let Some(&key) = self.locks.keys.get(&cell) {
+                self.label_cell(cell.0, cell.1, &key_color(key, 60), &((b'a' + key) as char).to_string(), &self.theme.ink.clone());
+            }
+        }
+        let cells: Vec<(usize, usize)> = route.iter().map(|&(cell, _)| cell).collect();
+        self.draw_jumps(&cells);
+    }
+}
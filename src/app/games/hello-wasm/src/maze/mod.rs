@@ -9,14 +9,54 @@
     // use std::thread;
     // use std::time::Duration;
 
-    mod pathfinding;
+    pub mod pathfinding;
     use pathfinding::{bfs::BfsSolver, dfs::DfsSolver, astar::AstarSolver, best_first::BestFirstSolver};
+    use pathfinding::{wall_follower::{WallFollowerSolver, Hand}, pledge::PledgeSolver, tremaux::TremauxSolver, dead_end_filling::DeadEndFillingSolver};
+    use pathfinding::fog::{Belief, FogSolver};
+    use pathfinding::pursuit::{PursuitSolver, TargetBehaviour};
+    use pathfinding::cooperative::Agent;
+    use pathfinding::{Floors, Links, Portals, Walked};
+    use pathfinding::topology::Topology;
+    use pathfinding::keys::{KeySolver, Locks};
+    use pathfinding::timed::{Gate, Patrol, Schedule, SpaceTimeSolver};
+    use pathfinding::hierarchical::{Hierarchy, HpaSolver};
+    use pathfinding::learning::{Learning, QTable};
+    use pathfinding::trace::{Event, Trace};
+    use pathfinding::grid::{Grid, Scratch};
+    use pathfinding::search::{Frontier, Search, Step};
+    use pathfinding::components::Components;
+    use pathfinding::distances::Distances;
+    use theme::{Heat, HeatMode, Theme};
+    use walk::Walk;
+    use viewport::Viewport;
+    use framebuffer::Framebuffer;
+    mod transform;
+    mod tour;
+    mod agents;
+    mod fields;
+    mod portals;
+    mod locks;
+    mod gates;
+    mod tiling;
+    mod floors;
+    mod clusters;
+    mod policy;
+    mod evolve;
+    mod replay;
+    mod theme;
+    mod walk;
+    mod viewport;
+    mod framebuffer;
+    mod figure;
+    mod png;
+    mod regions;
+    mod eccentricity;
 
 
 
 
     #[derive(Clone, Copy, PartialEq, Debug)]
-    enum CellType {
+    pub enum CellType {
         Wall,
         Path,
         // Target,
@@ -26,29 +66,59 @@
     #[derive(Clone, Copy, Debug, PartialEq)]
     enum PlayerType {
         Computer,
+        #[allow(dead_code)]
         Human,
         Target,
     }
 
+    #[allow(dead_code)]
     #[derive(Clone, Copy, Debug, PartialEq)]
     enum Algorithm {
-        BFS,
-        DFS,
+        Bfs,
+        Dfs,
         BestFirst,
         AStar,
-        DIJKSTRA,
+        Dijkstra,
     }
 
 
     #[wasm_bindgen]
     pub struct MazeState{
-        maze_grid: Vec<Vec<CellType>>,
+        maze_grid: Grid,
         computer_player: (usize, usize),
         target: (usize, usize),
         human_player: (usize, usize),
         canvas_context: CanvasRenderingContext2d,
         block_size: f64,
-        original_maze_grid: Vec<Vec<CellType>>,
+        original_maze_grid: Grid,
+        belief_map: Vec<Vec<Belief>>, // what the fog of war agent has seen so far
+        target_behaviour: TargetBehaviour,
+        target_move_every: usize, // the target moves once per this many computer steps
+        waypoints: Vec<(usize, usize)>, // stops for solve_tour, in the order they were placed
+        agents: Vec<Agent>, // the cooperative agents, separate from computer_player
+        agent_routes: Vec<Vec<(usize, usize)>>, // their last planned positions, one per tick
+        links: Links, // the tiling, and the teleporter pairs written as matching digits in the maze text
+        portal_labels: HashMap<(usize, usize), char>, // the digit drawn on each portal cell
+        locks: Locks, // keys and doors, written as matching lower and upper case letters
+        schedule: Schedule, // gates and patrols, from the `@` lines of the maze text
+        tick: usize, // the moment of the schedule draw_maze shows
+        hierarchy: Hierarchy, // the cluster graph of the hierarchical solver, kept between searches
+        learning: Learning, // settings of the reinforcement learning agent
+        q_table: QTable, // what it learned in its last training
+        learning_curve: Vec<usize>, // the steps each of those training episodes took
+        evolution_history: Vec<usize>, // the best fitness of every generation of the last evolve_maze
+        trace: Trace, // what the last solver run did, for replaying it
+        theme: Theme, // the colours of walls, floor, searches and players
+        heat_mode: HeatMode, // what expanded cells are coloured by
+        heat: Heat, // the expansions of the current search, for the heatmap
+        last_path: Vec<(usize, usize)>, // the path the last solve drew, from the start to the target
+        walk: Walk, // the computer player walking that path
+        view: Viewport, // the zoom and pan of the canvas
+        framebuffer: Framebuffer, // the pixel buffer cells are drawn into when it is enabled
+        scratch: Scratch, // the solvers' seen, parent and cost buffers, reused between searches
+        components: Components, // the connected regions of maze_grid, labelled again when it changes
+        reachable_only: bool, // whether set_target and reset refuse cells the computer cannot reach
+        distances: Distances, // the all pairs distance table of maze_grid, for its centre and diameter
     }
 
 
@@ -89,6 +159,10 @@
         let canvas_context = get_context(canvas_id)?;
         let maze_grid = parse_maze(maze_data)?;
         let original_maze_grid = maze_grid.clone();
+        let portal_labels = parse_portals(maze_data)?;
+        let locks = parse_locks(maze_data)?;
+        let schedule = parse_schedule(maze_data, &maze_grid)?;
+        let floors = parse_floors(maze_data)?;
 
         let mut state = MazeState{
             maze_grid,
@@ -98,11 +172,43 @@
             canvas_context,
             block_size,
             original_maze_grid,
+            belief_map: Vec::new(),
+            target_behaviour: TargetBehaviour::Static,
+            target_move_every: 2,
+            waypoints: Vec::new(),
+            agents: Vec::new(),
+            agent_routes: Vec::new(),
+            links: Links::new(
+                parse_topology(maze_data)?,
+                Portals { pairs: link_portals(&portal_labels), cost: 1 },
+                floors,
+            ),
+            portal_labels,
+            locks,
+            schedule,
+            tick: 0,
+            hierarchy: Hierarchy::default(),
+            learning: Learning::default(),
+            q_table: QTable::new(),
+            learning_curve: Vec::new(),
+            evolution_history: Vec::new(),
+            trace: Trace::default(),
+            theme: Theme::default(),
+            heat_mode: HeatMode::Off,
+            heat: Heat::default(),
+            last_path: Vec::new(),
+            walk: Walk::default(),
+            view: Viewport::default(),
+            framebuffer: Framebuffer::default(),
+            scratch: Scratch::default(),
+            components: Components::default(),
+            reachable_only: false,
+            distances: Distances::default(),
         };
         state.draw_maze()?;
-        let target_pos = state.generate_random_target()?;
+        let target_pos = state.generate_random_target(None)?;
         state.draw_player(target_pos[0], target_pos[1], PlayerType::Target);
-        let com_pos = state.generate_random_target()?;
+        let com_pos = state.generate_random_target(None)?;
         state.draw_player(com_pos[0], com_pos[1], PlayerType::Computer);
 
         Ok(state)
@@ -113,12 +219,15 @@
             self.computer_player = (0, 0);
             self.target = (0, 0);
             self.human_player = (0, 0);
-            self.canvas_context.set_fill_style(&"black".into());
+            self.waypoints.clear();
+            self.agents.clear();
+            self.agent_routes.clear();
+            self.pen().set_fill_style_str("black");
             self.clear_canvas();
-            self.draw_maze();
-            let target_pos = self.generate_random_target()?;
+            self.draw_maze()?;
+            let target_pos = self.generate_random_target(None)?;
             self.draw_player(target_pos[0], target_pos[1], PlayerType::Target);
-            let com_pos = self.generate_random_target()?;
+            let com_pos = self.generate_random_target(self.reachable_only.then_some(self.target))?;
             self.draw_player(com_pos[0], com_pos[1], PlayerType::Computer);
             Ok(())
         }
@@ -130,6 +239,10 @@
             self.draw_maze()?;
             self.draw_player(self.computer_player.0, self.computer_player.1, PlayerType::Computer);
             self.draw_player(self.target.0, self.target.1, PlayerType::Target);
+            for (x, y) in self.waypoints.clone() {
+                self.fill_cell(x, y, &self.theme.waypoint.clone());
+            }
+            self.draw_agents();
             Ok(())
         }
 
@@ -139,45 +252,67 @@
           Ok(JsValue::from_str(&format!("target:{:?} & computer_player:{:?}", target, computer_player)))
        }
 
+        // the maze as it is now (transforms included), written in the same text format new() reads
+        pub fn get_maze_text(&self) -> String {
+            self.maze_text(&self.original_maze_grid)
+        }
+
 
 
         pub fn draw_maze(&mut self)-> Result<(), JsValue> {
-            self.canvas_context.set_fill_style(&"black".into());
+            self.pen().set_fill_style_str("black");
             self.clear_canvas();
+            self.apply_viewport();
 
-            for (y, row) in self.maze_grid.iter().enumerate() {
-                for (x, &cell_type) in row.iter().enumerate() {
-                    if cell_type == CellType::Wall {
-                        self.canvas_context.fill_rect(
-                            x as f64 * self.block_size,
-                            y as f64 * self.block_size, 
-                            self.block_size, 
-                            self.block_size,
-                        );
+            let canvas = self.canvas()?;
+            let canvas_size = (canvas.width() as f64, canvas.height() as f64);
+            let (wall, path) = (self.theme.wall.clone(), self.theme.path.clone());
+            for y in 0..self.maze_grid.height() {
+                for x in 0..self.maze_grid.width() {
+                    if !self.is_visible(x, y, canvas_size) {
+                        continue;
+                    }
+                    if !self.maze_grid.is_path((x, y)) {
+                        self.fill_cell(x, y, &wall);
+                    } else if path != "transparent" {
+                        self.fill_cell(x, y, &path);
                     }
                 }
             }
+            self.draw_portals();
+            self.draw_locks();
+            self.draw_schedule();
+            self.draw_stairs();
             Ok(())
         }
 
         fn clear_canvas(&mut self) {
             let canvas = self.canvas_context.canvas().unwrap().dyn_into::<HtmlCanvasElement>().unwrap();
+            // the whole canvas, whatever the viewport
+            let _ = self.canvas_context.set_transform(1.0, 0.0, 0.0, 1.0, 0.0, 0.0);
             self.canvas_context.clear_rect(0.0, 0.0, canvas.width() as f64, canvas.height() as f64);
+            if self.framebuffer.enabled {
+                let _ = self.reset_framebuffer();
+            }
         }
 
 
-        fn generate_random_target(&self)-> Result<Vec<usize>, JsValue> {
+        // `connected_to`, when given, is a cell the position must be able to reach. with
+        // reachable_only on it is never a cell walled in on its own, where the other player
+        // could not be put anywhere it can reach
+        fn generate_random_target(&mut self, connected_to: Option<(usize, usize)>)-> Result<Vec<usize>, JsValue> {
         let mut rng = rand::thread_rng();
         const MAX_ATTEMPTS: usize = 50;
 
         for _ in 0..MAX_ATTEMPTS {
-            let x = rng.gen_range(0..self.maze_grid[0].len());
-            let y = rng.gen_range(0..self.maze_grid.len());
-            if self.maze_grid[y][x] == CellType::Path
+            let x = rng.gen_range(0..self.maze_grid.width());
+            let y = rng.gen_range(0..self.maze_grid.height());
+            if self.maze_grid.is_path((x, y))
+                && (x, y) != self.computer_player && (x, y) != self.target
+                && connected_to.is_none_or(|other| self.reachable((x, y), other))
+                && (!self.reachable_only || self.has_company((x, y)))
             {
-                    if (x, y) != self.computer_player && (x, y) != self.target {
-                        return Ok(vec![x, y]);
-                    }
+                return Ok(vec![x, y]);
             }
         }
         Err(JsValue::from_str("Failed to find a suitable position!"))
@@ -187,39 +322,25 @@
             match player_type {
                 PlayerType::Computer => {
                     self.computer_player = (x, y);
-                    self.canvas_context.set_fill_style(&"red".into());
-                    self.canvas_context.fill_rect(
-                        x as f64 * self.block_size, 
-                        y as f64 * self.block_size, 
-                        self.block_size, 
-                        self.block_size,
-                    );
+                    self.fill_cell(x, y, &self.theme.computer.clone());
                 },
                 PlayerType::Human => {
                     self.human_player = (x, y);
-                    self.canvas_context.set_fill_style(&"blue".into());
-                    self.canvas_context.fill_rect(
-                        x as f64 * self.block_size, 
-                        y as f64 * self.block_size, 
-                        self.block_size, 
-                        self.block_size,
-                    );
+                    self.fill_cell(x, y, &self.theme.human.clone());
                 },
                 PlayerType::Target => {
                     self.target =(x, y);
-                    self.canvas_context.set_fill_style(&"lime".into());
-                    self.canvas_context.fill_rect(
-                        x as f64 * self.block_size, 
-                        y as f64 * self.block_size, 
-                        self.block_size, 
-                        self.block_size,
-                    );
+                    self.fill_cell(x, y, &self.theme.target.clone());
                 },  
             }
         }
 
+    // runs one of the solvers, recording what it does as the trace (see get_trace_json)
     pub async fn find_path(&mut self, algorithm: &str, delay_ms: i32) -> Result<JsValue, JsValue> {
-        match algorithm {
+        self.trace = Trace::begin(algorithm, self.computer_player, self.target);
+        self.heat = Heat::new(&self.maze_grid);
+        self.last_path.clear();
+        let result = match algorithm {
                 "BFS" => {
                     let mut bfs_solver = BfsSolver;
                     bfs_solver.find_path(self, delay_ms).await
@@ -236,74 +357,309 @@
                     let mut best_first_solver = BestFirstSolver;
                     best_first_solver.find_path(self, delay_ms).await
                 }
+                "WallFollowerLeft" => {
+                    let mut wall_follower = WallFollowerSolver { hand: Hand::Left };
+                    wall_follower.find_path(self, delay_ms).await
+                }
+                "WallFollowerRight" => {
+                    let mut wall_follower = WallFollowerSolver { hand: Hand::Right };
+                    wall_follower.find_path(self, delay_ms).await
+                }
+                "Pledge" => {
+                    let mut pledge_solver = PledgeSolver;
+                    pledge_solver.find_path(self, delay_ms).await
+                }
+                "Tremaux" => {
+                    let mut tremaux_solver = TremauxSolver;
+                    tremaux_solver.find_path(self, delay_ms).await
+                }
+                "KeysAndDoors" => {
+                    let mut key_solver = KeySolver;
+                    key_solver.find_path(self, delay_ms).await
+                },
+                "SpaceTime" => {
+                    let mut space_time_solver = SpaceTimeSolver;
+                    space_time_solver.find_path(self, delay_ms).await
+                },
+                "HPAStar" => {
+                    let mut hpa_solver = HpaSolver;
+                    hpa_solver.find_path(self, delay_ms).await
+                },
+                "DeadEndFilling" => {
+                    let mut dead_end_filling_solver = DeadEndFillingSolver;
+                    dead_end_filling_solver.find_path(self, delay_ms).await
+                }
                 _ => {
                     Err(JsValue::from_str("Invalid algorithm name!"))
                 }
-            }
+            };
+        self.trace.recording = false;
+        self.finish_heatmap();
+        result
         }
 
+        // explores the maze with only a local sensor instead of reading the whole grid up front;
+        // with `line_of_sight` walls also block the view, otherwise the sensor sees through them
+        pub async fn find_path_fog(&mut self, sensor_radius: usize, line_of_sight: bool, delay_ms: i32) -> Result<JsValue, JsValue> {
+            let mut fog_solver = FogSolver { sensor_radius: sensor_radius.max(1), line_of_sight };
+            self.trace = Trace::begin("Fog", self.computer_player, self.target);
+            let result = fog_solver.find_path(self, delay_ms).await;
+            self.trace.recording = false;
+            result
+        }
 
-        pub fn set_target(&mut self, x: usize, y: usize) {
-           if self.computer_player != (x, y) && self.maze_grid[y][x] != CellType::Wall {
+        // makes the target move during pursue_target: "Static", "Wander" or "Flee",
+        // once every `move_every` computer steps (so 1 is as fast as the computer)
+        pub fn set_target_behaviour(&mut self, behaviour: &str, move_every: usize) -> Result<(), JsValue> {
+            self.target_behaviour = TargetBehaviour::from_name(behaviour)
+                .ok_or_else(|| JsValue::from_str("Invalid target behaviour!"))?;
+            self.target_move_every = move_every.max(1);
+            Ok(())
+        }
+
+        // chases the target with moving target search until it is caught,
+        // the reply reports how many steps that took
+        pub async fn pursue_target(&mut self, delay_ms: i32) -> Result<JsValue, JsValue> {
+            let mut pursuit_solver = PursuitSolver { behaviour: self.target_behaviour, move_every: self.target_move_every };
+            self.trace = Trace::begin("Pursuit", self.computer_player, self.target);
+            let result = pursuit_solver.find_path(self, delay_ms).await;
+            self.trace.recording = false;
+            result
+        }
+
+        // the fog of war agent's belief map, one byte per cell row by row:
+        // 0 unknown, 1 open, 2 wall. empty until find_path_fog has run
+        pub fn get_belief_map(&self) -> Vec<u8> {
+            self.belief_map.iter().flatten().map(|&belief| belief as u8).collect()
+        }
+
+
+        pub fn set_target(&mut self, x: usize, y: usize) -> Result<(), JsValue> {
+           let open = self.maze_grid.is_path((x, y));
+           if self.reachable_only && open && !self.reachable(self.computer_player, (x, y)) {
+                return Err(JsValue::from_str("Target not reachable!"));
+           }
+           if self.computer_player != (x, y) && open {
                     self.target = (x, y);
-                    self.clear_visualization();
+                    self.clear_visualization()?;
+                }
+            Ok(())
+        }
+
+        // helper function to paint a finished path, for the solvers that build it themselves
+        fn draw_path(&mut self, path: &[(usize, usize)]) {
+            for &(x, y) in path {
+                if (x, y) != self.computer_player {
+                    self.path_cell(x, y);
                 }
+            }
+            self.draw_jumps(path);
+            self.last_path = path.to_vec();
+        }
+
+        // helper function to hand the solvers the grid and their buffers, ready for a search.
+        // the grid is a copy that shares its cells and the buffers are taken out of the state,
+        // so a solver can read them while it draws
+        fn take_search_buffers(&mut self) -> (Grid, Scratch) {
+            let mut scratch = std::mem::take(&mut self.scratch);
+            scratch.begin(self.maze_grid.cell_count());
+            (self.maze_grid.clone(), scratch)
+        }
+
+        fn give_search_buffers(&mut self, scratch: Scratch) {
+            self.scratch = scratch;
+        }
+
+        // helper function to reconstruct the path from the parents the search left in scratch
+        fn reconstruct_path(&mut self, grid: &Grid, scratch: &Scratch, target: (usize, usize)) -> Vec<(usize, usize)> {
+            let path = scratch.path(grid, grid.index(target));
+            for &(x, y) in path.iter().rev().skip(1) {
+                self.path_cell(x, y);
+            }
+            self.draw_jumps(&path);
+            self.last_path = path.clone();
+            path
         }
 
-        // helper function to reconstruct the path from the parent set
-        fn reconstruct_path(&mut self, parent: Vec<Vec<Option<(usize, usize)>>>, target: (usize, usize)) -> Result<Vec<(usize, usize)>, JsValue> {
-            let mut path = Vec::new();
-            let mut current = target;
-            while let Some(parent_cell) = parent[current.1][current.0] {
-                path.push(current);
-                current = parent_cell;
-                self.color_cell(current.0, current.1, "red".to_string());
+        // helper function for the walking solvers: plays the walk back a step at a time, then
+        // draws the path or says why there is none
+        async fn draw_walk(&mut self, walked: Walked, delay_ms: i32) -> Result<JsValue, JsValue> {
+            for &((x, y), back) in walked.steps.iter() {
+                let color = if back { &self.theme.visited } else { &self.theme.frontier }.clone();
+                self.color_cell(x, y, color);
+                if delay_ms > 0 {
+                    sleep(delay_ms).await?;
+                }
+            }
+            match walked.path {
+                Ok(path) => {
+                    self.draw_path(&path);
+                    Ok(JsValue::from_str(&format!("Path found! --> {} steps walked, path length {}", walked.steps.len(), path.len() - 1)))
+                },
+                Err(reason) => Ok(JsValue::from_str(&format!("Target not reachable! --> {}", reason))),
             }
-            path.push(self.computer_player);
-            path.reverse();
-            Ok(path)
         }
 
-        // helper function to reconstruct the path from the parent set
-        //  for the A* algorithm as it uses hashmaps
-        fn reconstruct_path_astar(
+        // helper function for the frontier solvers (bfs, dfs, best first, a*): runs the shared
+        // search, colouring every cell it expands and every cell it finds, then draws the path.
+        // returns whether the target was reached
+        async fn draw_search(&mut self, frontier: Frontier, delay_ms: i32) -> Result<bool, JsValue> {
+            let (grid, mut scratch) = self.take_search_buffers();
+            let links = self.links.clone();
+            let result = self.draw_search_steps(&grid, &links, &mut scratch, frontier, delay_ms).await;
+            self.give_search_buffers(scratch);
+            result
+        }
+
+        async fn draw_search_steps(
             &mut self,
-            parent: HashMap<(usize, usize), Option<(usize, usize)>>,
-            target: (usize, usize),
-        ) -> Result<Vec<(usize, usize)>, JsValue> {
-            let mut path = Vec::new();
-            let mut current = target;
-
-            while let Some(&Some(prev)) = parent.get(&current) {
-                path.push(current);
-                current = prev;
-                self.color_cell(current.0, current.1, "red".to_string());
+            grid: &Grid,
+            links: &Links,
+            scratch: &mut Scratch,
+            frontier: Frontier,
+            delay_ms: i32,
+        ) -> Result<bool, JsValue> {
+            let (start, target) = (self.computer_player, self.target);
+            let search = Search::new(grid, links, scratch, frontier, start, target);
+            self.trace.record(Event::Push(start));
+
+            loop {
+                let index = match search.step(scratch) {
+                    Step::Exhausted => return Ok(false),
+                    Step::Arrived(index) => {
+                        self.expand_cell(grid.cell(index));
+                        self.reconstruct_path(grid, scratch, target);
+                        return Ok(true);
+                    }
+                    Step::Expanded(index) => index,
+                };
+                let current = grid.cell(index);
+                self.expand_cell(current);
+
+                // color each cell as it is found and add delay
+                for at in 0..scratch.next.len() {
+                    let (next, cost) = scratch.next[at];
+                    let cell = grid.cell(next);
+                    if frontier == Frontier::Cost {
+                        self.trace.record(Event::Relax(cell, scratch.cost[next] as usize));
+                    }
+                    self.frontier_cell(cell, current, cost);
+                    if delay_ms > 0 {
+                        sleep(delay_ms).await?;
+                    }
+                }
             }
-            path.push(current);
-            path.reverse();
-            Ok(path)
         }
 
 
 
 
 
+        // every move a solver may make from `cell` with its cost, portal jumps included
+        fn neighbours(&self, cell: (usize, usize)) -> Vec<((usize, usize), usize)> {
+            pathfinding::neighbours(&self.maze_grid, &self.links, cell).collect()
+        }
+
+        // `grid` written out as maze text that new() reads back the same, with this maze's
+        // portals, keys, doors and stairs on it and its metadata at the end
+        fn maze_text(&self, grid: &Grid) -> String {
+            let symbol = |x: usize, y: usize| -> char {
+                if let Some(&digit) = self.portal_labels.get(&(x, y)) {
+                    return digit;
+                }
+                if let Some(&key) = self.locks.keys.get(&(x, y)) {
+                    return (b'a' + key) as char;
+                }
+                if let Some(&door) = self.locks.doors.get(&(x, y)) {
+                    return (b'A' + door) as char;
+                }
+                match (self.links.floors.partner((x, y)), grid.is_path((x, y))) {
+                    (Some(other_end), _) => if other_end.0 > x { '>' } else { '<' },
+                    (None, false) => '*',
+                    (None, true) => ' ',
+                }
+            };
+
+            // each floor without the wall columns between it and the next, which parsing adds back
+            let floors = &self.links.floors;
+            let (count, width) = if floors.count > 1 { (floors.count, floors.stride - 2) } else { (1, usize::MAX) };
+            let blocks: Vec<String> = (0..count).map(|z| {
+                (0..grid.height()).map(|y| {
+                    let columns = (z * floors.stride).min(grid.width())..(z * floors.stride).saturating_add(width).min(grid.width());
+                    columns.map(|x| symbol(x, y)).collect::<String>()
+                }).collect::<Vec<String>>().join("\n")
+            }).collect();
+            let mut text = blocks.join("\n---\n");
+
+            if self.links.topology != Topology::Square {
+                text.push_str(&format!("\n@topology {:?}", self.links.topology));
+            }
+            for gate in self.schedule.gates.iter() {
+                text.push_str(&format!("\n@gate {} {} {} {}", gate.cell.0, gate.cell.1, gate.period, gate.offset));
+            }
+            for patrol in self.schedule.patrols.iter() {
+                // only the points where the patrol turns, the legs in between are straight
+                let heading = |a: (usize, usize), b: (usize, usize)| (b.0.cmp(&a.0), b.1.cmp(&a.1));
+                let route = &patrol.route;
+                let mut points = vec![route[0]];
+                for index in 1..route.len() {
+                    if index + 1 == route.len() || heading(route[index - 1], route[index]) != heading(route[index], route[index + 1]) {
+                        points.push(route[index]);
+                    }
+                }
+                if points.len() == 1 {
+                    points.push(route[0]); // a patrol standing still
+                }
+                let words: Vec<String> = points.iter().map(|&(x, y)| format!("{} {}", x, y)).collect();
+                text.push_str(&format!("\n@patrol {}", words.join(" ")));
+            }
+            text.push('\n');
+            text
+        }
+
         // helper function to color a cell
         fn color_cell(&mut self, x: usize, y: usize, color: String) {
-            let cell = self.maze_grid[y][x];
-            match cell {
-                CellType::Path => {
-                    if self.target != (x, y){
-                        self.canvas_context.set_fill_style(&color.into());
-                        self.canvas_context.fill_rect(
-                            x as f64 * self.block_size, 
-                            y as f64 * self.block_size, 
-                            self.block_size, 
-                            self.block_size,
-                        );
-                    }
-                },
-                _ => {}
+            self.trace.record_visit((x, y), &color);
+            if self.maze_grid.is_path((x, y)) && self.target != (x, y) {
+                self.fill_cell(x, y, &color);
+            }
+        }
+
+        // helper function to color a cell of the final path
+        fn path_cell(&mut self, x: usize, y: usize) {
+            self.trace.record(Event::Path((x, y)));
+            if self.maze_grid.is_path((x, y)) && self.target != (x, y) {
+                self.fill_cell(x, y, &self.theme.final_path.clone());
+            }
+        }
+
+        // helper function to paint a cell and write a short label on top of it
+        fn label_cell(&mut self, x: usize, y: usize, color: &str, label: &str, ink: &str) {
+            self.fill_cell(x, y, color);
+            self.pen().set_font(&format!("bold {}px sans-serif", (self.block_size * 1.4).round()));
+            self.pen().set_text_align("center");
+            self.pen().set_text_baseline("middle");
+            self.pen().set_fill_style_str(ink);
+            let (centre_x, centre_y) = self.cell_centre(x, y);
+            let _ = self.cell_context(x, y).fill_text(label, centre_x, centre_y);
+        }
+
+        // helper function to paint any cell, walls and players included
+        fn fill_cell(&mut self, x: usize, y: usize, color: &str) {
+            if self.buffer_cell(x, y, color, false) {
+                return;
+            }
+            self.pen().set_fill_style_str(color);
+            if self.links.topology == Topology::Square {
+                self.cell_context(x, y).fill_rect(
+                    x as f64 * self.block_size, 
+                    y as f64 * self.block_size, 
+                    self.block_size, 
+                    self.block_size,
+                );
+            } else {
+                self.trace_cell(x, y);
+                self.cell_context(x, y).fill();
             }
         }
     }
@@ -327,24 +683,379 @@
     }
 
 
-    fn parse_maze(maze_data: &str) -> Result<Vec<Vec<CellType>>, JsValue> {
+    // helper function to split the maze text into its floors, each a list of rows, skipping
+    // empty lines and `@` metadata. floors are separated by a line of dashes (`---`)
+    fn maze_floors(maze_data: &str) -> Vec<Vec<&str>> {
+        let mut floors = vec![Vec::new()];
+        for row in maze_data.split('\n').map(|row| row.trim_end_matches('\r').trim()) {
+            if row.len() >= 3 && row.chars().all(|cell| cell == '-') {
+                floors.push(Vec::new());
+            } else if !row.is_empty() && !row.starts_with('@') { // Avoid adding empty rows if the last line was empty
+                if let Some(floor) = floors.last_mut() {
+                    floor.push(row);
+                }
+            }
+        }
+        floors.retain(|floor| !floor.is_empty());
+        floors
+    }
+
+    // how far apart the floors are laid out in the grid: the widest row plus at least two
+    // wall columns, so knocking out a single wall never joins two floors, rounded up to even
+    // so triangles keep pointing the same way on every floor
+    fn floor_stride(floors: &[Vec<&str>]) -> usize {
+        let width = floors.iter().flatten().map(|row| row.chars().count()).max().unwrap_or(0);
+        (width + 3) & !1
+    }
+
+    // the rows as the grid holds them. a single floor as written; several floors side by side,
+    // every row padded with walls to the stride, so cell (x, y) of floor z sits at (x + z * stride, y)
+    fn maze_rows(maze_data: &str) -> Vec<String> {
+        let floors = maze_floors(maze_data);
+        if floors.len() <= 1 {
+            return floors.into_iter().flatten().map(str::to_string).collect();
+        }
+        let stride = floor_stride(&floors);
+        let height = floors.iter().map(|floor| floor.len()).max().unwrap_or(0);
+        (0..height).map(|y| {
+            floors.iter().map(|floor| {
+                let row = floor.get(y).copied().unwrap_or("");
+                format!("{}{}", row, "*".repeat(stride - row.chars().count()))
+            }).collect()
+        }).collect()
+    }
+
+
+    // the `@` metadata lines of the maze text, split into their words
+    fn maze_metadata(maze_data: &str) -> impl Iterator<Item = Vec<&str>> {
+        maze_data.split('\n')
+            .map(|row| row.trim())
+            .filter_map(|row| row.strip_prefix('@'))
+            .map(|row| row.split_whitespace().collect())
+    }
+
+
+    fn parse_maze(maze_data: &str) -> Result<Grid, String> {
         let mut maze_grid = Vec::new();
-        for (_, row) in maze_data.split('\n').enumerate() {
-            let trimmed_row = row.trim_end_matches('\r').trim();
+        for trimmed_row in maze_rows(maze_data).iter() {
             let mut row_cells = Vec::new();
-            for (_, cell) in trimmed_row.chars().enumerate() {
+            for cell in trimmed_row.chars() {
                 match cell {
                     '*' => row_cells.push(CellType::Wall),
                     ' ' => row_cells.push(CellType::Path),
+                    '0'..='9' => row_cells.push(CellType::Path), // a portal, see parse_portals
+                    'a'..='z' => row_cells.push(CellType::Path), // a key, see parse_locks
+                    'A'..='Z' => row_cells.push(CellType::Wall), // a door, closed until its key is found
+                    '>' | '<' => row_cells.push(CellType::Path), // a stair, see parse_floors
                     _ => {
                         let error_message = format!("Invalid maze data!--->{}", cell);
-                        return Err(JsValue::from_str(&error_message));
+                        return Err(error_message);
                     },
                 }
             }
-            if !trimmed_row.is_empty() { // Avoid adding empty rows if the last line was empty
-                    maze_grid.push(row_cells);
+            maze_grid.push(row_cells);
+        }
+        Ok(Grid::from_rows(&maze_grid))
+    }
+
+
+    // the portal cells of the maze text, each with its digit.
+    // every digit that is used must be used exactly twice, once for each end
+    fn parse_portals(maze_data: &str) -> Result<HashMap<(usize, usize), char>, String> {
+        let mut labels = HashMap::new();
+        let mut counts: HashMap<char, usize> = HashMap::new();
+        for (y, trimmed_row) in maze_rows(maze_data).iter().enumerate() {
+            for (x, cell) in trimmed_row.chars().enumerate() {
+                if cell.is_ascii_digit() {
+                    labels.insert((x, y), cell);
+                    *counts.entry(cell).or_insert(0) += 1;
+                }
+            }
+        }
+        if let Some((digit, _)) = counts.iter().find(|&(_, &count)| count != 2) {
+            return Err(format!("Portal {} needs exactly two ends!", digit));
+        }
+        Ok(labels)
+    }
+
+
+    // the floors of the maze text and the stairs between them. a `>` leads up to the `<` at
+    // the same spot on the next floor, and a stair without one at the other end is refused
+    fn parse_floors(maze_data: &str) -> Result<Floors, String> {
+        let count = maze_floors(maze_data).len();
+        let stride = floor_stride(&maze_floors(maze_data));
+        let rows = maze_rows(maze_data);
+        let symbol = |(x, y): (usize, usize)| rows.get(y).and_then(|row| row.chars().nth(x));
+
+        let mut floors = Floors { count, stride, stairs: HashMap::new() };
+        for (y, row) in rows.iter().enumerate() {
+            for (x, cell) in row.chars().enumerate() {
+                let other_end = match cell {
+                    '>' if count > 1 => Some((x + stride, y)).filter(|&above| symbol(above) == Some('<')),
+                    '<' if count > 1 => x.checked_sub(stride).map(|below| (below, y)).filter(|&below| symbol(below) == Some('>')),
+                    '>' | '<' => None,
+                    _ => continue,
+                };
+                let Some(other_end) = other_end else {
+                    let (x, y, z) = floors.locate((x, y));
+                    return Err(format!("Stair {} at {} {} on floor {} leads nowhere!", cell, x, y, z));
+                };
+                floors.stairs.insert((x, y), other_end);
+            }
+        }
+        Ok(floors)
+    }
+
+
+    // the keys and doors of the maze text. a door nobody can open is refused,
+    // the same key may lie in several places
+    fn parse_locks(maze_data: &str) -> Result<Locks, String> {
+        let mut locks = Locks::default();
+        for (y, trimmed_row) in maze_rows(maze_data).iter().enumerate() {
+            for (x, cell) in trimmed_row.chars().enumerate() {
+                if cell.is_ascii_lowercase() {
+                    locks.keys.insert((x, y), cell as u8 - b'a');
+                } else if cell.is_ascii_uppercase() {
+                    locks.doors.insert((x, y), cell as u8 - b'A');
+                }
+            }
+        }
+        if let Some(&door) = locks.doors.values().find(|door| !locks.keys.values().any(|key| key == *door)) {
+            return Err(format!("Door {} has no key!", (b'A' + door) as char));
+        }
+        Ok(locks)
+    }
+
+
+    // the gates and patrols of the maze text, see pathfinding::timed for the format
+    fn parse_schedule(maze_data: &str, maze_grid: &Grid) -> Result<Schedule, String> {
+        let mut schedule = Schedule::default();
+        for words in maze_metadata(maze_data) {
+            if words.first() == Some(&"topology") {
+                continue; // see parse_topology
+            }
+            let invalid = || format!("Invalid maze metadata!--->@{}", words.join(" "));
+            let numbers = words.iter().skip(1)
+                .map(|word| word.parse::<usize>())
+                .collect::<Result<Vec<usize>, _>>()
+                .map_err(|_| invalid())?;
+            let open = |x: usize, y: usize| maze_grid.is_path((x, y));
+
+            match (words.first().copied(), numbers.as_slice()) {
+                (Some("gate"), &[x, y, period]) | (Some("gate"), &[x, y, period, _]) if open(x, y) && period > 0 => {
+                    let offset = numbers.get(3).copied().unwrap_or(0);
+                    schedule.gates.push(Gate { cell: (x, y), period, offset });
+                },
+                (Some("patrol"), points) if points.len() >= 4 && points.len() % 2 == 0 => {
+                    let mut route = vec![(points[0], points[1])];
+                    for leg in points.chunks(2).collect::<Vec<_>>().windows(2) {
+                        let (from, to) = ((leg[0][0], leg[0][1]), (leg[1][0], leg[1][1]));
+                        if from.0 != to.0 && from.1 != to.1 {
+                            return Err(invalid()); // legs must be straight
+                        }
+                        let mut cell = from;
+                        while cell != to {
+                            cell = (
+                                if to.0 > cell.0 { cell.0 + 1 } else if to.0 < cell.0 { cell.0 - 1 } else { cell.0 },
+                                if to.1 > cell.1 { cell.1 + 1 } else if to.1 < cell.1 { cell.1 - 1 } else { cell.1 },
+                            );
+                            route.push(cell);
+                        }
+                    }
+                    if !route.iter().all(|&(x, y)| open(x, y)) {
+                        return Err(invalid());
+                    }
+                    schedule.patrols.push(Patrol { route });
+                },
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(schedule)
+    }
+
+
+    // the tiling named by an `@topology square|hex|triangle` line, square without one
+    fn parse_topology(maze_data: &str) -> Result<Topology, String> {
+        let mut topology = Topology::Square;
+        for words in maze_metadata(maze_data) {
+            if let [keyword, name] = words.as_slice() && *keyword == "topology" {
+                topology = Topology::from_name(name)
+                    .ok_or_else(|| format!("Unknown topology!--->{}", name))?;
+            }
+        }
+        Ok(topology)
+    }
+
+
+    // a maze read from text without a canvas, for the maze command line tool
+    pub struct MazeFile {
+        pub grid: Grid,
+        pub links: Links,
+        pub portal_labels: HashMap<(usize, usize), char>, // the digit of every portal cell
+        pub locks: Locks,
+        pub schedule: Schedule,
+    }
+
+    // the grid, what links its cells, the keys and doors, and the gates and patrols of maze text
+    pub fn read_maze(maze_data: &str) -> Result<MazeFile, String> {
+        let grid = parse_maze(maze_data)?;
+        let portal_labels = parse_portals(maze_data)?;
+        let links = Links::new(
+            parse_topology(maze_data)?,
+            Portals { pairs: link_portals(&portal_labels), cost: 1 },
+            parse_floors(maze_data)?,
+        );
+        let locks = parse_locks(maze_data)?;
+        let schedule = parse_schedule(maze_data, &grid)?;
+        Ok(MazeFile { grid, links, portal_labels, locks, schedule })
+    }
+
+
+    // pairs up the two ends of every portal, both ways round
+    fn link_portals(labels: &HashMap<(usize, usize), char>) -> HashMap<(usize, usize), (usize, usize)> {
+        let mut links = HashMap::new();
+        for (&cell, &digit) in labels.iter() {
+            if let Some((&partner, _)) = labels.iter().find(|&(&other, &label)| label == digit && other != cell) {
+                links.insert(cell, partner);
             }
         }
-        Ok(maze_grid)
+        links
+    }
+
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        const SPECIAL: &str = "\
+*********
+*a 1  *B*
+* *** * *
+*A 1 b  *
+*********
+@gate 2 3 2 1
+@patrol 5 3 7 3 7 2
+";
+
+        #[test]
+        fn parse_maze_reads_symbols_as_walls_and_paths() {
+            let grid = parse_maze(SPECIAL).expect("valid maze text");
+            assert_eq!((grid.width(), grid.height()), (9, 5));
+            assert!(grid.is_path((1, 1)));  // a key
+            assert!(grid.is_path((3, 1)));  // a portal
+            assert!(!grid.is_path((1, 3))); // a door
+            assert!(!grid.is_path((0, 0)));
+            assert_eq!(parse_maze("***\n*?*\n***"), Err("Invalid maze data!--->?".to_string()));
+        }
+
+        #[test]
+        fn rows_are_padded_with_walls() {
+            let grid = parse_maze("*****\n* *\n*****").expect("valid maze text");
+            assert_eq!(grid.width(), 5);
+            assert!(grid.is_path((1, 1)));
+            assert!(!grid.is_path((3, 1)));
+        }
+
+        #[test]
+        fn parse_portals_pairs_the_digits() {
+            let labels = parse_portals(SPECIAL).expect("two ends each");
+            assert_eq!(labels.len(), 2);
+            let pairs = link_portals(&labels);
+            assert_eq!(pairs.get(&(3, 1)), Some(&(3, 3)));
+            assert_eq!(pairs.get(&(3, 3)), Some(&(3, 1)));
+            assert_eq!(parse_portals("*1 1 1*"), Err("Portal 1 needs exactly two ends!".to_string()));
+        }
+
+        #[test]
+        fn parse_locks_needs_a_key_for_every_door() {
+            let locks = parse_locks(SPECIAL).expect("every door has its key");
+            assert_eq!(locks.keys.get(&(1, 1)), Some(&0));
+            assert_eq!(locks.keys.get(&(5, 3)), Some(&1));
+            assert_eq!(locks.doors.get(&(1, 3)), Some(&0));
+            assert_eq!(locks.doors.get(&(7, 1)), Some(&1));
+            assert_eq!(parse_locks("*a C*").map(|_| ()), Err("Door C has no key!".to_string()));
+        }
+
+        #[test]
+        fn parse_schedule_reads_gates_and_patrols() {
+            let grid = parse_maze(SPECIAL).expect("valid maze text");
+            let schedule = parse_schedule(SPECIAL, &grid).expect("valid metadata");
+            assert_eq!(schedule.gates.len(), 1);
+            assert_eq!((schedule.gates[0].cell, schedule.gates[0].period, schedule.gates[0].offset), ((2, 3), 2, 1));
+            assert_eq!(schedule.patrols.len(), 1);
+            assert_eq!(schedule.patrols[0].route, vec![(5, 3), (6, 3), (7, 3), (7, 2)]);
+
+            let refused = |metadata: &str| parse_schedule(&format!("{}{}\n", SPECIAL, metadata), &grid).is_err();
+            assert!(refused("@gate 0 0 2"));         // on a wall
+            assert!(refused("@gate 2 3 0"));         // never changes
+            assert!(refused("@patrol 5 3 7 2"));     // not a straight leg
+            assert!(refused("@patrol 1 1 1 3"));     // through a door
+            assert!(refused("@gate 2 three 2"));
+            assert!(refused("@teleport 1 1"));
+            assert!(!refused("@topology square"));
+        }
+
+        #[test]
+        fn parse_topology_defaults_to_square() {
+            assert_eq!(parse_topology(SPECIAL), Ok(Topology::Square));
+            assert_eq!(parse_topology("***\n@topology hex"), Ok(Topology::Hex));
+            assert_eq!(parse_topology("@topology Triangle"), Ok(Topology::Triangle));
+            assert_eq!(parse_topology("@topology octagon"), Err("Unknown topology!--->octagon".to_string()));
+        }
+
+        const FLOORS: &str = "\
+*****
+*  >*
+*****
+---
+*****
+*> <*
+*****
+---
+****
+*<**
+****
+";
+
+        #[test]
+        fn parse_floors_joins_the_stairs() {
+            let floors = parse_floors(FLOORS).expect("every stair has another end");
+            assert_eq!((floors.count, floors.stride), (3, 8));
+            assert_eq!(floors.partner((3, 1)), Some((11, 1)));
+            assert_eq!(floors.partner((11, 1)), Some((3, 1)));
+            assert_eq!(floors.partner((9, 1)), Some((17, 1)));
+            assert_eq!(floors.locate((17, 1)), (1, 1, 2));
+            assert_eq!(floors.stairs.len(), 4);
+
+            let grid = parse_maze(FLOORS).expect("valid maze text");
+            assert_eq!((grid.width(), grid.height()), (24, 3)); // every floor padded to the stride
+            assert!((5..8).all(|x| !grid.is_path((x, 1))), "walls between the floors");
+        }
+
+        #[test]
+        fn parse_floors_refuses_a_stair_to_nowhere() {
+            assert!(parse_floors("*****\n*  >*\n*****").is_err());
+            assert_eq!(
+                parse_floors("*****\n*  >*\n*****\n---\n*****\n*>  *\n*****"),
+                Err("Stair > at 3 1 on floor 0 leads nowhere!".to_string()),
+            );
+        }
+
+        #[test]
+        fn read_maze_gathers_everything() {
+            let file = read_maze(SPECIAL).expect("valid maze text");
+            assert_eq!(file.grid, parse_maze(SPECIAL).expect("valid maze text"));
+            assert_eq!(file.links.portals.partner((3, 1)), Some((3, 3)));
+            assert_eq!(file.portal_labels.get(&(3, 3)), Some(&'1'));
+            assert_eq!(file.locks.doors.len(), 2);
+            assert_eq!(file.schedule.gates.len() + file.schedule.patrols.len(), 2);
+            assert!(read_maze("*?*").is_err());
+        }
+
+        #[test]
+        fn floors_are_laid_out_side_by_side() {
+            let grid = parse_maze(FLOORS).expect("valid maze text");
+            let again = parse_maze(&maze_rows(FLOORS).join("\n")).expect("valid maze text");
+            assert_eq!(grid, again);
+        }
     }
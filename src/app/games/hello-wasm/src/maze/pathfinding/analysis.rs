@@ -0,0 +1,51 @@
+use crate::maze::fields::distance_field;
+use super::UNREACHABLE;
+use super::Links;
+use super::components::Components;
+use super::grid::Grid;
+
+// what a maze is like as a whole: how its open cells are connected to each other, counted
+// over the moves the solvers can make (portals and stairs included)
+
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Analysis {
+    pub cells: usize,
+    pub open: usize,
+    pub dead_ends: usize,  // open cells with one way out
+    pub corridors: usize,  // two ways out
+    pub junctions: usize,  // three or more
+    pub isolated: usize,   // none at all
+    pub regions: usize,    // connected regions of open cells
+    pub largest_region: usize, // the open cells in the biggest of them
+    pub reachable: usize,  // open cells reachable from the start, the start included
+    pub shortest: Option<usize>, // the cost of the cheapest route from the start to the target
+}
+
+pub fn analyse(grid: &Grid, links: &Links, start: (usize, usize), target: (usize, usize)) -> Analysis {
+    let mut analysis = Analysis { cells: grid.cell_count(), ..Analysis::default() };
+    for index in (0..grid.cell_count()).filter(|&index| grid.is_open(index)) {
+        analysis.open += 1;
+        match grid.neighbours(links, index).count() {
+            0 => analysis.isolated += 1,
+            1 => analysis.dead_ends += 1,
+            2 => analysis.corridors += 1,
+            _ => analysis.junctions += 1,
+        }
+    }
+
+    let components = Components::new(grid, links);
+    analysis.regions = components.sizes.len();
+    analysis.largest_region = components.sizes.iter().copied().max().unwrap_or(0);
+
+    if grid.is_path(start) {
+        let distance = distance_field(grid, links, start);
+        analysis.reachable = distance.iter().filter(|&&cost| cost != UNREACHABLE).count();
+        analysis.shortest = Some(target)
+            .filter(|&cell| grid.is_path(cell))
+            .map(|cell| distance[grid.index(cell)])
+            .filter(|&cost| cost != UNREACHABLE)
+            .map(|cost| cost as usize);
+    }
+    analysis
+}
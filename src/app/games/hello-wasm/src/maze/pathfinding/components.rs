@@ -0,0 +1,180 @@
+use super::Links;
+use super::grid::Grid;
+
+// the connected regions of a maze: every open cell labelled with the region it is in, over the
+// moves the solvers can make (portals and stairs included). all of those moves work both ways,
+// so two cells with the same label can reach each other and two with different labels cannot,
+// which answers "can this cell be reached from that one" without searching
+
+// the label of a wall
+pub const NO_REGION: u32 = u32::MAX;
+
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Components {
+    width: usize,
+    height: usize,
+    revision: u64,       // of the grid the labels are for
+    links_revision: u64, // and of the links
+    pub labels: Vec<u32>,  // the region of every cell, row by row over the widest row
+    pub sizes: Vec<usize>, // the open cells in each region, numbered in the order they are met row by row
+}
+
+impl Components {
+    pub fn new(grid: &Grid, links: &Links) -> Components {
+        let mut components = Components::default();
+        components.sync(grid, links);
+        components
+    }
+
+    // labels the regions again if the grid or the links changed since the last time, which
+    // their revisions tell without looking at either. returns whether it did
+    pub fn sync(&mut self, grid: &Grid, links: &Links) -> bool {
+        if grid.revision() == self.revision && links.revision() == self.links_revision {
+            return false;
+        }
+        self.labels = vec![NO_REGION; grid.cell_count()];
+        self.sizes.clear();
+
+        // a flood fill from every open cell no earlier fill got to
+        let mut stack = Vec::new();
+        for first in 0..grid.cell_count() {
+            if !grid.is_open(first) || self.labels[first] != NO_REGION {
+                continue;
+            }
+            let region = self.sizes.len() as u32;
+            let mut size = 0;
+            self.labels[first] = region;
+            stack.push(first);
+            while let Some(index) = stack.pop() {
+                size += 1;
+                for (next, _) in grid.neighbours(links, index) {
+                    if self.labels[next] == NO_REGION {
+                        self.labels[next] = region;
+                        stack.push(next);
+                    }
+                }
+            }
+            self.sizes.push(size);
+        }
+
+        (self.width, self.height, self.revision) = (grid.width(), grid.height(), grid.revision());
+        self.links_revision = links.revision();
+        true
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    // the region of `cell`, None for walls and cells outside the maze
+    pub fn region(&self, (x, y): (usize, usize)) -> Option<usize> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        match self.labels.get(y * self.width + x) {
+            Some(&label) if label != NO_REGION => Some(label as usize),
+            _ => None,
+        }
+    }
+
+    // whether `to` can be reached from `from`, both open cells
+    pub fn connected(&self, from: (usize, usize), to: (usize, usize)) -> bool {
+        match (self.region(from), self.region(to)) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maze::{read_maze, CellType};
+    use crate::maze::pathfinding::bfs::distances_from;
+    use crate::maze::pathfinding::generate::generate;
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+
+    const ROOMS: &str = "\
+*********
+*  *  * *
+*  *  ***
+*********
+";
+
+    #[test]
+    fn regions_are_numbered_row_by_row() {
+        let file = read_maze(ROOMS).expect("valid maze text");
+        let components = Components::new(&file.grid, &file.links);
+        assert_eq!(components.sizes, vec![4, 4, 1]);
+        assert_eq!(components.region((1, 1)), Some(0));
+        assert_eq!(components.region((5, 2)), Some(1));
+        assert_eq!(components.region((7, 1)), Some(2));
+        assert_eq!(components.region((3, 1)), None); // a wall
+        assert_eq!(components.region((9, 1)), None); // outside
+        assert_eq!(components.region((1, 9)), None);
+        assert!(components.connected((1, 1), (2, 2)));
+        assert!(!components.connected((1, 1), (4, 1)));
+        assert!(!components.connected((1, 1), (3, 1)));
+    }
+
+    #[test]
+    fn portals_and_stairs_join_regions() {
+        let file = read_maze("*********\n*1 *  *1*\n*********\n").expect("valid maze text");
+        let components = Components::new(&file.grid, &file.links);
+        assert_eq!(components.sizes, vec![3, 2]);
+        assert!(components.connected((2, 1), (7, 1)));
+
+        let file = read_maze("****\n*> *\n****\n---\n****\n*< *\n****\n").expect("valid maze text");
+        let components = Components::new(&file.grid, &file.links);
+        assert_eq!(components.sizes, vec![4]);
+    }
+
+    #[test]
+    fn sync_only_works_when_the_grid_changed() {
+        let file = read_maze(ROOMS).expect("valid maze text");
+        let mut grid = file.grid.clone();
+        let mut components = Components::new(&grid, &file.links);
+        assert!(!components.sync(&grid, &file.links));
+        assert!(!components.sync(&file.grid, &file.links)); // a copy has the same revision
+
+        // setting a cell to what it already is changes nothing
+        grid.set((3, 1), CellType::Wall);
+        assert!(!components.sync(&grid, &file.links));
+
+        grid.set((3, 1), CellType::Path);
+        assert!(components.sync(&grid, &file.links));
+        assert_eq!(components.sizes, vec![9, 1]);
+
+        let mut links = file.links.clone();
+        links.portals.cost = 3;
+        assert!(!components.sync(&grid, &links)); // a copy has the same revision, until it is told otherwise
+        links.changed();
+        assert!(components.sync(&grid, &links));
+    }
+
+    // two cells are in the same region exactly when a search from one gets to the other
+    #[test]
+    fn regions_agree_with_search() {
+        let mut rng = StdRng::seed_from_u64(9);
+        for _ in 0..5 {
+            let mut grid = generate(21, 15, 10, &mut rng);
+            for _ in 0..30 {
+                let cell = (rng.gen_range(1..20), rng.gen_range(1..14));
+                grid.set(cell, CellType::Wall);
+            }
+            let links = Links::default();
+            let components = Components::new(&grid, &links);
+            let open: Vec<(usize, usize)> = grid.open_cells().collect();
+            for &from in open.iter().step_by(7) {
+                let distance = distances_from(&grid, &links, from);
+                for &to in open.iter() {
+                    assert_eq!(components.connected(from, to), distance[grid.index(to)] != usize::MAX);
+                }
+            }
+            assert_eq!(components.sizes.iter().sum::<usize>(), open.len());
+        }
+    }
+}
@@ -0,0 +1,325 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use super::{neighbours, Links};
+use super::bfs::distances_from;
+use super::grid::Grid;
+
+
+// one of the computer agents that share the maze
+#[derive(Clone, Debug)]
+pub struct Agent {
+    pub start: (usize, usize),
+    pub goal: (usize, usize),
+    pub color: String,
+}
+
+// whether a new agent from `start` to `goal` would share a cell with the start or the goal of
+// one already placed. an agent waiting at its goal would block the start another has to leave,
+// or the goal another has to reach, and no planning gets round that
+pub fn is_taken(agents: &[Agent], start: (usize, usize), goal: (usize, usize)) -> bool {
+    agents.iter().any(|agent| [agent.start, agent.goal].iter().any(|&cell| cell == start || cell == goal))
+}
+
+
+// a move from one cell to another, leaving at the given tick
+type TimedMove = ((usize, usize), (usize, usize), usize);
+
+// a space-time reservation table: which cells (and which moves between cells)
+// are already claimed by a higher priority agent at which tick
+#[derive(Default)]
+struct Reservations {
+    cells: HashSet<((usize, usize), usize)>,
+    moves: HashSet<TimedMove>,
+}
+
+impl Reservations {
+    fn reserve_route(&mut self, route: &[(usize, usize)], first_tick: usize) {
+        for (offset, &cell) in route.iter().enumerate() {
+            self.cells.insert((cell, first_tick + offset));
+        }
+        for (offset, pair) in route.windows(2).enumerate() {
+            self.moves.insert((pair[0], pair[1], first_tick + offset));
+        }
+    }
+
+    fn is_free(&self, from: (usize, usize), to: (usize, usize), tick: usize) -> bool {
+        // the cell must be empty on arrival, and nobody may be coming the other way (a swap)
+        !self.cells.contains(&(to, tick + 1)) && !self.moves.contains(&(to, from, tick))
+    }
+}
+
+
+// windowed hierarchical cooperative a* (whca*): agents plan one after another in space-time,
+// each avoiding the cells and moves the agents before it reserved, but only `window` ticks ahead.
+// after half a window everyone replans with the priorities rotated, so nobody is always last.
+// beyond the window the true maze distance to the goal is used as the heuristic.
+// a portal jump takes an agent one tick, like any other move.
+// returns one position per tick for every agent, all of the same length
+pub fn plan_whca(
+    grid: &Grid,
+    links: &Links,
+    agents: &[Agent],
+    window: usize,
+    max_ticks: usize,
+) -> Result<Vec<Vec<(usize, usize)>>, String> {
+    let window = window.max(2);
+    // true distances to each goal by grid index, shared by every window
+    let distance_maps: Vec<Vec<usize>> = agents.iter()
+        .map(|agent| distances_from(grid, links, agent.goal))
+        .collect();
+    for (index, agent) in agents.iter().enumerate() {
+        if distance_maps[index][grid.index(agent.start)] == usize::MAX {
+            return Err(format!("Agent {} cannot reach its goal!", index));
+        }
+    }
+
+    let mut routes: Vec<Vec<(usize, usize)>> = agents.iter().map(|agent| vec![agent.start]).collect();
+    let mut tick = 0;
+    let mut rotation = 0;
+
+    while agents.iter().enumerate().any(|(index, agent)| *routes[index].last().unwrap_or(&agent.start) != agent.goal) {
+        if tick >= max_ticks {
+            return Err(format!("Agents did not all arrive within {} ticks!", max_ticks));
+        }
+
+        let mut order: Vec<usize> = (0..agents.len()).map(|i| (i + rotation) % agents.len()).collect();
+        let mut planned: Vec<Vec<(usize, usize)>> = vec![Vec::new(); agents.len()];
+        // an agent that gets boxed in is moved to the front and the window planned again.
+        // boxed in a second time it waits where it is, and since that claims its cell for the
+        // whole window it is reserved before anybody else plans
+        let mut promoted: Vec<usize> = Vec::new();
+        let mut waiting: Vec<usize> = Vec::new();
+        loop {
+            let mut reservations = Reservations::default();
+            for &index in waiting.iter() {
+                let from = *routes[index].last().unwrap_or(&agents[index].start);
+                planned[index] = vec![from; window + 1];
+                reservations.reserve_route(&planned[index], tick);
+            }
+            let mut boxed_in = None;
+            for &index in order.iter().filter(|index| !waiting.contains(index)) {
+                let from = *routes[index].last().unwrap_or(&agents[index].start);
+                let Some(partial) = window_search(grid, links, &distance_maps[index], &reservations, from, tick, window) else {
+                    boxed_in = Some(index);
+                    break;
+                };
+                reservations.reserve_route(&partial, tick);
+                planned[index] = partial;
+            }
+            match boxed_in {
+                None => break,
+                Some(index) if !promoted.contains(&index) => {
+                    promoted.push(index);
+                    order.retain(|&i| i != index);
+                    order.insert(0, index);
+                },
+                Some(index) => waiting.push(index),
+            }
+        }
+
+        // follow the first half of the window, then replan
+        let advance = (window / 2).max(1);
+        for (index, partial) in planned.into_iter().enumerate() {
+            routes[index].extend(partial.into_iter().skip(1).take(advance));
+        }
+        tick += advance;
+        rotation += 1;
+    }
+
+    // trim the shared tail where everybody is already waiting at their goal
+    let mut length = routes.iter().map(|route| route.len()).max().unwrap_or(0);
+    while length > 1 && routes.iter().all(|route| route[length - 1] == route[length - 2]) {
+        length -= 1;
+    }
+    for route in routes.iter_mut() {
+        route.truncate(length);
+    }
+    Ok(routes)
+}
+
+
+// space-time a* for one agent over ticks `tick..=tick + window`. the search ends when the
+// window runs out, and the cost is the ticks spent plus the true distance left to the goal;
+// waiting on the goal (the one cell at distance 0) is free, so agents that are home prefer to stay there
+fn window_search(
+    grid: &Grid,
+    links: &Links,
+    distance: &[usize],
+    reservations: &Reservations,
+    from: (usize, usize),
+    tick: usize,
+    window: usize,
+) -> Option<Vec<(usize, usize)>> {
+    type State = ((usize, usize), usize); // (cell, ticks into the window)
+    let mut g_score: HashMap<State, usize> = HashMap::new();
+    let mut parent: HashMap<State, State> = HashMap::new();
+    let mut pq = BinaryHeap::new();
+
+    let start = (from, 0);
+    g_score.insert(start, 0);
+    pq.push(Reverse((distance[grid.index(from)], 0, start)));
+
+    while let Some(Reverse((_, g, state))) = pq.pop() {
+        if g > *g_score.get(&state).unwrap_or(&usize::MAX) {
+            continue;
+        }
+        let (cell, offset) = state;
+        if offset == window {
+            let mut route = vec![cell];
+            let mut current = state;
+            while let Some(&prev) = parent.get(&current) {
+                route.push(prev.0);
+                current = prev;
+            }
+            route.reverse();
+            return Some(route);
+        }
+
+        // every move, and waiting in place
+        let moves = neighbours(grid, links, cell).map(|(next, _)| next).chain(std::iter::once(cell));
+        for next in moves {
+            if !reservations.is_free(cell, next, tick + offset) {
+                continue;
+            }
+            let at_goal = distance[grid.index(cell)] == 0;
+            let step_cost = if next == cell && at_goal { 0 } else { 1 };
+            let next_state = (next, offset + 1);
+            let next_g = g + step_cost;
+            if next_g < *g_score.get(&next_state).unwrap_or(&usize::MAX) {
+                g_score.insert(next_state, next_g);
+                parent.insert(next_state, state);
+                pq.push(Reverse((next_g + distance[grid.index(next)], next_g, next_state)));
+            }
+        }
+    }
+    None
+}
+
+
+// checks a finished plan for two agents on one cell, or two agents swapping cells;
+// returns the first tick where that happens
+pub fn first_conflict(routes: &[Vec<(usize, usize)>]) -> Option<usize> {
+    let length = routes.iter().map(|route| route.len()).max().unwrap_or(0);
+    let at = |route: &Vec<(usize, usize)>, tick: usize| route[tick.min(route.len() - 1)];
+    for tick in 0..length {
+        let mut taken = HashSet::new();
+        for route in routes.iter() {
+            if !taken.insert(at(route, tick)) {
+                return Some(tick);
+            }
+        }
+        if tick + 1 < length {
+            for (a, first) in routes.iter().enumerate() {
+                for second in routes.iter().skip(a + 1) {
+                    if at(first, tick) == at(second, tick + 1) && at(first, tick + 1) == at(second, tick) {
+                        return Some(tick);
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maze::read_maze;
+    use crate::maze::pathfinding::generate::generate;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+
+    fn agent(start: (usize, usize), goal: (usize, usize)) -> Agent {
+        Agent { start, goal, color: "black".to_string() }
+    }
+
+    #[test]
+    fn starts_and_goals_are_never_shared() {
+        let placed = [agent((1, 1), (5, 1))];
+        assert!(is_taken(&placed, (1, 1), (3, 3)));
+        assert!(is_taken(&placed, (3, 3), (5, 1)));
+        // one agent's goal is another's start, or the other way round
+        assert!(is_taken(&placed, (5, 1), (3, 3)));
+        assert!(is_taken(&placed, (3, 3), (1, 1)));
+        assert!(!is_taken(&placed, (2, 1), (4, 1)));
+    }
+
+    #[test]
+    fn reservations_block_the_cell_and_the_swap() {
+        let mut reservations = Reservations::default();
+        reservations.reserve_route(&[(1, 1), (2, 1), (3, 1)], 4);
+        // (2, 1) is taken at tick 5, so nobody may arrive there leaving at tick 4
+        assert!(!reservations.is_free((2, 2), (2, 1), 4));
+        assert!(reservations.is_free((2, 2), (2, 1), 5));
+        // (1, 1) -> (2, 1) leaves at tick 4, coming the other way at the same time is a swap
+        assert!(!reservations.is_free((2, 1), (1, 1), 4));
+        assert!(reservations.is_free((2, 1), (1, 1), 6));
+    }
+
+    #[test]
+    fn conflicts_on_a_cell_and_in_a_swap_are_found() {
+        let apart = vec![vec![(1, 1), (2, 1)], vec![(4, 1), (3, 1)]];
+        assert_eq!(first_conflict(&apart), None);
+        let same_cell = vec![vec![(1, 1), (2, 1)], vec![(3, 1), (2, 1)]];
+        assert_eq!(first_conflict(&same_cell), Some(1));
+        let swap = vec![vec![(1, 1), (2, 1), (3, 1)], vec![(3, 1), (3, 1), (2, 1)]];
+        assert_eq!(first_conflict(&swap), Some(1));
+        // a route that has ended waits on its last cell
+        let arrived = vec![vec![(2, 1)], vec![(1, 1), (2, 1)]];
+        assert_eq!(first_conflict(&arrived), Some(1));
+    }
+
+    #[test]
+    fn agents_swap_ends_through_a_side_pocket() {
+        let file = read_maze("\
+*******
+*     *
+*** ***
+*******
+").expect("valid maze text");
+        let agents = [agent((1, 1), (5, 1)), agent((5, 1), (1, 1))];
+        let routes = plan_whca(&file.grid, &file.links, &agents, 8, 40).expect("the pocket lets them pass");
+        assert_eq!(first_conflict(&routes), None);
+        for (route, agent) in routes.iter().zip(agents.iter()) {
+            assert_eq!(route.first(), Some(&agent.start));
+            assert_eq!(route.last(), Some(&agent.goal));
+            for pair in route.windows(2) {
+                assert!(pair[0] == pair[1] || neighbours(&file.grid, &file.links, pair[0]).any(|(next, _)| next == pair[1]));
+            }
+        }
+        assert!(routes.iter().any(|route| route.contains(&(3, 2))), "one of them steps aside");
+    }
+
+    #[test]
+    fn corridor_without_a_pocket_cannot_be_swapped() {
+        let file = read_maze("*******\n*     *\n*******\n").expect("valid maze text");
+        let agents = [agent((1, 1), (5, 1)), agent((5, 1), (1, 1))];
+        // neither can get past, so they wait rather than walk into each other
+        assert_eq!(plan_whca(&file.grid, &file.links, &agents, 8, 40), Err("Agents did not all arrive within 40 ticks!".to_string()));
+    }
+
+    #[test]
+    fn plans_never_collide() {
+        let mut arrived = 0;
+        for seed in 0..6 {
+            let grid = generate(15, 11, 30, &mut StdRng::seed_from_u64(seed));
+            let mut rng = StdRng::seed_from_u64(seed);
+            let open: Vec<(usize, usize)> = grid.open_cells().collect();
+            let mut agents = Vec::new();
+            while agents.len() < 5 {
+                let (start, goal) = (*open.choose(&mut rng).unwrap(), *open.choose(&mut rng).unwrap());
+                if !is_taken(&agents, start, goal) {
+                    agents.push(agent(start, goal));
+                }
+            }
+            if let Ok(routes) = plan_whca(&grid, &Links::default(), &agents, 6, open.len() * 4) {
+                assert_eq!(first_conflict(&routes), None, "seed {}", seed);
+                arrived += 1;
+            }
+        }
+        assert!(arrived > 0);
+    }
+}
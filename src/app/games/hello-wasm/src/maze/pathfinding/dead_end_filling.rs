@@ -0,0 +1,137 @@
+use crate::maze::CellType;
+use wasm_bindgen::prelude::*;
+use crate::maze::MazeState;
+use super::{bfs, Links};
+use super::grid::{Grid, Scratch};
+
+
+// dead-end filling: keep walling up dead ends (except the start and the target) until none
+// are left. what stays open is every route between the two, in a perfect maze just the one
+pub struct DeadEndFillingSolver ;
+
+// the cells walled up, in order, and the path through what is left if there is one
+pub type Filled = (Vec<(usize, usize)>, Option<Vec<(usize, usize)>>);
+
+impl DeadEndFillingSolver {
+    pub async fn find_path(
+        &mut self,
+        match_state: &mut MazeState,
+        delay_ms: i32,
+    ) -> Result<JsValue, JsValue> {
+        let (grid, mut scratch) = match_state.take_search_buffers();
+        let links = match_state.links.clone();
+        let (filled, path) = fill(grid, &links, &mut scratch, match_state.computer_player, match_state.target);
+        match_state.give_search_buffers(scratch);
+
+        // the filling runs in one go, the cells it walled up are played back afterwards
+        for &(x, y) in filled.iter() {
+            match_state.color_cell(x, y, match_state.theme.filled.clone());
+            if delay_ms > 0 {
+                crate::maze::sleep(delay_ms).await?;
+            }
+        }
+        match path {
+            Some(path) => {
+                match_state.draw_path(&path);
+                Ok(JsValue::from_str(&format!("Path found! --> {} cells filled, path length {}", filled.len(), path.len() - 1)))
+            },
+            None => Ok(JsValue::from_str(&format!("Target not reachable! --> {} cells filled", filled.len()))),
+        }
+    }
+}
+
+
+// the filling itself, without drawing, on a copy of the grid. the path is the shortest
+// through what is left
+pub fn fill(
+    mut grid: Grid,
+    links: &Links,
+    scratch: &mut Scratch,
+    start: (usize, usize),
+    target: (usize, usize),
+) -> Filled {
+    let (start, target) = (grid.index(start), grid.index(target));
+
+    // a portal leads on to its partner, so a portal at the end of a corridor is no dead end
+    let fillable = |grid: &Grid, index: usize| {
+        index != start && index != target &&
+        grid.is_open(index) &&
+        grid.neighbours(links, index).nth(1).is_none()
+    };
+
+    scratch.begin(grid.cell_count());
+    scratch.queue.extend((0..grid.cell_count()).filter(|&index| fillable(&grid, index)));
+
+    let mut filled = Vec::new();
+    while let Some(index) = scratch.queue.pop_front() {
+        if !fillable(&grid, index) {
+            continue;
+        }
+        // filling a dead end can turn the cell next to it into the new dead end
+        let cell = grid.cell(index);
+        let next_to = grid.neighbours(links, index).next();
+        grid.set(cell, CellType::Wall);
+        filled.push(cell);
+        scratch.queue.extend(next_to.map(|(next, _)| next).filter(|&next| fillable(&grid, next)));
+    }
+
+    // in a maze with loops more than one corridor survives, take the shortest through them
+    let path = bfs::shortest_path(&grid, links, scratch, grid.cell(start), grid.cell(target));
+    (filled, path)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maze::read_maze;
+
+    fn fill_maze(text: &str, start: (usize, usize), target: (usize, usize)) -> Filled {
+        let file = read_maze(text).expect("valid maze text");
+        fill(file.grid, &file.links, &mut Scratch::default(), start, target)
+    }
+
+    #[test]
+    fn fills_back_to_the_route() {
+        // both ends of the corridor along the top carry a dead end, each filled back to it
+        let (filled, path) = fill_maze("\
+*******
+*     *
+* *** *
+* *   *
+*******
+", (1, 1), (5, 1));
+        assert_eq!(filled, vec![(1, 3), (3, 3), (1, 2), (4, 3), (5, 3), (5, 2)]);
+        assert_eq!(path, Some(vec![(1, 1), (2, 1), (3, 1), (4, 1), (5, 1)]));
+    }
+
+    #[test]
+    fn loop_survives_and_the_shorter_way_is_taken() {
+        let (filled, path) = fill_maze("\
+*******
+*   * *
+* * * *
+* *   *
+* *** *
+*     *
+*******
+", (1, 1), (5, 1));
+        assert!(filled.is_empty());
+        assert_eq!(path, Some(vec![(1, 1), (2, 1), (3, 1), (3, 2), (3, 3), (4, 3), (5, 3), (5, 2), (5, 1)]));
+    }
+
+    #[test]
+    fn walled_off_target_has_no_path() {
+        let (filled, path) = fill_maze("\
+*******
+*     *
+* *****
+* *   *
+*******
+", (1, 1), (3, 3));
+        assert_eq!(path, None);
+        // the start and the target are never filled
+        assert!(!filled.contains(&(1, 1)) && !filled.contains(&(3, 3)));
+        assert!(filled.contains(&(5, 1)) && filled.contains(&(5, 3)));
+    }
+}
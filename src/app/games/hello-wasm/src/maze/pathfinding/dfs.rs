@@ -1,7 +1,6 @@
-use crate::maze::CellType;
 use wasm_bindgen::prelude::*;
 use crate::maze::MazeState;
-use std::vec::Vec;
+use super::search::Frontier;
 
 pub struct DfsSolver ;
 
@@ -11,56 +10,12 @@ impl DfsSolver {
         match_state: &mut MazeState,
         delay_ms: i32,
     ) -> Result<JsValue, JsValue> {
-
-        let start =  match_state.computer_player;
-        let target = match_state.target;
-        let rows = match_state.maze_grid.len();
-        let cols = match_state.maze_grid[0].len();
-
-
-        // Initialize the queue and visited set
-        let mut queue = Vec::new(); // queue for the DFS
-        let mut visited = vec![vec![false; cols]; rows]; // visited set for the DFS
-        let mut parent = vec![vec![None; cols]; rows]; // parent set for the DFS
-   
-        // Add the start node to the queue
-        queue.push(start);
-        visited[start.1][start.0] = true;
-
-        let moves: [(i32, i32); 4] = [(0, -1), (0, 1), (-1, 0), (1, 0)];
-
-        while let Some(current) = queue.pop() {
-            if current == target {
-                // target found, now backtrack to find the path
-                match_state.reconstruct_path(parent, target)?;
-                return Ok(JsValue::from_str(&format!("Path found! --> {:?} ", delay_ms)));
-            }
-
-            let (cx, cy) = current;
-            for &(dx, dy) in moves.iter() {
-                let new_x = cx as i32 + dx;
-                let new_y = cy as i32 + dy;
-
-                if new_x >= 0 && new_x < cols as i32 &&
-                   new_y >= 0 && new_y < rows  as i32
-                   {
-                    let (nx_usize, ny_usize) = (new_x as usize, new_y as usize);
-
-                  if match_state.maze_grid[ny_usize][nx_usize]== CellType::Path  && !visited[ny_usize][nx_usize] {
-                        visited[ny_usize][nx_usize] = true;
-                        parent[ny_usize][nx_usize] = Some(current);
-                        queue.push((nx_usize, ny_usize));
-
-                        // color each cell as we visit it and add delay
-                        match_state.color_cell(nx_usize, ny_usize, "lightcoral".to_string());
-                        if delay_ms > 0 {
-                            crate::maze::sleep(delay_ms).await?;
-                        }
-                   }
-                }
-            }
+        // the frontier is a stack, the cell found last is expanded first
+        if match_state.draw_search(Frontier::Stack, delay_ms).await? {
+            return Ok(JsValue::from_str(&format!("Path found! --> {:?} ", delay_ms)));
         }
-        let col_row_value = format!("Target not reachable! --> {:?}  {:?} start: {:?}", rows, cols, start);
+        let grid = &match_state.maze_grid;
+        let col_row_value = format!("Target not reachable! --> {:?}  {:?} start: {:?}", grid.height(), grid.width(), match_state.computer_player);
         Ok(JsValue::from_str(&col_row_value))
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,284 @@
+use std::cmp::Reverse;
+use super::{Links, UNREACHABLE};
+use super::grid::{Grid, Scratch, NO_PARENT};
+
+// the distance between every two open cells, for level design on small and medium mazes:
+// how far each cell is from the one furthest from it (its eccentricity), the cells that are
+// least far from everything (the centre) and the two cells furthest apart (the diameter).
+// one search from every open cell: breadth first while every move costs 1, dijkstra when
+// portals cost more, which is cheaper than floyd-warshall on a grid where each cell has a few
+// neighbours at most
+
+// the most open cells a table is made for, it holds the square of this many distances
+pub const MAX_OPEN_CELLS: usize = 2048;
+
+// the slot of a wall
+const NO_SLOT: u32 = u32::MAX;
+
+// two cells and the distance between them
+pub type Pair = ((usize, usize), (usize, usize), u32);
+
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Distances {
+    width: usize,
+    height: usize,
+    revision: u64,       // of the grid the table is for
+    links_revision: u64, // and of the links
+    pub cells: Vec<(usize, usize)>, // the open cells, row by row, one row and column of the table each
+    slots: Vec<u32>, // the table row of every grid cell, NO_SLOT on walls
+    pub table: Vec<u32>, // cells x cells distances, UNREACHABLE between cells in different regions
+    pub eccentricity: Vec<u32>, // the furthest any cell reachable from each cell is, in the same order
+}
+
+impl Distances {
+    pub fn new(grid: &Grid, links: &Links) -> Result<Distances, String> {
+        let mut distances = Distances::default();
+        distances.sync(grid, links)?;
+        Ok(distances)
+    }
+
+    // works the table out again if the grid (by its revision) or the links changed since the
+    // last time. returns whether it did, an error when the maze has too many open cells for a table
+    pub fn sync(&mut self, grid: &Grid, links: &Links) -> Result<bool, String> {
+        if grid.revision() == self.revision && links.revision() == self.links_revision {
+            return Ok(false);
+        }
+        let open: Vec<usize> = (0..grid.cell_count()).filter(|&index| grid.is_open(index)).collect();
+        if open.len() > MAX_OPEN_CELLS {
+            return Err(format!("The maze has {} open cells, all pairs distances need {} or fewer!", open.len(), MAX_OPEN_CELLS));
+        }
+
+        let count = open.len();
+        self.slots = vec![NO_SLOT; grid.cell_count()];
+        for (slot, &index) in open.iter().enumerate() {
+            self.slots[index] = slot as u32;
+        }
+        self.cells = open.iter().map(|&index| grid.cell(index)).collect();
+        self.table = vec![UNREACHABLE; count * count];
+
+        let unit_cost = links.portals.pairs.is_empty() || links.portals.cost == 1;
+        let mut scratch = Scratch::default();
+        for (slot, &source) in open.iter().enumerate() {
+            scratch.begin(grid.cell_count());
+            if unit_cost {
+                breadth_first(grid, links, &mut scratch, source);
+            } else {
+                dijkstra(grid, links, &mut scratch, source);
+            }
+            let row = &mut self.table[slot * count..(slot + 1) * count];
+            for (to, &index) in open.iter().enumerate() {
+                if scratch.seen(index) {
+                    row[to] = scratch.cost[index];
+                }
+            }
+        }
+
+        self.eccentricity = self.table.chunks(count.max(1))
+            .take(count)
+            .map(|row| row.iter().copied().filter(|&cost| cost != UNREACHABLE).max().unwrap_or(0))
+            .collect();
+        (self.width, self.height, self.revision) = (grid.width(), grid.height(), grid.revision());
+        self.links_revision = links.revision();
+        Ok(true)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    // the table row and column of `cell`, None for walls and cells outside the maze
+    pub fn slot(&self, (x, y): (usize, usize)) -> Option<usize> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        self.slots.get(y * self.width + x).filter(|&&slot| slot != NO_SLOT).map(|&slot| slot as usize)
+    }
+
+    // the cost of the cheapest route from `from` to `to`, None if there is none
+    pub fn between(&self, from: (usize, usize), to: (usize, usize)) -> Option<u32> {
+        let (from, to) = (self.slot(from)?, self.slot(to)?);
+        Some(self.table[from * self.cells.len() + to]).filter(|&cost| cost != UNREACHABLE)
+    }
+
+    pub fn eccentricity_of(&self, cell: (usize, usize)) -> Option<u32> {
+        self.slot(cell).map(|slot| self.eccentricity[slot])
+    }
+
+    // whether each cell, in the order of `cells`, is in the largest region. a cell walled in on
+    // its own is no distance from everything it can reach, so measures like the centre only
+    // look at the largest region, the first one row by row when two are as large
+    pub fn in_largest_region(&self) -> Vec<bool> {
+        let count = self.cells.len();
+        let reach = |slot: usize| self.row(slot).iter().filter(|&&cost| cost != UNREACHABLE).count();
+        // a row is everything its cell can reach, which is its whole region
+        let Some(first) = (0..count).rev().max_by_key(|&slot| reach(slot)) else {
+            return Vec::new();
+        };
+        self.row(first).iter().map(|&cost| cost != UNREACHABLE).collect()
+    }
+
+    // the cells of least eccentricity in the largest region
+    pub fn centre(&self) -> Vec<(usize, usize)> {
+        let in_largest = self.in_largest_region();
+        let radius = (0..self.cells.len()).filter(|&slot| in_largest[slot]).map(|slot| self.eccentricity[slot]).min();
+        (0..self.cells.len())
+            .filter(|&slot| in_largest[slot] && Some(self.eccentricity[slot]) == radius)
+            .map(|slot| self.cells[slot])
+            .collect()
+    }
+
+    // the distances from the cell in table row `slot` to every cell
+    pub fn row(&self, slot: usize) -> &[u32] {
+        let count = self.cells.len();
+        &self.table[slot * count..(slot + 1) * count]
+    }
+
+    // the two cells furthest apart that can reach each other and their distance,
+    // the first such pair row by row
+    pub fn diameter(&self) -> Option<Pair> {
+        let count = self.cells.len();
+        let from = (0..count).rev().max_by_key(|&slot| self.eccentricity[slot])?;
+        let row = self.row(from);
+        let to = (0..count).rev().filter(|&slot| row[slot] != UNREACHABLE).max_by_key(|&slot| row[slot])?;
+        Some((self.cells[from], self.cells[to], row[to]))
+    }
+}
+
+
+fn breadth_first(grid: &Grid, links: &Links, scratch: &mut Scratch, source: usize) {
+    scratch.reach(source, NO_PARENT, 0);
+    scratch.queue.push_back(source);
+    while let Some(index) = scratch.queue.pop_front() {
+        let cost = scratch.cost[index] + 1;
+        for (next, _) in grid.neighbours(links, index) {
+            if !scratch.seen(next) {
+                scratch.reach(next, index as u32, cost);
+                scratch.queue.push_back(next);
+            }
+        }
+    }
+}
+
+fn dijkstra(grid: &Grid, links: &Links, scratch: &mut Scratch, source: usize) {
+    scratch.reach(source, NO_PARENT, 0);
+    scratch.heap.push(Reverse((0, grid.cell(source))));
+    while let Some(Reverse((cost, cell))) = scratch.heap.pop() {
+        let index = grid.index(cell);
+        if cost as u32 > scratch.cost[index] {
+            continue;
+        }
+        for (next, step_cost) in grid.neighbours(links, index) {
+            let next_cost = cost as u32 + step_cost as u32;
+            if !scratch.seen(next) || next_cost < scratch.cost[next] {
+                scratch.reach(next, index as u32, next_cost);
+                scratch.heap.push(Reverse((next_cost as i64, grid.cell(next))));
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maze::read_maze;
+    use crate::maze::pathfinding::bfs::distances_from;
+    use crate::maze::pathfinding::generate::generate;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    fn distances(text: &str) -> Distances {
+        let file = read_maze(text).expect("valid maze text");
+        Distances::new(&file.grid, &file.links).expect("a small maze")
+    }
+
+    #[test]
+    fn corridor_centre_and_diameter() {
+        let odd = distances("*******\n*     *\n*******\n");
+        assert_eq!(odd.centre(), vec![(3, 1)]);
+        assert_eq!(odd.eccentricity_of((3, 1)), Some(2));
+        assert_eq!(odd.diameter(), Some(((1, 1), (5, 1), 4)));
+
+        let even = distances("******\n*    *\n******\n");
+        assert_eq!(even.centre(), vec![(2, 1), (3, 1)]);
+        assert_eq!(even.diameter(), Some(((1, 1), (4, 1), 3)));
+    }
+
+    #[test]
+    fn centre_and_diameter_of_a_cross() {
+        let cross = distances("\
+*******
+*** ***
+*** ***
+*     *
+*** ***
+*** ***
+*******
+");
+        assert_eq!(cross.centre(), vec![(3, 3)]);
+        assert_eq!(cross.eccentricity_of((3, 3)), Some(2));
+        // the first pair of arm ends row by row
+        assert_eq!(cross.diameter(), Some(((3, 1), (1, 3), 4)));
+        assert_eq!(cross.between((3, 1), (3, 5)), Some(4));
+    }
+
+    #[test]
+    fn measures_only_look_at_the_largest_region() {
+        let rooms = distances("*********\n*     * *\n*********\n");
+        assert_eq!(rooms.in_largest_region(), vec![true, true, true, true, true, false]);
+        assert_eq!(rooms.centre(), vec![(3, 1)]);
+        assert_eq!(rooms.eccentricity_of((7, 1)), Some(0));
+        assert_eq!(rooms.between((1, 1), (7, 1)), None);
+        assert_eq!(rooms.diameter(), Some(((1, 1), (5, 1), 4)));
+    }
+
+    #[test]
+    fn two_regions_as_large_give_the_first_one() {
+        let rooms = distances("*********\n*   *   *\n*********\n");
+        assert_eq!(rooms.in_largest_region(), vec![true, true, true, false, false, false]);
+        assert_eq!(rooms.centre(), vec![(2, 1)]);
+        assert_eq!(rooms.diameter(), Some(((1, 1), (3, 1), 2)));
+    }
+
+    #[test]
+    fn no_open_cells_no_centre() {
+        let walls = distances("***\n***\n");
+        assert_eq!(walls.centre(), Vec::<(usize, usize)>::new());
+        assert_eq!(walls.diameter(), None);
+        assert_eq!(walls.slot((1, 1)), None);
+    }
+
+    #[test]
+    fn costly_portals_take_the_cheapest_route() {
+        let text = "*********\n*1     1*\n*********\n";
+        let file = read_maze(text).expect("valid maze text");
+        let mut links = file.links.clone();
+        assert_eq!(Distances::new(&file.grid, &links).expect("a small maze").between((1, 1), (7, 1)), Some(1));
+        links.portals.cost = 4;
+        assert_eq!(Distances::new(&file.grid, &links).expect("a small maze").between((1, 1), (7, 1)), Some(4));
+        links.portals.cost = 100;
+        assert_eq!(Distances::new(&file.grid, &links).expect("a small maze").between((1, 1), (7, 1)), Some(6));
+    }
+
+    #[test]
+    fn table_agrees_with_search() {
+        let grid = generate(15, 11, 20, &mut StdRng::seed_from_u64(10));
+        let links = Links::default();
+        let distances = Distances::new(&grid, &links).expect("a small maze");
+        for &from in distances.cells.iter() {
+            let expected = distances_from(&grid, &links, from);
+            for &to in distances.cells.iter() {
+                assert_eq!(distances.between(from, to), Some(expected[grid.index(to)] as u32));
+                assert_eq!(distances.between(from, to), distances.between(to, from));
+            }
+        }
+    }
+
+    #[test]
+    fn big_mazes_are_refused() {
+        let grid = generate(101, 101, 0, &mut StdRng::seed_from_u64(11));
+        assert!(grid.open_cells().count() > MAX_OPEN_CELLS);
+        assert!(Distances::new(&grid, &Links::default()).is_err());
+    }
+}
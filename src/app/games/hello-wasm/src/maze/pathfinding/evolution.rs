@@ -0,0 +1,237 @@
+use crate::maze::CellType;
+use rand::Rng;
+use std::collections::HashSet;
+use super::Links;
+use super::grid::{Grid, Scratch};
+use super::search::{self, Frontier};
+
+
+// a genetic algorithm that breeds harder mazes for one solver. every member of the population
+// is a wall layout of the same size as the maze it started from; children are made by taking
+// the top rows of one parent and the rest of another, then flipping a few cells. a child the
+// target cannot be reached in is thrown away, so every maze in the population stays solvable.
+// border cells and `fixed` cells (the players, portals, keys, doors, stairs) are never flipped
+
+// a layout and its fitness
+pub type Scored = (Grid, usize);
+
+const TOURNAMENT: usize = 3;
+const MAX_ATTEMPTS: usize = 20;
+
+
+// the solvers a maze can be made hard for, run headless the same way they run on the canvas
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Probe {
+    Bfs,
+    Dfs,
+    AStar,
+    BestFirst,
+}
+
+impl Probe {
+    pub fn from_name(name: &str) -> Option<Probe> {
+        match name {
+            "BFS" => Some(Probe::Bfs),
+            "DFS" => Some(Probe::Dfs),
+            "AStar" => Some(Probe::AStar),
+            "BestFirst" => Some(Probe::BestFirst),
+            _ => None,
+        }
+    }
+
+    // (cells the solver colours on its way, moves in the path it reports), None when it finds no path
+    pub fn run(self, grid: &Grid, links: &Links, scratch: &mut Scratch, start: (usize, usize), target: (usize, usize)) -> Option<(usize, usize)> {
+        self.search(grid, links, scratch, start, target).map(|(expanded, path)| (expanded, path.len() - 1))
+    }
+
+    // the frontier of the shared search the solver runs on the canvas
+    pub fn frontier(self) -> Frontier {
+        match self {
+            Probe::Bfs => Frontier::Queue,
+            Probe::Dfs => Frontier::Stack,
+            Probe::AStar => Frontier::Cost,
+            Probe::BestFirst => Frontier::Heuristic,
+        }
+    }
+
+    // the cells coloured and the path itself, from start to target. `scratch` is only
+    // working space, handed in so that scoring a whole population reuses one set of buffers
+    pub fn search(self, grid: &Grid, links: &Links, scratch: &mut Scratch, start: (usize, usize), target: (usize, usize)) -> Option<(usize, Vec<(usize, usize)>)> {
+        search::run(grid, links, scratch, self.frontier(), start, target)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fitness {
+    NodesExpanded, // the more cells the solver colours, the harder the maze
+    PathLength,    // the longer the path it reports, the harder the maze
+}
+
+impl Fitness {
+    pub fn from_name(name: &str) -> Option<Fitness> {
+        match name {
+            "NodesExpanded" => Some(Fitness::NodesExpanded),
+            "PathLength" => Some(Fitness::PathLength),
+            _ => None,
+        }
+    }
+}
+
+
+// what the mazes are bred for: making `probe` work hard between the two ends
+#[derive(Clone, Copy, Debug)]
+pub struct Challenge {
+    pub probe: Probe,
+    pub fitness: Fitness,
+    pub start: (usize, usize),
+    pub target: (usize, usize),
+}
+
+impl Challenge {
+    // None when the target cannot be reached at all
+    fn score(&self, grid: &Grid, links: &Links, scratch: &mut Scratch) -> Option<usize> {
+        let (expanded, moves) = self.probe.run(grid, links, scratch, self.start, self.target)?;
+        Some(match self.fitness {
+            Fitness::NodesExpanded => expanded,
+            Fitness::PathLength => moves,
+        })
+    }
+}
+
+
+pub struct Population {
+    pub members: Vec<Scored>,
+    links: Links,
+    fixed: HashSet<(usize, usize)>,
+    challenge: Challenge,
+    flips: usize, // cells flipped in every child
+    scratch: Scratch, // the probe's buffers, shared by every child it scores
+}
+
+impl Population {
+    // the maze itself and mutants of it, None if the maze is not solvable to begin with
+    pub fn seed(
+        grid: &Grid,
+        links: &Links,
+        fixed: HashSet<(usize, usize)>,
+        challenge: Challenge,
+        size: usize,
+        rng: &mut impl Rng,
+    ) -> Option<Population> {
+        let inner = grid.width().saturating_sub(2) * grid.height().saturating_sub(2);
+        let mut population = Population {
+            members: Vec::new(),
+            links: links.clone(),
+            fixed,
+            challenge,
+            flips: (inner / 100).max(1),
+            scratch: Scratch::default(),
+        };
+        let original = (grid.clone(), challenge.score(grid, links, &mut population.scratch)?);
+        population.members.push(original.clone());
+        while population.members.len() < size.max(2) {
+            let child = population.breed(&original, &original, rng);
+            population.members.push(child);
+        }
+        Some(population)
+    }
+
+    pub fn best(&self) -> &Scored {
+        self.members.iter().max_by_key(|(_, fitness)| *fitness).expect("a population is never empty")
+    }
+
+    // the best member carries over unchanged, the rest are children of tournament winners
+    pub fn step(&mut self, rng: &mut impl Rng) {
+        let mut next = vec![self.best().clone()];
+        while next.len() < self.members.len() {
+            let mother = self.select(rng).clone();
+            let father = self.select(rng).clone();
+            next.push(self.breed(&mother, &father, rng));
+        }
+        self.members = next;
+    }
+
+    // the fittest of a few members picked at random
+    fn select(&self, rng: &mut impl Rng) -> &Scored {
+        (0..TOURNAMENT).map(|_| &self.members[rng.gen_range(0..self.members.len())])
+            .max_by_key(|(_, fitness)| *fitness)
+            .expect("the tournament has entrants")
+    }
+
+    // a solvable child of the two, or a copy of the first parent when none turns up
+    fn breed(&mut self, mother: &Scored, father: &Scored, rng: &mut impl Rng) -> Scored {
+        for _ in 0..MAX_ATTEMPTS {
+            let (width, height) = (mother.0.width(), mother.0.height());
+            let cut = rng.gen_range(0..=height);
+            let mut child = mother.0.clone();
+            for y in cut..height {
+                for x in 0..width {
+                    child.set((x, y), father.0.get((x, y)).unwrap_or(CellType::Wall));
+                }
+            }
+            for _ in 0..self.flips {
+                let y = rng.gen_range(0..height);
+                if width < 3 || y == 0 || y + 1 == height {
+                    continue;
+                }
+                let x = rng.gen_range(1..width - 1);
+                if self.fixed.contains(&(x, y)) {
+                    continue;
+                }
+                child.set((x, y), if child.is_path((x, y)) { CellType::Wall } else { CellType::Path });
+            }
+            if let Some(fitness) = self.challenge.score(&child, &self.links, &mut self.scratch) {
+                return (child, fitness);
+            }
+        }
+        mother.clone()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maze::pathfinding::bfs;
+    use crate::maze::pathfinding::generate::generate;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    fn population(probe: Probe, fitness: Fitness, rng: &mut StdRng) -> Population {
+        let grid = generate(15, 11, 10, rng);
+        let challenge = Challenge { probe, fitness, start: (1, 1), target: (13, 9) };
+        let fixed = HashSet::from([(1, 1), (13, 9)]);
+        Population::seed(&grid, &Links::default(), fixed, challenge, 12, rng).expect("a generated maze is solvable")
+    }
+
+    #[test]
+    fn every_member_stays_solvable() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let mut population = population(Probe::Dfs, Fitness::NodesExpanded, &mut rng);
+        for _ in 0..15 {
+            population.step(&mut rng);
+            for (grid, fitness) in population.members.iter() {
+                assert!(grid.is_path((1, 1)) && grid.is_path((13, 9)), "fixed cells are never flipped");
+                let route = bfs::shortest_path(grid, &Links::default(), &mut Scratch::default(), (1, 1), (13, 9));
+                assert!(route.is_some());
+                // and the fitness is the one the probe gives it afresh
+                let fresh = Probe::Dfs.run(grid, &Links::default(), &mut Scratch::default(), (1, 1), (13, 9));
+                assert_eq!(fresh.map(|(expanded, _)| expanded), Some(*fitness));
+            }
+        }
+    }
+
+    #[test]
+    fn elitism_never_loses_the_best_fitness() {
+        for (probe, fitness) in [(Probe::Bfs, Fitness::PathLength), (Probe::AStar, Fitness::NodesExpanded)] {
+            let mut rng = StdRng::seed_from_u64(9);
+            let mut population = population(probe, fitness, &mut rng);
+            let mut best = population.best().1;
+            for _ in 0..20 {
+                population.step(&mut rng);
+                assert!(population.best().1 >= best, "{:?} {:?}", probe, fitness);
+                best = population.best().1;
+            }
+        }
+    }
+}
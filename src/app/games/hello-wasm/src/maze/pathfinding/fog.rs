@@ -0,0 +1,398 @@
+use wasm_bindgen::prelude::*;
+use std::collections::{HashSet, VecDeque};
+use crate::maze::MazeState;
+use super::{grid_step, trim_walk, Links};
+use super::topology::Topology;
+use super::grid::{Grid, Scratch, NO_PARENT};
+
+
+// what the agent believes about a cell, exported to js as one byte per cell
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Belief {
+    Unknown = 0,
+    Open = 1,
+    Wall = 2,
+}
+
+
+// exploration under fog of war: the agent only knows what its sensor has shown it.
+// each turn it plans on its belief map, either straight to the target if a known route exists,
+// or to the frontier cell (known open, next to unknown) that looks cheapest to reach the
+// target through, takes one step, senses, and replans whenever something new was revealed.
+// the sensor cannot see through a portal, so the agent treats portal cells as plain floor.
+// it only sees its own floor, and the cell a stair leads to while standing on the stair
+pub struct FogSolver {
+    pub sensor_radius: usize,
+    pub line_of_sight: bool,
+}
+
+// how an exploration went: every cell the agent stood on from the start, how often it
+// planned, and whether it got to the target
+#[derive(Clone, Debug, PartialEq)]
+pub struct Explored {
+    pub walk: Vec<(usize, usize)>,
+    pub replans: usize,
+    pub arrived: bool,
+}
+
+impl FogSolver {
+    pub async fn find_path(
+        &mut self,
+        match_state: &mut MazeState,
+        delay_ms: i32,
+    ) -> Result<JsValue, JsValue> {
+        let target = match_state.target;
+        let rows = match_state.maze_grid.height();
+        let cols = match_state.maze_grid.width();
+
+        let (grid, mut scratch) = match_state.take_search_buffers();
+        let links = match_state.links.clone();
+        let explored = self.explore(&grid, &links, &mut scratch, match_state.computer_player, target);
+        match_state.give_search_buffers(scratch);
+
+        match_state.belief_map = vec![vec![Belief::Unknown; cols]; rows];
+        let unknown = match_state.theme.unknown.clone();
+        for y in 0..rows {
+            for x in 0..cols {
+                match_state.fill_cell(x, y, &unknown);
+            }
+        }
+
+        // the exploration is worked out in one go, and played back step by step
+        let mut trail = HashSet::new();
+        let mut visible = HashSet::new();
+        for (step, &current) in explored.walk.iter().enumerate() {
+            let now_visible = self.visible(&grid, &links, current).into_iter().collect();
+            self.sense(match_state, now_visible, &mut visible, &trail);
+            match_state.fill_cell(current.0, current.1, &match_state.theme.computer.clone());
+            match_state.fill_cell(target.0, target.1, &match_state.theme.target.clone());
+            if step + 1 == explored.walk.len() {
+                break;
+            }
+
+            trail.insert(current);
+            match_state.fill_cell(current.0, current.1, &match_state.theme.frontier.clone());
+            if delay_ms > 0 {
+                crate::maze::sleep(delay_ms).await?;
+            }
+        }
+
+        let steps = explored.walk.len() - 1;
+        if !explored.arrived {
+            return Ok(JsValue::from_str(&format!("Target not reachable! --> explored for {} steps", steps)));
+        }
+        let path = trim_walk(&explored.walk);
+        match_state.draw_path(&path);
+        Ok(JsValue::from_str(&format!("Path found! --> {} steps walked, {} replans, path length {}", steps, explored.replans, path.len() - 1)))
+    }
+
+
+    // the exploration itself, without drawing
+    pub fn explore(
+        &self,
+        grid: &Grid,
+        links: &Links,
+        scratch: &mut Scratch,
+        start: (usize, usize),
+        target: (usize, usize),
+    ) -> Explored {
+        let mut belief = vec![Belief::Unknown; grid.cell_count()];
+        let mut current = start;
+        let mut walk = vec![start];
+        let mut plan: VecDeque<(usize, usize)> = VecDeque::new();
+        let mut replans = 0;
+
+        while current != target {
+            let mut revealed = false;
+            for cell in self.visible(grid, links, current) {
+                let index = grid.index(cell);
+                if belief[index] == Belief::Unknown {
+                    belief[index] = if grid.is_open(index) { Belief::Open } else { Belief::Wall };
+                    revealed = true;
+                }
+            }
+
+            // the old plan still stands unless something new came into view
+            if revealed || plan.is_empty() {
+                plan = plan_route(grid, links, &belief, scratch, current, target);
+                replans += 1;
+            }
+            let Some(next) = plan.pop_front() else {
+                return Explored { walk, replans, arrived: false };
+            };
+            current = next;
+            walk.push(current);
+        }
+        Explored { walk, replans, arrived: true }
+    }
+
+
+    // every cell the sensor picks up from `from`: those whose centre is within the radius on
+    // the same floor, less the ones behind walls with line of sight, and the other end of a stair.
+    // the radius counts steps between neighbouring centres, whatever the shape of the cells
+    // (with a little slack, the hex centres are worked out with square roots)
+    pub fn visible(&self, grid: &Grid, links: &Links, from: (usize, usize)) -> Vec<(usize, usize)> {
+        let topology = links.topology;
+        // hex and triangle rows and columns sit closer together than their centres,
+        // so the box to look in reaches up to twice the radius in cells
+        let reach = self.sensor_radius.saturating_mul(if topology == Topology::Square { 1 } else { 2 });
+        let (left, right) = (from.0.saturating_sub(reach), from.0.saturating_add(reach).min(grid.width() - 1));
+        let (top, bottom) = (from.1.saturating_sub(reach), from.1.saturating_add(reach).min(grid.height() - 1));
+        let floor = links.floors.locate(from).2;
+
+        let mut cells = Vec::new();
+        for y in top..=bottom {
+            for x in left..=right {
+                let cell = (x, y);
+                if topology.gap(from, cell) > self.sensor_radius as f64 + 1e-9 || links.floors.locate(cell).2 != floor {
+                    continue;
+                }
+                if !self.line_of_sight || in_line_of_sight(grid, topology, from, cell) {
+                    cells.push(cell);
+                }
+            }
+        }
+        cells.extend(links.floors.partner(from));
+        cells
+    }
+
+
+    // updates the belief map and the canvas with what the sensor sees now,
+    // cells that dropped out of view fade to remembered
+    fn sense(
+        &self,
+        match_state: &mut MazeState,
+        now_visible: HashSet<(usize, usize)>,
+        visible: &mut HashSet<(usize, usize)>,
+        trail: &HashSet<(usize, usize)>,
+    ) {
+        let trail_color = match_state.theme.frontier.clone();
+
+        for &(x, y) in visible.difference(&now_visible) {
+            let color = if trail.contains(&(x, y)) {
+                trail_color.clone()
+            } else if match_state.belief_map[y][x] == Belief::Wall {
+                match_state.theme.known_wall.clone()
+            } else {
+                match_state.theme.known_open.clone()
+            };
+            match_state.fill_cell(x, y, &color);
+        }
+
+        for &(x, y) in now_visible.iter() {
+            let truth = if match_state.maze_grid.is_path((x, y)) { Belief::Open } else { Belief::Wall };
+            match_state.belief_map[y][x] = truth;
+            let color = if trail.contains(&(x, y)) {
+                trail_color.clone()
+            } else if truth == Belief::Wall {
+                match_state.theme.seen_wall.clone()
+            } else {
+                match_state.theme.seen_open.clone()
+            };
+            match_state.fill_cell(x, y, &color);
+        }
+
+        *visible = now_visible;
+    }
+}
+
+
+// breadth first search over the cells believed open, `belief` holding one per grid cell;
+// heads for the target if it is already reachable that way, otherwise for the frontier cell
+// with the lowest distance-so-far + distance-to-target. the route leaves out `from`
+pub fn plan_route(
+    grid: &Grid,
+    links: &Links,
+    belief: &[Belief],
+    scratch: &mut Scratch,
+    from: (usize, usize),
+    target: (usize, usize),
+) -> VecDeque<(usize, usize)> {
+    scratch.begin(grid.cell_count());
+    scratch.reach(grid.index(from), NO_PARENT, 0);
+    scratch.queue.push_back(grid.index(from));
+
+    let mut best: Option<((usize, usize), f64)> = None;
+    while let Some(index) = scratch.queue.pop_front() {
+        let current = grid.cell(index);
+        if current == target {
+            best = Some((current, 0.0));
+            break;
+        }
+
+        // the cells around it, and up or down the stairs
+        let steps = links.topology.directions(current).iter().filter_map(|&dir| grid_step(grid, current, dir));
+        let mut frontier = false;
+        for next in steps.chain(links.floors.partner(current)).filter(|&next| grid.contains(next)) {
+            let next_index = grid.index(next);
+            if belief[next_index] == Belief::Unknown {
+                frontier = true;
+            } else if belief[next_index] == Belief::Open && !scratch.seen(next_index) {
+                scratch.reach(next_index, index as u32, scratch.cost[index] + 1);
+                scratch.queue.push_back(next_index);
+            }
+        }
+
+        if frontier {
+            let cost = scratch.cost[index] as f64 + links.distance(current, target);
+            if best.is_none_or(|(_, best_cost)| cost < best_cost) {
+                best = Some((current, cost));
+            }
+        }
+    }
+
+    match best {
+        Some((goal, _)) => scratch.path(grid, grid.index(goal)).into_iter().skip(1).collect(),
+        None => VecDeque::new(),
+    }
+}
+
+
+// the sensor can see a cell if the straight line to it passes through open cells only:
+// bresenham on square cells, otherwise the line between the centres in small steps
+fn in_line_of_sight(grid: &Grid, topology: Topology, from: (usize, usize), to: (usize, usize)) -> bool {
+    if topology != Topology::Square {
+        let ((x0, y0), (x1, y1)) = (topology.centre(from, 1.0), topology.centre(to, 1.0));
+        let steps = ((x1 - x0).hypot(y1 - y0) * 8.0).ceil() as usize;
+        return (1..steps).all(|step| {
+            let t = step as f64 / steps as f64;
+            match topology.cell_at((x0 + t * (x1 - x0), y0 + t * (y1 - y0)), 1.0) {
+                Some(cell) => cell == from || cell == to || (grid.contains(cell) && grid.is_path(cell)),
+                None => false,
+            }
+        });
+    }
+    let (mut x, mut y) = (from.0 as i32, from.1 as i32);
+    let (x1, y1) = (to.0 as i32, to.1 as i32);
+    let dx = (x1 - x).abs();
+    let dy = -(y1 - y).abs();
+    let sx = if x < x1 { 1 } else { -1 };
+    let sy = if y < y1 { 1 } else { -1 };
+    let mut err = dx + dy;
+
+    while (x, y) != (x1, y1) {
+        if (x, y) != (from.0 as i32, from.1 as i32) && !grid.is_path((x as usize, y as usize)) {
+            return false;
+        }
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y += sy;
+        }
+    }
+    true
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maze::read_maze;
+
+    fn explore(text: &str, sensor_radius: usize, line_of_sight: bool, start: (usize, usize), target: (usize, usize)) -> Explored {
+        let file = read_maze(text).expect("valid maze text");
+        FogSolver { sensor_radius, line_of_sight }.explore(&file.grid, &file.links, &mut Scratch::default(), start, target)
+    }
+
+    #[test]
+    fn huge_radius_sees_the_whole_grid() {
+        let file = read_maze("*****\n*   *\n*****\n").expect("valid maze text");
+        let sensor = FogSolver { sensor_radius: usize::MAX, line_of_sight: false };
+        assert_eq!(sensor.visible(&file.grid, &file.links, (1, 1)).len(), 15);
+        let sensor = FogSolver { sensor_radius: 1_000_000, line_of_sight: false };
+        assert_eq!(sensor.visible(&file.grid, &file.links, (3, 1)).len(), 15);
+    }
+
+    #[test]
+    fn walls_block_the_line_of_sight() {
+        let file = read_maze("*******\n*  *  *\n*******\n").expect("valid maze text");
+        let visible = FogSolver { sensor_radius: 4, line_of_sight: true }.visible(&file.grid, &file.links, (1, 1));
+        assert!(visible.contains(&(3, 1)), "the wall itself is seen");
+        assert!(!visible.contains(&(4, 1)) && !visible.contains(&(5, 1)));
+        let visible = FogSolver { sensor_radius: 4, line_of_sight: false }.visible(&file.grid, &file.links, (1, 1));
+        assert!(visible.contains(&(4, 1)) && visible.contains(&(5, 1)));
+    }
+
+    #[test]
+    fn radius_one_sees_the_neighbours_whatever_the_cell_shape() {
+        let mut file = read_maze("*******\n*     *\n*     *\n*     *\n*******\n").expect("valid maze text");
+        for topology in [Topology::Square, Topology::Hex, Topology::Triangle] {
+            file.links.topology = topology;
+            let mut visible = FogSolver { sensor_radius: 1, line_of_sight: true }.visible(&file.grid, &file.links, (3, 2));
+            let mut expected: Vec<(usize, usize)> = topology.directions((3, 2)).iter()
+                .map(|&(dx, dy)| ((3 + dx) as usize, (2 + dy) as usize))
+                .chain([(3, 2)])
+                .collect();
+            visible.sort();
+            expected.sort();
+            assert_eq!(visible, expected, "{:?}", topology);
+        }
+    }
+
+    #[test]
+    fn walls_block_the_line_of_sight_between_hexes() {
+        let mut file = read_maze("*******\n*  *  *\n*******\n").expect("valid maze text");
+        file.links.topology = Topology::Hex;
+        let visible = FogSolver { sensor_radius: 4, line_of_sight: true }.visible(&file.grid, &file.links, (1, 1));
+        assert!(visible.contains(&(2, 1)) && visible.contains(&(3, 1)));
+        assert!(!visible.contains(&(4, 1)) && !visible.contains(&(5, 1)));
+    }
+
+    #[test]
+    fn explores_hex_cells() {
+        let mut file = read_maze("\
+*******
+*     *
+***** *
+*     *
+*******
+").expect("valid maze text");
+        file.links.topology = Topology::Hex;
+        let explored = FogSolver { sensor_radius: 1, line_of_sight: true }
+            .explore(&file.grid, &file.links, &mut Scratch::default(), (1, 1), (1, 3));
+        assert!(explored.arrived);
+        for pair in explored.walk.windows(2) {
+            let step = (pair[1].0 as i32 - pair[0].0 as i32, pair[1].1 as i32 - pair[0].1 as i32);
+            assert!(Topology::Hex.directions(pair[0]).contains(&step));
+        }
+    }
+
+    #[test]
+    fn explores_round_a_bend_it_cannot_see() {
+        let explored = explore("\
+*******
+*     *
+***** *
+*     *
+*******
+", 1, true, (1, 1), (1, 3));
+        assert!(explored.arrived);
+        assert_eq!(trim_walk(&explored.walk).len(), 11);
+        assert!(explored.replans > 1);
+    }
+
+    #[test]
+    fn takes_the_stairs_to_another_floor() {
+        let text = "*****\n*  >*\n*****\n---\n*****\n*  <*\n*****\n";
+        let file = read_maze(text).expect("valid maze text");
+        // the floors sit side by side in the grid, the sensor stays on its own
+        let visible = FogSolver { sensor_radius: 10, line_of_sight: false }.visible(&file.grid, &file.links, (1, 1));
+        assert!(visible.iter().all(|&(x, _)| x < file.links.floors.stride));
+
+        let explored = explore(text, 10, false, (1, 1), (9, 1));
+        assert!(explored.arrived);
+        assert_eq!(explored.walk, vec![(1, 1), (2, 1), (3, 1), (11, 1), (10, 1), (9, 1)]);
+    }
+
+    #[test]
+    fn walled_off_target_is_not_reached() {
+        let explored = explore("*******\n*  *  *\n*******\n", 1, true, (1, 1), (5, 1));
+        assert!(!explored.arrived);
+        assert_eq!(explored.walk.last(), Some(&(2, 1)));
+    }
+}
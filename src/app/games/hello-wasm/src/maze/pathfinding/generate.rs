@@ -0,0 +1,142 @@
+use crate::maze::CellType;
+use super::grid::Grid;
+use rand::Rng;
+use rand::seq::SliceRandom;
+
+// new square mazes for the maze text format: a recursive backtracker carves a perfect maze
+// (exactly one route between any two cells) through the cells at odd coordinates, then
+// `loops` percent of the remaining inner walls between two corridors are knocked out so
+// there is more than one way round
+
+
+// `width` and `height` are rounded up to odd, at least 5, so the border is wall all round
+pub fn generate(width: usize, height: usize, loops: usize, rng: &mut impl Rng) -> Grid {
+    let (width, height) = (width.max(5) | 1, height.max(5) | 1);
+    let mut grid = Grid::new(width, height);
+
+    let mut stack = vec![(1, 1)];
+    grid.set((1, 1), CellType::Path);
+    while let Some(&(x, y)) = stack.last() {
+        let mut steps: Vec<(usize, usize)> = [(0i32, -2i32), (0, 2), (-2, 0), (2, 0)].iter()
+            .filter_map(|&(dx, dy)| {
+                let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+                (nx > 0 && ny > 0 && (nx as usize) < width - 1 && (ny as usize) < height - 1)
+                    .then_some((nx as usize, ny as usize))
+            })
+            .filter(|&cell| !grid.is_path(cell))
+            .collect();
+        steps.shuffle(rng);
+        match steps.first() {
+            Some(&(nx, ny)) => {
+                grid.set(((x + nx) / 2, (y + ny) / 2), CellType::Path);
+                grid.set((nx, ny), CellType::Path);
+                stack.push((nx, ny));
+            },
+            None => {
+                stack.pop();
+            },
+        }
+    }
+
+    // a wall with a corridor on both sides, across or along
+    let mut walls: Vec<(usize, usize)> = (1..height - 1)
+        .flat_map(|y| (1..width - 1).map(move |x| (x, y)))
+        .filter(|&(x, y)| {
+            !grid.is_path((x, y))
+                && ((grid.is_path((x - 1, y)) && grid.is_path((x + 1, y)))
+                    || (grid.is_path((x, y - 1)) && grid.is_path((x, y + 1))))
+        })
+        .collect();
+    walls.shuffle(rng);
+    for &(x, y) in walls.iter().take(walls.len() * loops.min(100) / 100) {
+        grid.set((x, y), CellType::Path);
+    }
+    grid
+}
+
+// the grid as maze text, walls as `*` and open floor as spaces
+pub fn to_text(grid: &Grid) -> String {
+    (0..grid.height())
+        .map(|y| (0..grid.width()).map(|x| if grid.is_path((x, y)) { ' ' } else { '*' }).collect::<String>())
+        .collect::<Vec<String>>()
+        .join("\n") + "\n"
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maze::read_maze;
+    use crate::maze::pathfinding::Links;
+    use crate::maze::pathfinding::bfs::distances_from;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    fn maze(width: usize, height: usize, loops: usize, seed: u64) -> Grid {
+        generate(width, height, loops, &mut StdRng::seed_from_u64(seed))
+    }
+
+    // pairs of open cells side by side
+    fn passages(grid: &Grid) -> usize {
+        grid.open_cells()
+            .map(|(x, y)| grid.is_path((x + 1, y)) as usize + grid.is_path((x, y + 1)) as usize)
+            .sum()
+    }
+
+    #[test]
+    fn sizes_are_rounded_up_to_odd() {
+        let grid = maze(20, 3, 0, 1);
+        assert_eq!((grid.width(), grid.height()), (21, 5));
+        let grid = maze(0, 0, 0, 1);
+        assert_eq!((grid.width(), grid.height()), (5, 5));
+    }
+
+    #[test]
+    fn border_is_wall_all_round() {
+        let grid = maze(31, 21, 50, 2);
+        let (width, height) = (grid.width(), grid.height());
+        for x in 0..width {
+            assert!(!grid.is_path((x, 0)) && !grid.is_path((x, height - 1)));
+        }
+        for y in 0..height {
+            assert!(!grid.is_path((0, y)) && !grid.is_path((width - 1, y)));
+        }
+    }
+
+    // connected, and with one passage fewer than cells there is no loop: one route between any two
+    #[test]
+    fn without_loops_the_maze_is_perfect() {
+        for seed in 0..10 {
+            let grid = maze(31, 21, 0, seed);
+            let open = grid.open_cells().count();
+            let distance = distances_from(&grid, &Links::default(), (1, 1));
+            assert!(grid.open_cells().all(|cell| distance[grid.index(cell)] != usize::MAX));
+            assert_eq!(passages(&grid), open - 1);
+            // every cell at odd coordinates is carved
+            assert!((1..grid.height()).step_by(2).all(|y| (1..grid.width()).step_by(2).all(|x| grid.is_path((x, y)))));
+        }
+    }
+
+    #[test]
+    fn loops_add_passages() {
+        let perfect = maze(31, 21, 0, 3);
+        let braided = maze(31, 21, 30, 3);
+        assert!(passages(&braided) > braided.open_cells().count() - 1);
+        assert!(braided.open_cells().count() > perfect.open_cells().count());
+    }
+
+    #[test]
+    fn the_same_seed_makes_the_same_maze() {
+        assert_eq!(maze(25, 25, 10, 4), maze(25, 25, 10, 4));
+        assert_ne!(maze(25, 25, 10, 4), maze(25, 25, 10, 5));
+    }
+
+    #[test]
+    fn text_reads_back_as_the_same_grid() {
+        let grid = maze(15, 9, 20, 6);
+        let text = to_text(&grid);
+        assert_eq!(text.lines().count(), 9);
+        assert!(text.lines().all(|line| line.len() == 15 && line.chars().all(|cell| cell == '*' || cell == ' ')));
+        assert_eq!(read_maze(&text).map(|file| file.grid), Ok(grid));
+    }
+}
@@ -0,0 +1,313 @@
+use crate::maze::CellType;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use super::Links;
+
+// the maze: one bit per cell, row after row, a cell identified by its index y * width + x
+// instead of a pair. rows shorter than the widest are padded with walls, which is the same as
+// the cells not being there. copies share the bits until one of them is written to, and every
+// write gives the grid a new revision, so what is worked out from a grid (its regions, its
+// distances, its clusters) only has to be worked out again when the revision changes. the
+// buffers a search needs (seen, parent, cost, its queue) live in a Scratch that is kept
+// between searches, so searching a big maze again allocates nothing and hashes nothing
+
+// the parent of a cell no search came to from another cell
+pub const NO_PARENT: u32 = u32::MAX;
+
+const BITS: usize = 64;
+
+// the last revision handed out, shared by every grid (and the links) so two never have the
+// same one unless one is a copy of the other
+static REVISION: AtomicU64 = AtomicU64::new(0);
+
+pub(super) fn next_revision() -> u64 {
+    REVISION.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+
+#[derive(Clone, Debug, Default)]
+pub struct Grid {
+    width: usize,
+    height: usize,
+    walls: Rc<Vec<u64>>,
+    revision: u64, // 0 only for the empty grid
+}
+
+// the same cells, whatever the revisions
+impl PartialEq for Grid {
+    fn eq(&self, other: &Grid) -> bool {
+        self.width == other.width && self.height == other.height && self.walls == other.walls
+    }
+}
+
+impl Grid {
+    // all walls
+    pub fn new(width: usize, height: usize) -> Grid {
+        Grid {
+            width,
+            height,
+            walls: Rc::new(vec![!0; (width * height).div_ceil(BITS)]),
+            revision: next_revision(),
+        }
+    }
+
+    pub fn from_rows(rows: &[Vec<CellType>]) -> Grid {
+        let mut grid = Grid::new(rows.iter().map(|row| row.len()).max().unwrap_or(0), rows.len());
+        for (y, row) in rows.iter().enumerate() {
+            for (x, &cell) in row.iter().enumerate() {
+                grid.set((x, y), cell);
+            }
+        }
+        grid
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // how many cells, walls included: the length of a buffer with one entry per cell
+    pub fn cell_count(&self) -> usize {
+        self.width * self.height
+    }
+
+    // a different number whenever the cells change
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn contains(&self, (x, y): (usize, usize)) -> bool {
+        x < self.width && y < self.height
+    }
+
+    pub fn index(&self, (x, y): (usize, usize)) -> usize {
+        y * self.width + x
+    }
+
+    pub fn cell(&self, index: usize) -> (usize, usize) {
+        (index % self.width, index / self.width)
+    }
+
+    pub fn is_open(&self, index: usize) -> bool {
+        index < self.cell_count() && self.walls[index / BITS] & (1 << (index % BITS)) == 0
+    }
+
+    // what is at `cell`, None outside the grid
+    pub fn get(&self, cell: (usize, usize)) -> Option<CellType> {
+        self.contains(cell).then(|| if self.is_open(self.index(cell)) { CellType::Path } else { CellType::Wall })
+    }
+
+    // whether `cell` is in the grid and open
+    pub fn is_path(&self, cell: (usize, usize)) -> bool {
+        self.contains(cell) && self.is_open(self.index(cell))
+    }
+
+    // panics outside the grid, like indexing a row past its end
+    pub fn set(&mut self, cell: (usize, usize), cell_type: CellType) {
+        assert!(self.contains(cell), "cell {:?} is outside the {} x {} grid", cell, self.width, self.height);
+        let index = self.index(cell);
+        let bit = 1 << (index % BITS);
+        let wall = self.walls[index / BITS] & bit != 0;
+        if wall == (cell_type == CellType::Wall) {
+            return;
+        }
+        Rc::make_mut(&mut self.walls)[index / BITS] ^= bit;
+        self.revision = next_revision();
+    }
+
+    // the cells a word at a time, a set bit for a wall; comparing two grids' words finds the
+    // cells that differ without looking at each
+    pub fn words(&self) -> &[u64] {
+        &self.walls
+    }
+
+    // the open cells, row by row
+    pub fn open_cells(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (0..self.cell_count()).filter(|&index| self.is_open(index)).map(|index| self.cell(index))
+    }
+
+    // the same moves as pathfinding::neighbours, in the same order, as (index, cost)
+    pub fn neighbours<'a>(&'a self, links: &'a Links, index: usize) -> impl Iterator<Item = (usize, usize)> + 'a {
+        let cell = self.cell(index);
+        // most mazes have no portals or stairs, and an empty map is not worth hashing into
+        let portal = if links.portals.pairs.is_empty() { None } else { links.portals.partner(cell) };
+        let stair = if links.floors.stairs.is_empty() { None } else { links.floors.partner(cell) };
+        let open_partner = |partner: Option<(usize, usize)>| partner
+            .filter(|&(x, y)| x < self.width && y < self.height)
+            .map(|partner| self.index(partner))
+            .filter(|&next| self.is_open(next));
+        let portal = open_partner(portal).map(|next| (next, links.portals.cost));
+        let stair = open_partner(stair).map(|next| (next, 1));
+
+        links.topology.directions(cell).iter()
+            .filter_map(move |&(dx, dy)| {
+                let (x, y) = (cell.0 as i64 + dx as i64, cell.1 as i64 + dy as i64);
+                (x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height)
+                    .then(|| self.index((x as usize, y as usize)))
+            })
+            .filter(move |&next| self.is_open(next))
+            .map(|next| (next, 1))
+            .chain(portal)
+            .chain(stair)
+    }
+}
+
+
+// a priority queue entry: the priority, then the cell, so ties go the way they always have
+pub type Entry = Reverse<(i64, (usize, usize))>;
+
+#[derive(Clone, Debug, Default)]
+pub struct Scratch {
+    stamp: Vec<u32>, // the search that last saw each cell
+    closed: Vec<u32>, // and the one that last closed it
+    search: u32,
+    pub parent: Vec<u32>, // valid where seen
+    pub cost: Vec<u32>,   // valid where seen
+    pub queue: VecDeque<usize>,
+    pub heap: BinaryHeap<Entry>,
+    pub next: Vec<(usize, usize)>, // the neighbours of the cell being expanded
+}
+
+impl Scratch {
+    // starts a search over `len` cells; nothing is cleared, the cells seen before just stop counting
+    pub fn begin(&mut self, len: usize) {
+        if self.stamp.len() != len {
+            self.stamp = vec![0; len];
+            self.closed = vec![0; len];
+            self.parent = vec![NO_PARENT; len];
+            self.cost = vec![u32::MAX; len];
+            self.search = 0;
+        }
+        self.search += 1;
+        if self.search == u32::MAX {
+            self.stamp.fill(0);
+            self.closed.fill(0);
+            self.search = 1;
+        }
+        self.queue.clear();
+        self.heap.clear();
+        self.next.clear();
+    }
+
+    pub fn seen(&self, index: usize) -> bool {
+        self.stamp[index] == self.search
+    }
+
+    // marks the cell seen, reached from `parent` for `cost`
+    pub fn reach(&mut self, index: usize, parent: u32, cost: u32) {
+        self.stamp[index] = self.search;
+        self.parent[index] = parent;
+        self.cost[index] = cost;
+    }
+
+    // marks the cell done with, for searches that keep a closed set
+    pub fn close(&mut self, index: usize) {
+        self.closed[index] = self.search;
+    }
+
+    pub fn is_closed(&self, index: usize) -> bool {
+        self.closed[index] == self.search
+    }
+
+    // the cells from the start of the search to `index`
+    pub fn path(&self, grid: &Grid, index: usize) -> Vec<(usize, usize)> {
+        let mut path = vec![grid.cell(index)];
+        let mut current = index;
+        while self.parent[current] != NO_PARENT {
+            current = self.parent[current] as usize;
+            path.push(grid.cell(current));
+        }
+        path.reverse();
+        path
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cells_either_side_of_a_word_boundary_are_set_apart() {
+        // 10 x 13 is 130 cells, three words with the last one two cells long
+        let mut grid = Grid::new(10, 13);
+        assert_eq!(grid.words().len(), 3);
+        for index in [62, 63, 64, 65, 127, 128, 129] {
+            let cell = grid.cell(index);
+            grid.set(cell, CellType::Path);
+            let open: Vec<usize> = grid.open_cells().map(|cell| grid.index(cell)).collect();
+            assert_eq!(open.last(), Some(&index));
+            grid.set(cell, CellType::Wall);
+            assert!(!grid.is_path(cell));
+        }
+        grid.set(grid.cell(63), CellType::Path);
+        grid.set(grid.cell(64), CellType::Path);
+        assert_eq!(grid.open_cells().map(|cell| grid.index(cell)).collect::<Vec<_>>(), vec![63, 64]);
+        assert_eq!(grid.words()[0], !0 ^ 1 << 63);
+        assert_eq!(grid.words()[1], !0 ^ 1);
+    }
+
+    #[test]
+    fn widths_off_a_word_still_map_cells_to_their_bits() {
+        for width in [1, 7, 63, 65, 100] {
+            let mut grid = Grid::new(width, 3);
+            let cells = [(0, 0), (width - 1, 0), (0, 1), (width - 1, 2)];
+            for &cell in cells.iter() {
+                grid.set(cell, CellType::Path);
+            }
+            for y in 0..3 {
+                for x in 0..width {
+                    assert_eq!(grid.is_path((x, y)), cells.contains(&(x, y)), "{} wide at ({}, {})", width, x, y);
+                }
+            }
+            // outside the grid is never open, even where the last word has spare bits
+            assert!(!grid.is_path((width, 0)) && !grid.is_path((0, 3)));
+            assert_eq!(grid.get((width, 2)), None);
+            assert!(!grid.is_open(grid.cell_count()));
+        }
+    }
+
+    #[test]
+    fn the_revision_changes_only_when_a_cell_does() {
+        let mut grid = Grid::new(5, 5);
+        let first = grid.revision();
+        grid.set((1, 1), CellType::Wall);
+        assert_eq!(grid.revision(), first);
+        grid.set((1, 1), CellType::Path);
+        let second = grid.revision();
+        assert_ne!(second, first);
+        grid.set((1, 1), CellType::Path);
+        assert_eq!(grid.revision(), second);
+        grid.set((1, 1), CellType::Wall);
+        assert_ne!(grid.revision(), second);
+        // a copy keeps the revision until one of them changes
+        let copy = grid.clone();
+        assert_eq!(copy.revision(), grid.revision());
+        assert_ne!(Grid::new(5, 5).revision(), grid.revision());
+    }
+
+    #[test]
+    fn stamps_from_before_the_search_count_wraps_are_forgotten() {
+        let mut scratch = Scratch::default();
+        scratch.begin(4);
+        scratch.search = u32::MAX - 1;
+        scratch.reach(2, NO_PARENT, 0);
+        scratch.close(2);
+        assert!(scratch.seen(2) && scratch.is_closed(2));
+
+        // the next search would be u32::MAX, it starts over at 1 with the stamps cleared
+        scratch.begin(4);
+        assert_eq!(scratch.search, 1);
+        assert!(!scratch.seen(2) && !scratch.is_closed(2));
+        scratch.reach(1, NO_PARENT, 0);
+        assert!(scratch.seen(1));
+        scratch.begin(4);
+        assert!(!scratch.seen(1) && !scratch.seen(2));
+        assert_eq!(scratch.search, 2);
+    }
+}
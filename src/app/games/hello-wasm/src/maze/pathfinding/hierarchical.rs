@@ -0,0 +1,411 @@
+use wasm_bindgen::prelude::*;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use crate::maze::MazeState;
+use super::{grid_step, neighbours, Links};
+use super::grid::{Grid, Scratch, NO_PARENT};
+
+
+// hierarchical a* (HPA*) for mazes too big to search cell by cell. the grid is cut into
+// square clusters of `size` x `size` cells; every open cell with a move into another cluster
+// is an entrance, and the abstract graph joins the entrances of a cluster by their shortest
+// distance inside it, and each entrance to the cells it steps to in the next cluster.
+// a query only searches that graph and then fills in the cells of each leg.
+// in a maze the openings between clusters are single cells, so every crossing is kept as its
+// own entrance instead of merging runs of them, which also keeps the path as short as a*'s.
+// clusters are numbered row by row and everything about them is kept in vectors by that number
+
+pub const DEFAULT_CLUSTER_SIZE: usize = 8;
+
+// which cluster a cell belongs to, as (column, row) of clusters
+pub type Cluster = (usize, usize);
+
+// the abstract edges leaving a node, each with its cost
+pub type Edges = Vec<((usize, usize), usize)>;
+
+// the path, if there is one, and the abstract nodes in the order they were expanded
+type Found = (Option<Vec<(usize, usize)>>, Vec<(usize, usize)>);
+
+
+#[derive(Clone, Debug)]
+pub struct Hierarchy {
+    pub size: usize,
+    columns: usize, // clusters across
+    grid: Grid,     // the grid the graph was built for, a copy sharing its cells
+    links: Links,
+    entrances: Vec<Vec<(usize, usize)>>, // of every cluster
+    edges: Vec<Vec<Edges>>, // leaving each of those entrances, in the same order
+}
+
+impl Default for Hierarchy {
+    fn default() -> Self {
+        Hierarchy::new(DEFAULT_CLUSTER_SIZE)
+    }
+}
+
+
+// what a search inside one cluster reached, by each cell's place in the cluster row by row
+struct Reached {
+    corner: (usize, usize), // the cluster's top left cell
+    size: usize,
+    cost: Vec<usize>, // usize::MAX where the search did not get to
+    parent: Vec<Option<(usize, usize)>>,
+}
+
+impl Reached {
+    // where `cell` is kept, None outside the cluster
+    fn slot(&self, (x, y): (usize, usize)) -> Option<usize> {
+        let (dx, dy) = (x.checked_sub(self.corner.0)?, y.checked_sub(self.corner.1)?);
+        (dx < self.size && dy < self.size).then_some(dy * self.size + dx)
+    }
+
+    fn cost(&self, cell: (usize, usize)) -> Option<usize> {
+        self.slot(cell).map(|slot| self.cost[slot]).filter(|&cost| cost != usize::MAX)
+    }
+
+    fn parent(&self, cell: (usize, usize)) -> Option<(usize, usize)> {
+        self.slot(cell).and_then(|slot| self.parent[slot])
+    }
+}
+
+// dijkstra that never leaves the cluster `from` is in: the cost and parent of every cell reached
+fn local_search(
+    grid: &Grid,
+    links: &Links,
+    size: usize,
+    from: (usize, usize),
+) -> Reached {
+    let mut reached = Reached {
+        corner: (from.0 / size * size, from.1 / size * size),
+        size,
+        cost: vec![usize::MAX; size * size],
+        parent: vec![None; size * size],
+    };
+    let mut pq = BinaryHeap::new();
+    if let Some(slot) = reached.slot(from) {
+        reached.cost[slot] = 0;
+    }
+    pq.push(Reverse((0, from)));
+
+    while let Some(Reverse((cost, cell))) = pq.pop() {
+        if Some(cost) > reached.cost(cell) {
+            continue;
+        }
+        for (next, step_cost) in neighbours(grid, links, cell) {
+            let Some(slot) = reached.slot(next) else {
+                continue; // in another cluster
+            };
+            let next_cost = cost + step_cost;
+            if next_cost < reached.cost[slot] {
+                reached.cost[slot] = next_cost;
+                reached.parent[slot] = Some(cell);
+                pq.push(Reverse((next_cost, next)));
+            }
+        }
+    }
+    reached
+}
+
+
+impl Hierarchy {
+    pub fn new(size: usize) -> Self {
+        Hierarchy {
+            size: size.max(2),
+            columns: 0,
+            grid: Grid::default(),
+            links: Links::default(),
+            entrances: Vec::new(),
+            edges: Vec::new(),
+        }
+    }
+
+    pub fn cluster_of(&self, (x, y): (usize, usize)) -> Cluster {
+        (x / self.size, y / self.size)
+    }
+
+    fn number_of(&self, (cx, cy): Cluster) -> usize {
+        cy * self.columns + cx
+    }
+
+    fn cells_of(&self, (cx, cy): Cluster) -> Vec<(usize, usize)> {
+        let rows = cy * self.size..((cy + 1) * self.size).min(self.grid.height());
+        let columns = cx * self.size..((cx + 1) * self.size).min(self.grid.width());
+        rows.flat_map(|y| columns.clone().map(move |x| (x, y)))
+            .collect()
+    }
+
+    // every entrance with the abstract edges leaving it
+    pub fn nodes(&self) -> impl Iterator<Item = ((usize, usize), &Edges)> + '_ {
+        self.entrances.iter().zip(self.edges.iter())
+            .flat_map(|(entrances, edges)| entrances.iter().copied().zip(edges.iter()))
+    }
+
+    // the edges leaving `node`, empty when it is not an entrance
+    fn edges_of(&self, node: (usize, usize)) -> &[((usize, usize), usize)] {
+        let number = self.number_of(self.cluster_of(node));
+        self.entrances.get(number)
+            .and_then(|entrances| entrances.iter().position(|&entrance| entrance == node))
+            .map_or(&[], |at| &self.edges[number][at])
+    }
+
+    // brings the graph up to date with `grid` and `links`, rebuilding only the clusters a
+    // changed cell is in or next to (their entrances may have come or gone with it).
+    // nothing is looked at while the grid's revision and the links are the ones the graph
+    // was built for, and the changed cells are found a word of cells at a time.
+    // a new tiling or portal cost, or a grid of another shape, rebuilds everything.
+    // returns how many clusters were rebuilt
+    pub fn sync(&mut self, grid: &Grid, links: &Links) -> usize {
+        let same_links = self.links == *links;
+        if grid.revision() == self.grid.revision() && same_links {
+            return 0;
+        }
+        let same_shape = self.grid.width() == grid.width() && self.grid.height() == grid.height();
+        let columns = grid.width().div_ceil(self.size);
+        let count = columns * grid.height().div_ceil(self.size);
+        let mut dirty = vec![false; count];
+
+        if !same_shape || !same_links {
+            self.columns = columns;
+            self.entrances = vec![Vec::new(); count];
+            self.edges = vec![Vec::new(); count];
+            dirty.fill(true);
+        } else {
+            for (word, (&old, &new)) in self.grid.words().iter().zip(grid.words()).enumerate() {
+                let mut changed = old ^ new;
+                while changed != 0 {
+                    let index = word * 64 + changed.trailing_zeros() as usize;
+                    changed &= changed - 1;
+                    let cell = grid.cell(index);
+                    dirty[self.number_of(self.cluster_of(cell))] = true;
+                    let around = links.topology.directions(cell).iter().filter_map(|&dir| grid_step(grid, cell, dir));
+                    let jumps = links.portals.partner(cell).into_iter().chain(links.floors.partner(cell));
+                    for next in around.chain(jumps).filter(|&next| grid.contains(next)) {
+                        dirty[self.number_of(self.cluster_of(next))] = true;
+                    }
+                }
+            }
+        }
+
+        self.grid = grid.clone();
+        if !same_links {
+            self.links = links.clone();
+        }
+        let mut rebuilt = 0;
+        for (number, _) in dirty.iter().enumerate().filter(|&(_, &dirty)| dirty) {
+            self.rebuild((number % columns, number / columns));
+            rebuilt += 1;
+        }
+        rebuilt
+    }
+
+    fn rebuild(&mut self, cluster: Cluster) {
+        let entrances: Vec<(usize, usize)> = self.cells_of(cluster).into_iter()
+            .filter(|&cell| self.grid.is_path(cell))
+            .filter(|&cell| neighbours(&self.grid, &self.links, cell).any(|(next, _)| self.cluster_of(next) != cluster))
+            .collect();
+
+        let edges = entrances.iter().map(|&entrance| {
+            let reached = local_search(&self.grid, &self.links, self.size, entrance);
+            let mut out: Edges = entrances.iter()
+                .filter(|&&other| other != entrance)
+                .filter_map(|&other| reached.cost(other).map(|cost| (other, cost)))
+                .collect();
+            out.extend(neighbours(&self.grid, &self.links, entrance).filter(|&(next, _)| self.cluster_of(next) != cluster));
+            out
+        }).collect();
+
+        let number = self.number_of(cluster);
+        self.entrances[number] = entrances;
+        self.edges[number] = edges;
+    }
+
+    // a* over the entrances, with `start` joined to the entrances of its cluster and those of the
+    // target's cluster joined to `target`, then every leg filled in cell by cell. the costs and
+    // parents of the abstract nodes are kept in `scratch` by grid index
+    pub fn find(&self, scratch: &mut Scratch, start: (usize, usize), target: (usize, usize)) -> Found {
+        if !self.grid.contains(start) || !self.grid.contains(target) {
+            return (None, Vec::new());
+        }
+        let from_start = local_search(&self.grid, &self.links, self.size, start);
+        let to_target = local_search(&self.grid, &self.links, self.size, target); // moves go both ways
+        let target_entrances = &self.entrances[self.number_of(self.cluster_of(target))];
+        let mut start_edges: Edges = self.entrances[self.number_of(self.cluster_of(start))].iter()
+            .filter_map(|&entrance| from_start.cost(entrance).map(|cost| (entrance, cost)))
+            .collect();
+        if let Some(cost) = from_start.cost(target) {
+            start_edges.push((target, cost));
+        }
+
+        // the priority is f in the high half and g in the low one, so ties on f still go to the
+        // cheaper node first
+        let priority = |f: usize, g: usize| ((f as i64) << 32) | g as i64;
+        let mut expanded = Vec::new();
+        scratch.begin(self.grid.cell_count());
+        scratch.reach(self.grid.index(start), NO_PARENT, 0);
+        scratch.heap.push(Reverse((priority(self.links.heuristic(&start, &target) as usize, 0), start)));
+
+        while let Some(Reverse((entry, node))) = scratch.heap.pop() {
+            let index = self.grid.index(node);
+            let cost = (entry & 0xffff_ffff) as usize;
+            if cost > scratch.cost[index] as usize {
+                continue;
+            }
+            if node == target {
+                let route = scratch.path(&self.grid, index);
+                return (Some(self.refine(&route)), expanded);
+            }
+            expanded.push(node);
+
+            let own = self.edges_of(node).iter().copied();
+            let from_here = if node == start { start_edges.as_slice() } else { &[] };
+            let finish = Some(node).filter(|node| target_entrances.contains(node))
+                .and_then(|node| to_target.cost(node)).map(|cost| (target, cost));
+            for (next, step_cost) in own.chain(from_here.iter().copied()).chain(finish) {
+                let next_index = self.grid.index(next);
+                let next_cost = cost + step_cost;
+                if !scratch.seen(next_index) || next_cost < scratch.cost[next_index] as usize {
+                    scratch.reach(next_index, index as u32, next_cost as u32);
+                    let f = next_cost + self.links.heuristic(&next, &target) as usize;
+                    scratch.heap.push(Reverse((priority(f, next_cost), next)));
+                }
+            }
+        }
+        (None, expanded)
+    }
+
+    // the cells between consecutive abstract nodes: a leg to another cluster is a single move,
+    // a leg inside one is searched again within the cluster
+    fn refine(&self, route: &[(usize, usize)]) -> Vec<(usize, usize)> {
+        let mut path = vec![route[0]];
+        for leg in route.windows(2) {
+            let (from, to) = (leg[0], leg[1]);
+            if self.cluster_of(from) != self.cluster_of(to) {
+                path.push(to);
+                continue;
+            }
+            let reached = local_search(&self.grid, &self.links, self.size, from);
+            let mut cells = Vec::new();
+            let mut current = to;
+            while let Some(prev) = reached.parent(current) {
+                cells.push(current);
+                current = prev;
+            }
+            cells.reverse();
+            path.extend(cells);
+        }
+        path
+    }
+}
+
+
+pub struct HpaSolver ;
+
+impl HpaSolver {
+    pub async fn find_path(
+        &mut self,
+        match_state: &mut MazeState,
+        delay_ms: i32,
+    ) -> Result<JsValue, JsValue> {
+        let start = match_state.computer_player;
+        let target = match_state.target;
+        let rebuilt = match_state.sync_hierarchy();
+
+        // the abstract search runs in one go, the entrances it expanded are played back afterwards
+        let (_, mut scratch) = match_state.take_search_buffers();
+        let (path, expanded) = match_state.hierarchy.find(&mut scratch, start, target);
+        match_state.give_search_buffers(scratch);
+        for &(x, y) in expanded.iter().skip(1) {
+            match_state.color_cell(x, y, match_state.theme.frontier.clone());
+            if delay_ms > 0 {
+                crate::maze::sleep(delay_ms).await?;
+            }
+        }
+
+        let Some(path) = path else {
+            return Ok(JsValue::from_str("Target not reachable!"));
+        };
+        match_state.draw_path(&path);
+        Ok(JsValue::from_str(&format!(
+            "Path found! --> {} steps, {} abstract nodes expanded, {} clusters rebuilt",
+            path.len() - 1, expanded.len(), rebuilt,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maze::CellType;
+    use crate::maze::pathfinding::generate::generate;
+    use crate::maze::pathfinding::search::{self, Frontier};
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+
+    // hpa* finds a route exactly when a* does, and one just as short
+    fn assert_as_short_as_astar(hierarchy: &Hierarchy, grid: &Grid, rng: &mut StdRng) {
+        let open: Vec<(usize, usize)> = grid.open_cells().collect();
+        let mut scratch = Scratch::default();
+        for _ in 0..40 {
+            let start = open[rng.gen_range(0..open.len())];
+            let target = open[rng.gen_range(0..open.len())];
+            let (path, _) = hierarchy.find(&mut scratch, start, target);
+            let astar = search::run(grid, &Links::default(), &mut scratch, Frontier::Cost, start, target);
+            assert_eq!(path.as_ref().map(|path| path.len()), astar.map(|(_, path)| path.len()), "{:?} to {:?}", start, target);
+            let Some(path) = path else { continue };
+            assert_eq!((path[0], path[path.len() - 1]), (start, target));
+            for pair in path.windows(2) {
+                assert!(neighbours(grid, &Links::default(), pair[0]).any(|(next, _)| next == pair[1]));
+            }
+        }
+    }
+
+    fn all_nodes(hierarchy: &Hierarchy) -> Vec<((usize, usize), Edges)> {
+        let mut nodes: Vec<((usize, usize), Edges)> = hierarchy.nodes().map(|(node, edges)| (node, edges.clone())).collect();
+        nodes.sort();
+        nodes
+    }
+
+    #[test]
+    fn path_cost_matches_astar_after_an_incremental_sync() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let mut grid = generate(41, 31, 15, &mut rng);
+        let mut hierarchy = Hierarchy::new(DEFAULT_CLUSTER_SIZE);
+        assert_eq!(hierarchy.sync(&grid, &Links::default()), 6 * 4);
+        assert_as_short_as_astar(&hierarchy, &grid, &mut rng);
+
+        // nothing changed, nothing to do
+        assert_eq!(hierarchy.sync(&grid, &Links::default()), 0);
+
+        for round in 0..5 {
+            // close a corridor and open a wall somewhere inside the border
+            let open: Vec<(usize, usize)> = grid.open_cells().collect();
+            grid.set(open[rng.gen_range(0..open.len())], CellType::Wall);
+            let (x, y) = (rng.gen_range(1..40), rng.gen_range(1..30));
+            grid.set((x, y), CellType::Path);
+
+            let rebuilt = hierarchy.sync(&grid, &Links::default());
+            assert!(rebuilt > 0 && rebuilt < 6 * 4, "round {} rebuilt {}", round, rebuilt);
+            assert_as_short_as_astar(&hierarchy, &grid, &mut rng);
+
+            // and the graph is the one a fresh build makes
+            let mut fresh = Hierarchy::new(DEFAULT_CLUSTER_SIZE);
+            fresh.sync(&grid, &Links::default());
+            assert_eq!(all_nodes(&hierarchy), all_nodes(&fresh));
+        }
+    }
+
+    #[test]
+    fn walled_off_target_has_no_route() {
+        let mut grid = generate(21, 21, 0, &mut StdRng::seed_from_u64(2));
+        let mut hierarchy = Hierarchy::new(4);
+        hierarchy.sync(&grid, &Links::default());
+        // wall in the target's corner
+        for cell in [(19, 18), (18, 19), (19, 19)] {
+            grid.set(cell, CellType::Wall);
+        }
+        grid.set((19, 19), CellType::Path);
+        hierarchy.sync(&grid, &Links::default());
+        let (path, _) = hierarchy.find(&mut Scratch::default(), (1, 1), (19, 19));
+        assert_eq!(path, None);
+    }
+}
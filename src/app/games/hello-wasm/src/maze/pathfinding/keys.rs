@@ -0,0 +1,197 @@
+use wasm_bindgen::prelude::*;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use crate::maze::MazeState;
+use super::{grid_step, neighbours, Links};
+use super::grid::Grid;
+
+
+// keys and the doors they open, written as letters in the maze text: `a` is the key to `A`.
+// a door is stored as a wall in the grid, so every other solver treats it as closed
+#[derive(Clone, Debug, Default)]
+pub struct Locks {
+    pub keys: HashMap<(usize, usize), u8>,  // key cell -> letter index (a = 0)
+    pub doors: HashMap<(usize, usize), u8>, // door cell -> letter index (A = 0)
+}
+
+// the set of keys carried, bit i for letter i
+pub type KeySet = u32;
+
+// a search state: where the agent stands and which keys it has picked up so far
+pub type KeyState = ((usize, usize), KeySet);
+
+impl Locks {
+    // the keys carried after stepping onto `cell`
+    pub fn pick_up(&self, cell: (usize, usize), carried: KeySet) -> KeySet {
+        match self.keys.get(&cell) {
+            Some(&key) => carried | (1 << key),
+            None => carried,
+        }
+    }
+
+    pub fn opens(&self, cell: (usize, usize), carried: KeySet) -> bool {
+        self.doors.get(&cell).is_some_and(|&door| carried & (1 << door) != 0)
+    }
+}
+
+
+// every state reachable in one move with its cost: the usual moves and portal jumps,
+// plus any door next to the agent that one of its keys opens
+fn successors(
+    grid: &Grid,
+    links: &Links,
+    locks: &Locks,
+    (cell, carried): KeyState,
+) -> Vec<(KeyState, usize)> {
+    let unlocked = links.topology.directions(cell).iter()
+        .filter_map(|&dir| grid_step(grid, cell, dir))
+        .filter(|&next| locks.opens(next, carried))
+        .map(|next| (next, 1));
+    neighbours(grid, links, cell)
+        .chain(unlocked)
+        .map(|(next, cost)| ((next, locks.pick_up(next, carried)), cost))
+        .collect()
+}
+
+
+// dijkstra over (cell, keys carried) instead of just the cell: the same cell is a different
+// place once the agent holds another key, because other doors are open to it from there.
+// `on_first_visit` is called the first time a cell is reached with any set of keys
+pub fn search(
+    grid: &Grid,
+    links: &Links,
+    locks: &Locks,
+    start: (usize, usize),
+    target: (usize, usize),
+    mut on_first_visit: impl FnMut((usize, usize)),
+) -> Option<Vec<KeyState>> {
+    let first = (start, locks.pick_up(start, 0));
+    let mut cost_so_far: HashMap<KeyState, usize> = HashMap::new();
+    let mut parent: HashMap<KeyState, KeyState> = HashMap::new();
+    let mut seen_cells = HashSet::new();
+    let mut pq = BinaryHeap::new();
+    cost_so_far.insert(first, 0);
+    seen_cells.insert(start);
+    pq.push(Reverse((0, first)));
+
+    while let Some(Reverse((cost, state))) = pq.pop() {
+        if cost > cost_so_far[&state] {
+            continue;
+        }
+        if state.0 == target {
+            let mut route = vec![state];
+            let mut current = state;
+            while let Some(&prev) = parent.get(&current) {
+                route.push(prev);
+                current = prev;
+            }
+            route.reverse();
+            return Some(route);
+        }
+        for (next, step_cost) in successors(grid, links, locks, state) {
+            let next_cost = cost + step_cost;
+            if next_cost < *cost_so_far.get(&next).unwrap_or(&usize::MAX) {
+                cost_so_far.insert(next, next_cost);
+                parent.insert(next, state);
+                pq.push(Reverse((next_cost, next)));
+                if seen_cells.insert(next.0) {
+                    on_first_visit(next.0);
+                }
+            }
+        }
+    }
+    None
+}
+
+
+pub struct KeySolver ;
+
+impl KeySolver {
+    pub async fn find_path(
+        &mut self,
+        match_state: &mut MazeState,
+        delay_ms: i32,
+    ) -> Result<JsValue, JsValue> {
+        let start = match_state.computer_player;
+        let target = match_state.target;
+
+        // the search itself runs in one go, the cells it reached are played back afterwards
+        let mut reached = Vec::new();
+        let route = search(&match_state.maze_grid, &match_state.links, &match_state.locks, start, target, |cell| reached.push(cell));
+        for (x, y) in reached {
+            match_state.color_cell(x, y, match_state.theme.frontier.clone());
+            if delay_ms > 0 {
+                crate::maze::sleep(delay_ms).await?;
+            }
+        }
+
+        let Some(route) = route else {
+            return Ok(JsValue::from_str("Target not reachable!"));
+        };
+        match_state.draw_key_route(&route);
+
+        // the keys in the order they were picked up
+        let mut order = Vec::new();
+        let mut carried = 0;
+        for &(_, keys) in route.iter() {
+            let gained = keys & !carried;
+            if gained != 0 {
+                order.push(((b'a' + gained.trailing_zeros() as u8) as char).to_string());
+            }
+            carried = keys;
+        }
+        let keys = if order.is_empty() { "none".to_string() } else { order.join(", ") };
+        Ok(JsValue::from_str(&format!("Path found! --> path length {}, keys picked up: {}", route.len() - 1, keys)))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maze::read_maze;
+
+    fn route(text: &str, start: (usize, usize), target: (usize, usize)) -> Option<Vec<KeyState>> {
+        let file = read_maze(text).expect("valid maze text");
+        search(&file.grid, &file.links, &file.locks, start, target, |_| ())
+    }
+
+    #[test]
+    fn key_a_opens_the_way_to_key_b() {
+        // b lies behind door A, and the target behind door B: the agent fetches a on the right,
+        // goes back left through A for b, then all the way right through B
+        let route = route("\
+**********
+*bA a B  *
+**********
+", (3, 1), (8, 1)).expect("both doors can be opened");
+        let cells: Vec<(usize, usize)> = route.iter().map(|&(cell, _)| cell).collect();
+        assert_eq!(cells, vec![
+            (3, 1), (4, 1), (3, 1), (2, 1), (1, 1), (2, 1), (3, 1), (4, 1), (5, 1), (6, 1), (7, 1), (8, 1),
+        ]);
+        let first_with = |key: u32| route.iter().position(|&(_, carried)| carried & key != 0);
+        assert_eq!(first_with(0b01), Some(1));
+        assert_eq!(first_with(0b10), Some(4));
+        assert_eq!(route.last(), Some(&((8, 1), 0b11)));
+    }
+
+    #[test]
+    fn key_locked_behind_its_own_door_is_out_of_reach() {
+        assert_eq!(route("*******\n*aA   *\n*******\n", (4, 1), (1, 1)), None);
+    }
+
+    #[test]
+    fn same_cell_with_other_keys_is_another_state() {
+        // the agent has to walk back over (3, 1) and (2, 1) once it holds a key
+        let file = read_maze("*******\n*A  a *\n*******\n").expect("valid maze text");
+        let mut visits = Vec::new();
+        let route = search(&file.grid, &file.links, &file.locks, (2, 1), (1, 1), |cell| visits.push(cell))
+            .expect("the key is on this side of the door");
+        assert_eq!(route.len() - 1, 3 + 2);
+        // each cell is reported once however many key sets it is reached with
+        let mut unique = visits.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), visits.len());
+    }
+}
@@ -0,0 +1,241 @@
+use wasm_bindgen::prelude::*;
+use rand::Rng;
+use std::collections::HashMap;
+use crate::maze::MazeState;
+use super::{neighbours, Links};
+use super::grid::Grid;
+
+
+// tabular reinforcement learning: the agent knows nothing about the maze, it only walks it
+// from the computer player over and over (an episode ends at the target or after a step limit)
+// and learns a value for every move out of every cell. a move costs its step cost as a
+// negative reward, discounted by gamma for every step further on, so over unit steps the best
+// value of a cell `d` steps from the target ends up as -(1 - gamma^d) / (1 - gamma): minus the
+// remaining distance only without a discount, but with one still the higher the closer it is
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Method {
+    QLearning, // learns from the best next move, whatever it then does
+    Sarsa,     // learns from the next move it actually makes, exploration included
+}
+
+impl Method {
+    pub fn from_name(name: &str) -> Option<Method> {
+        match name {
+            "QLearning" => Some(Method::QLearning),
+            "Sarsa" => Some(Method::Sarsa),
+            _ => None,
+        }
+    }
+}
+
+
+#[derive(Clone, Copy, Debug)]
+pub struct Learning {
+    pub method: Method,
+    pub alpha: f64,   // learning rate, how far a value moves towards each new estimate
+    pub gamma: f64,   // discount, how much a reward one step later is worth now
+    pub epsilon: f64, // chance of a random move instead of the best known one
+}
+
+impl Default for Learning {
+    fn default() -> Self {
+        Learning { method: Method::QLearning, alpha: 0.5, gamma: 0.95, epsilon: 0.1 }
+    }
+}
+
+
+// one value per move out of a cell, in the order neighbours() lists them
+pub type QTable = HashMap<(usize, usize), Vec<f64>>;
+
+
+// the best known move out of `cell`, the first one on a tie
+pub fn greedy(table: &QTable, cell: (usize, usize)) -> Option<usize> {
+    let values = table.get(&cell)?;
+    (0..values.len()).reduce(|best, index| if values[index] > values[best] { index } else { best })
+}
+
+// what the agent thinks standing on `cell` is worth, None where it has never been
+pub fn value(table: &QTable, cell: (usize, usize)) -> Option<f64> {
+    table.get(&cell).and_then(|values| values.iter().copied().reduce(f64::max))
+}
+
+// ε-greedy: a random move with probability epsilon, otherwise the best one, ties broken at random
+fn choose(values: &[f64], epsilon: f64, rng: &mut impl Rng) -> usize {
+    if rng.gen_bool(epsilon.clamp(0.0, 1.0)) {
+        return rng.gen_range(0..values.len());
+    }
+    let best = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let ties: Vec<usize> = (0..values.len()).filter(|&index| values[index] == best).collect();
+    ties[rng.gen_range(0..ties.len())]
+}
+
+
+// runs `episodes` more episodes from `start`, learning into `table`,
+// returns how many steps each of them took
+pub fn train(
+    grid: &Grid,
+    links: &Links,
+    learning: Learning,
+    (start, target): ((usize, usize), (usize, usize)),
+    episodes: usize,
+    table: &mut QTable,
+    rng: &mut impl Rng,
+) -> Vec<usize> {
+    let moves = |cell: (usize, usize)| -> Vec<((usize, usize), usize)> { neighbours(grid, links, cell).collect() };
+    let open_cells = grid.open_cells().count();
+    let max_steps = open_cells * 4;
+
+    // every value starts at 0, better than any real route, so untried moves look worth trying
+    let mut curve = Vec::with_capacity(episodes);
+    for _ in 0..episodes {
+        let mut cell = start;
+        let mut steps = 0;
+        let mut options = moves(cell);
+        if options.is_empty() || cell == target {
+            curve.push(0);
+            continue;
+        }
+        let mut action = choose(table.entry(cell).or_insert_with(|| vec![0.0; options.len()]), learning.epsilon, rng);
+
+        while cell != target && steps < max_steps {
+            let (next, cost) = options[action];
+            let next_options = moves(next);
+            let next_values = table.entry(next).or_insert_with(|| vec![0.0; next_options.len()]).clone();
+
+            let (next_action, estimate) = if next == target || next_values.is_empty() {
+                (0, 0.0)
+            } else {
+                let next_action = choose(&next_values, learning.epsilon, rng);
+                let estimate = match learning.method {
+                    Method::QLearning => next_values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+                    Method::Sarsa => next_values[next_action],
+                };
+                (next_action, estimate)
+            };
+
+            let current = &mut table.get_mut(&cell).expect("every visited cell has values")[action];
+            *current += learning.alpha * (-(cost as f64) + learning.gamma * estimate - *current);
+
+            steps += 1;
+            if next_values.is_empty() {
+                break;
+            }
+            (cell, action, options) = (next, next_action, next_options);
+        }
+        curve.push(steps);
+    }
+    curve
+}
+
+
+// the route the greedy policy takes from `start`, stopping where it reaches the target,
+// runs out of moves or starts going round in circles
+pub fn policy_route(grid: &Grid, links: &Links, table: &QTable, start: (usize, usize), target: (usize, usize)) -> Vec<(usize, usize)> {
+    let mut route = vec![start];
+    let mut cell = start;
+    while cell != target {
+        let Some(action) = greedy(table, cell) else { break };
+        let Some((next, _)) = neighbours(grid, links, cell).nth(action) else { break };
+        if route.contains(&next) {
+            break;
+        }
+        route.push(next);
+        cell = next;
+    }
+    route
+}
+
+
+pub struct LearningSolver {
+    pub learning: Learning,
+    pub episodes: usize,
+}
+
+impl LearningSolver {
+    pub async fn find_path(
+        &mut self,
+        match_state: &mut MazeState,
+        delay_ms: i32,
+    ) -> Result<JsValue, JsValue> {
+        let start = match_state.computer_player;
+        let target = match_state.target;
+
+        // training runs in batches, with the values redrawn after each one
+        let batches = if delay_ms > 0 { 10.min(self.episodes.max(1)) } else { 1 };
+        let mut rng = rand::thread_rng();
+        match_state.q_table.clear();
+        match_state.learning_curve.clear();
+        for batch in 0..batches {
+            let episodes = self.episodes * (batch + 1) / batches - self.episodes * batch / batches;
+            let mut table = std::mem::take(&mut match_state.q_table);
+            let curve = train(&match_state.maze_grid, &match_state.links, self.learning, (start, target), episodes, &mut table, &mut rng);
+            match_state.q_table = table;
+            match_state.learning_curve.extend(curve);
+            if delay_ms > 0 {
+                match_state.draw_learned_values()?;
+                crate::maze::sleep(delay_ms).await?;
+            }
+        }
+
+        match_state.draw_learned_values()?;
+        let route = policy_route(&match_state.maze_grid, &match_state.links, &match_state.q_table, start, target);
+        let last = match_state.learning_curve.last().copied().unwrap_or(0);
+        if route.last() != Some(&target) {
+            return Ok(JsValue::from_str(&format!("Target not reachable! --> the policy gets stuck after {} episodes", self.episodes)));
+        }
+        match_state.draw_path(&route);
+        Ok(JsValue::from_str(&format!("Path found! --> {} steps, the last episode took {}", route.len() - 1, last)))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maze::read_maze;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    // a corridor from (1, 1) to the target (7, 1), with a dead end going down at (3, 1)
+    const CORRIDOR: &str = "\
+*********
+*       *
+*** *****
+*** *****
+*********
+";
+
+    fn trained(method: Method, episodes: usize) -> (QTable, Vec<usize>) {
+        let file = read_maze(CORRIDOR).expect("valid maze text");
+        let learning = Learning { method, ..Learning::default() };
+        let mut table = QTable::new();
+        let curve = train(&file.grid, &file.links, learning, ((1, 1), (7, 1)), episodes, &mut table, &mut StdRng::seed_from_u64(3));
+        (table, curve)
+    }
+
+    #[test]
+    fn both_methods_learn_a_greedy_route_to_the_target() {
+        let file = read_maze(CORRIDOR).expect("valid maze text");
+        for method in [Method::QLearning, Method::Sarsa] {
+            let (table, curve) = trained(method, 300);
+            let route = policy_route(&file.grid, &file.links, &table, (1, 1), (7, 1));
+            assert_eq!(route, (1..=7).map(|x| (x, 1)).collect::<Vec<_>>(), "{:?}", method);
+            // the later episodes are the shortest walk, give or take the exploring
+            assert!(curve[curve.len() - 20..].iter().sum::<usize>() < curve[..20].iter().sum::<usize>(), "{:?}", method);
+        }
+    }
+
+    #[test]
+    fn q_learning_values_converge_to_the_discounted_distance() {
+        let (table, _) = trained(Method::QLearning, 2000);
+        let gamma = Learning::default().gamma;
+        for x in 1..7 {
+            let steps = (7 - x) as i32;
+            let expected = -(1.0 - gamma.powi(steps)) / (1.0 - gamma);
+            let learned = value(&table, (x, 1)).expect("on the route");
+            assert!((learned - expected).abs() < 1e-3, "({}, 1): {} not {}", x, learned, expected);
+        }
+    }
+}
@@ -1,18 +1,45 @@
-use crate::maze::CellType;
 use wasm_bindgen::prelude::JsValue;
+use std::collections::HashMap;
 
 pub mod bfs;
 pub mod dfs;
 pub mod best_first;
 pub mod astar;
+pub mod wall_follower;
+pub mod pledge;
+pub mod tremaux;
+pub mod dead_end_filling;
+pub mod fog;
+pub mod pursuit;
+pub mod cooperative;
+pub mod keys;
+pub mod timed;
+pub mod hierarchical;
+pub mod learning;
+pub mod evolution;
+pub mod trace;
+pub mod topology;
+pub mod generate;
+pub mod analysis;
+pub mod grid;
+pub mod components;
+pub mod distances;
+pub mod search;
 
+use topology::Topology;
+use grid::Grid;
+
+// the cost of a cell that cannot be reached, in distance fields and tables
+pub const UNREACHABLE: u32 = u32::MAX;
+
+#[allow(dead_code)]
 pub trait PathFindingAlgorithm {
     fn find_path(
         &mut self,
         maze_state: &mut crate::maze::MazeState,
         start: (usize, usize),
         end: (usize, usize),
-        grid: &Vec<Vec<CellType>>,
+        grid: &Grid,
         delay_ms: i32,
     ) -> Result<Option<Vec<(usize, usize)>>, JsValue>;
 }
@@ -23,4 +50,319 @@ pub fn heuristic( node: &(usize, usize), target: &(usize, usize)) -> f64 {
     let (x1, y1) = *node; // dereference the tuple
     let (x2, y2) = *target;  // dereference the tuple
     ((x2 as i32 - x1 as i32).abs() + (y2 as i32 - y1 as i32).abs()) as f64
-}
\ No newline at end of file
+}
+
+
+// the side of `cell` a walker stepping into it with `heading` came in by, as an index into
+// `topology.clockwise(cell)`. the walkers (wall follower, pledge) turn from there
+pub fn entered_by(topology: Topology, cell: (usize, usize), heading: i32) -> usize {
+    let back = (heading + 6) % 12;
+    topology.clockwise(cell).iter().position(|&(_, side)| side == back)
+        .expect("every move has one back the other way")
+}
+
+
+// helper function to take one step from a cell, None if it leaves the grid
+pub fn grid_step(grid: &Grid, cell: (usize, usize), (dx, dy): (i32, i32)) -> Option<(usize, usize)> {
+    let new_x = cell.0 as i32 + dx;
+    let new_y = cell.1 as i32 + dy;
+    if new_y < 0 || new_x < 0 || !grid.contains((new_x as usize, new_y as usize)) {
+        return None;
+    }
+    Some((new_x as usize, new_y as usize))
+}
+
+// helper function to take one step from a cell, None if it leaves the grid or hits a wall
+pub fn open_step(grid: &Grid, cell: (usize, usize), dir: (i32, i32)) -> Option<(usize, usize)> {
+    grid_step(grid, cell, dir).filter(|&next| grid.is_path(next))
+}
+
+
+// paired teleporter cells. from a portal cell the partner cell is one more neighbour,
+// reached for `cost` instead of the usual 1
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Portals {
+    pub pairs: HashMap<(usize, usize), (usize, usize)>,
+    pub cost: usize,
+}
+
+impl Portals {
+    pub fn partner(&self, cell: (usize, usize)) -> Option<(usize, usize)> {
+        self.pairs.get(&cell).copied()
+    }
+}
+
+
+// the floors of a multi-storey maze, laid out side by side in the one grid: cell (x, y)
+// of floor z is grid cell (x + z * stride, y). a stair up on one floor and the stair down
+// at the same spot on the floor above are partners, a move of 1 apart
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Floors {
+    pub count: usize,
+    pub stride: usize,
+    pub stairs: HashMap<(usize, usize), (usize, usize)>,
+}
+
+impl Floors {
+    // the grid cell as (x, y, floor)
+    pub fn locate(&self, (x, y): (usize, usize)) -> (usize, usize, usize) {
+        if self.count <= 1 {
+            return (x, y, 0);
+        }
+        (x % self.stride, y, x / self.stride)
+    }
+
+    pub fn partner(&self, cell: (usize, usize)) -> Option<(usize, usize)> {
+        self.stairs.get(&cell).copied()
+    }
+}
+
+
+// everything that decides which cells are next to which: the tiling, the portals and the stairs.
+// like a grid the links have a revision, so what is worked out from them can tell they changed
+// without comparing them; whoever changes a field calls changed() to get a new one
+#[derive(Clone, Debug, Default)]
+pub struct Links {
+    pub topology: Topology,
+    pub portals: Portals,
+    pub floors: Floors,
+    revision: u64, // 0 only for the default links
+}
+
+// the same links, whatever the revisions
+impl PartialEq for Links {
+    fn eq(&self, other: &Links) -> bool {
+        self.topology == other.topology && self.portals == other.portals && self.floors == other.floors
+    }
+}
+
+impl Links {
+    pub fn new(topology: Topology, portals: Portals, floors: Floors) -> Links {
+        Links { topology, portals, floors, revision: grid::next_revision() }
+    }
+
+    // a different number whenever changed() was called
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn changed(&mut self) {
+        self.revision = grid::next_revision();
+    }
+
+    // fewest moves between two cells on open floors: the distance within a floor,
+    // plus a stair for every floor in between
+    pub fn distance(&self, a: (usize, usize), b: (usize, usize)) -> f64 {
+        let (ax, ay, az) = self.floors.locate(a);
+        let (bx, by, bz) = self.floors.locate(b);
+        self.topology.distance((ax, ay), (bx, by)) + az.abs_diff(bz) as f64
+    }
+
+    // the grid distance stops being admissible once a portal can beat it, so this also
+    // considers walking to the nearest portal, jumping, and walking on from the portal
+    // closest to the target. taking the smaller of the two keeps it a lower bound
+    pub fn heuristic(&self, node: &(usize, usize), target: &(usize, usize)) -> f64 {
+        let distance = |a: &(usize, usize), b: &(usize, usize)| self.distance(*a, *b);
+        let direct = distance(node, target);
+        if self.portals.pairs.is_empty() {
+            return direct;
+        }
+        let to_portal = self.portals.pairs.keys().map(|portal| distance(node, portal)).fold(f64::INFINITY, f64::min);
+        let from_portal = self.portals.pairs.keys().map(|portal| distance(portal, target)).fold(f64::INFINITY, f64::min);
+        direct.min(to_portal + self.portals.cost as f64 + from_portal)
+    }
+}
+
+
+// every cell reachable in one move from `cell` with the cost of the move:
+// the open cells around it, plus the partner when it stands on a portal or a stair (and it is still open)
+pub fn neighbours<'a>(
+    grid: &'a Grid,
+    links: &'a Links,
+    cell: (usize, usize),
+) -> impl Iterator<Item = ((usize, usize), usize)> + 'a {
+    links.topology.directions(cell).iter()
+        .filter_map(move |&dir| open_step(grid, cell, dir))
+        .map(|next| (next, 1))
+        .chain(links.portals.partner(cell)
+            .filter(|&partner| grid.is_path(partner))
+            .map(|partner| (partner, links.portals.cost)))
+        .chain(links.floors.partner(cell)
+            .filter(|&partner| grid.is_path(partner))
+            .map(|partner| (partner, 1)))
+}
+
+
+// the walkers only know the route they actually walked, which doubles back on itself
+// at every dead end; cutting each loop at the cell it returns to leaves a simple path
+pub fn trim_walk(walk: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    let mut path: Vec<(usize, usize)> = Vec::new();
+    let mut index_of: HashMap<(usize, usize), usize> = HashMap::new();
+    for &cell in walk {
+        if let Some(&index) = index_of.get(&cell) {
+            for dropped in path.drain(index + 1..) {
+                index_of.remove(&dropped);
+            }
+        } else {
+            index_of.insert(cell, path.len());
+            path.push(cell);
+        }
+    }
+    path
+}
+
+
+// how a walking solver (wall follower, pledge, trémaux) got on, worked out without drawing:
+// every cell it stepped onto after the start, in order and with whether it was backing out
+// of a passage it had walked before, then its path or why it gave up
+#[derive(Clone, Debug, PartialEq)]
+pub struct Walked {
+    pub steps: Vec<((usize, usize), bool)>,
+    pub path: Result<Vec<(usize, usize)>, String>,
+}
+
+impl Walked {
+    // a walk that reached the target, its path the walk with the loops cut out
+    pub fn arrived(walk: &[(usize, usize)]) -> Walked {
+        Walked { steps: forward(walk), path: Ok(trim_walk(walk)) }
+    }
+
+    pub fn gave_up(walk: &[(usize, usize)], reason: String) -> Walked {
+        Walked { steps: forward(walk), path: Err(reason) }
+    }
+}
+
+fn forward(walk: &[(usize, usize)]) -> Vec<((usize, usize), bool)> {
+    walk.iter().skip(1).map(|&cell| (cell, false)).collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maze::fields::distance_field;
+    use crate::maze::read_maze;
+    use generate::generate;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    // h(a, b) never more than the cheapest route from a to b, for every two open cells
+    fn assert_admissible(grid: &Grid, links: &Links) {
+        let cells: Vec<(usize, usize)> = grid.open_cells().collect();
+        for &target in cells.iter() {
+            let cost = distance_field(grid, links, target);
+            for &cell in cells.iter() {
+                let cost = cost[grid.index(cell)];
+                if cost != UNREACHABLE {
+                    let h = links.heuristic(&cell, &target);
+                    assert!(h <= cost as f64, "h({:?}, {:?}) = {} over {}", cell, target, h, cost);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn portal_heuristic_is_admissible_at_any_cost() {
+        // the two ends are closer together than a jump costs from 3 on
+        let mut file = read_maze("\
+*********
+*1 *    *
+*1 * ** *
+*    *  *
+*********
+").expect("valid maze text");
+        for cost in [1, 2, 3, 5, 20] {
+            file.links.portals.cost = cost;
+            assert_admissible(&file.grid, &file.links);
+        }
+    }
+
+    #[test]
+    fn portal_heuristic_is_admissible_in_generated_mazes() {
+        for seed in 0..3 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let grid = generate(13, 9, 20, &mut rng);
+            let mut links = Links::default();
+            for (a, b) in [((1, 1), (11, 7)), ((1, 7), (3, 7))] {
+                links.portals.pairs.insert(a, b);
+                links.portals.pairs.insert(b, a);
+            }
+            for cost in [1, 4, 30] {
+                links.portals.cost = cost;
+                assert_admissible(&grid, &links);
+            }
+        }
+    }
+
+    #[test]
+    fn heuristic_is_admissible_on_hex_and_triangle_cells() {
+        for topology in [Topology::Hex, Topology::Triangle] {
+            for seed in 0..3 {
+                let grid = generate(13, 9, 20, &mut StdRng::seed_from_u64(seed));
+                let mut links = Links { topology, ..Links::default() };
+                assert_admissible(&grid, &links);
+                links.portals.pairs.insert((1, 1), (11, 7));
+                links.portals.pairs.insert((11, 7), (1, 1));
+                assert_admissible(&grid, &links);
+            }
+        }
+    }
+
+    #[test]
+    fn portal_heuristic_takes_the_jump_when_it_is_shorter() {
+        let mut links = Links::default();
+        links.portals.pairs.insert((1, 1), (20, 1));
+        links.portals.pairs.insert((20, 1), (1, 1));
+        links.portals.cost = 2;
+        assert_eq!(links.heuristic(&(2, 1), &(19, 1)), 1.0 + 2.0 + 1.0);
+        links.portals.cost = 40;
+        assert_eq!(links.heuristic(&(2, 1), &(19, 1)), 17.0);
+    }
+
+    // three floors, the stairs of one floor meeting those of the next at the same spot
+    const TOWER: &str = "\
+******
+*   >*
+******
+---
+******
+*>  <*
+******
+---
+******
+*<   *
+******
+";
+
+    #[test]
+    fn locate_and_the_stairs_round_trip() {
+        let file = read_maze(TOWER).expect("valid maze text");
+        let floors = &file.links.floors;
+        assert_eq!((floors.count, floors.stride), (3, 8));
+        for cell in file.grid.open_cells() {
+            let (x, y, z) = floors.locate(cell);
+            assert_eq!((x + z * floors.stride, y), cell);
+        }
+        for (&stair, &other_end) in floors.stairs.iter() {
+            assert_eq!(floors.partner(other_end), Some(stair));
+            let (x, y, z) = floors.locate(stair);
+            let (other_x, other_y, other_z) = floors.locate(other_end);
+            assert_eq!((x, y), (other_x, other_y));
+            assert_eq!(z.abs_diff(other_z), 1);
+        }
+        // one floor is floor 0 wherever the cell is
+        assert_eq!(Floors::default().locate((30, 2)), (30, 2, 0));
+    }
+
+    #[test]
+    fn routes_climb_the_stairs() {
+        let file = read_maze(TOWER).expect("valid maze text");
+        let (start, top) = ((1, 1), (17, 1));
+        let path = bfs::shortest_path(&file.grid, &file.links, &mut grid::Scratch::default(), start, top).expect("the stairs join every floor");
+        assert_eq!(path, vec![(1, 1), (2, 1), (3, 1), (4, 1), (12, 1), (11, 1), (10, 1), (9, 1), (17, 1)]);
+        // the distance counts the floors climbed and never overestimates
+        assert_eq!(file.links.distance(start, top), 2.0); // right above the start, two floors up
+        assert!(file.links.heuristic(&start, &top) <= (path.len() - 1) as f64);
+        assert_admissible(&file.grid, &file.links);
+    }
+}
@@ -0,0 +1,199 @@
+use wasm_bindgen::prelude::*;
+use crate::maze::MazeState;
+use super::{entered_by, open_step, Links, Walked};
+use super::grid::Grid;
+use super::topology::Topology;
+
+
+// the pledge algorithm: walk straight in a preferred direction, and when a wall is in the way
+// follow it (left hand on the wall) while counting the turns, leaving it again once the turns
+// add back up to zero. the turn count is what stops it circling an island forever.
+// like the wall follower it walks over portals without using them. on triangles no line is
+// straight, so walking it is a zigzag a twelfth of a turn either side, and that counts as straight
+pub struct PledgeSolver ;
+
+impl PledgeSolver {
+    pub async fn find_path(
+        &mut self,
+        match_state: &mut MazeState,
+        delay_ms: i32,
+    ) -> Result<JsValue, JsValue> {
+        let walked = pledge(&match_state.maze_grid, &match_state.links, match_state.computer_player, match_state.target);
+        match_state.draw_walk(walked, delay_ms).await
+    }
+}
+
+
+// the walk itself, without drawing. the start and target are open cells, the cells around
+// are those of `links.topology`
+pub fn pledge(grid: &Grid, links: &Links, start: (usize, usize), target: (usize, usize)) -> Walked {
+    let topology = links.topology;
+    let preferred = preferred_heading(topology, start, target);
+
+    // pledge is only guaranteed to escape a maze, not to reach a cell inside one,
+    // so give up once it has had plenty of chances to cover every cell
+    let max_steps = grid.open_cells().count() * 8;
+
+    let mut current = start;
+    let mut heading = preferred;
+    // how far the heading has turned from the preferred one, in twelfths of a turn,
+    // counting every turn: left (anticlockwise) adds, right takes away
+    let mut turn_sum: i32 = 0;
+    let mut walk = vec![start];
+
+    while current != target {
+        if walk.len() > max_steps {
+            let steps = walk.len() - 1;
+            return Walked::gave_up(&walk, format!("gave up after {} steps", steps));
+        }
+
+        let around = topology.clockwise(current);
+        let sides = around.len();
+        let step = |side: usize| open_step(grid, current, around[side].0).map(|cell| (around[side].1, cell));
+        let next = if turn_sum.abs() <= 1 {
+            // free walking, straight on in the preferred direction. at a wall turn right,
+            // round the cell clockwise, until the wall is on our left hand
+            let straight = around.iter().position(|&(_, side)| turn(preferred, side).abs() <= 1)
+                .expect("every cell has a move along every straight line");
+            let ahead = around[straight].1;
+            (0..sides).map(|offset| (straight + offset) % sides).find_map(step)
+                .map(|(side, cell)| (side, cell, turn(preferred, ahead) - (ahead - side).rem_euclid(12)))
+        } else {
+            // left hand on the wall: clockwise from the side we came in by, so left first,
+            // then on round to the right, and back as a last resort
+            let back = entered_by(topology, current, heading);
+            (1..=sides).map(|offset| (back + offset) % sides).find_map(step)
+                .map(|(side, cell)| (side, cell, turn_sum + turn(heading, side)))
+        };
+
+        let Some((side, cell, turned)) = next else {
+            return Walked::gave_up(&walk, "walled in".to_string());
+        };
+        heading = side;
+        turn_sum = turned;
+        current = cell;
+        walk.push(current);
+    }
+    Walked::arrived(&walk)
+}
+
+// the straight line through the start pointing closest to the target, as a heading in twelfths
+// of a turn; on a tie the line across the rows wins
+fn preferred_heading(topology: Topology, start: (usize, usize), target: (usize, usize)) -> i32 {
+    let ((x0, y0), (x1, y1)) = (topology.centre(start, 1.0), topology.centre(target, 1.0));
+    let towards = (y0 - y1).atan2(x1 - x0).to_degrees() / 30.0;
+    let off = |heading: i32| ((towards - heading as f64).rem_euclid(12.0) - 6.0).abs();
+    let mut lines: Vec<i32> = (0..12).step_by(topology.straight_heading() as usize).collect();
+    lines.sort_by_key(|&heading| heading % 6 != 0);
+    lines.into_iter().fold((0, f64::INFINITY), |best, heading| {
+        if 6.0 - off(heading) < best.1 - 1e-9 { (heading, 6.0 - off(heading)) } else { best }
+    }).0
+}
+
+// the turn from one heading to another, in twelfths of a turn from -6 up to 5: left is
+// positive, and turning right round is -6
+fn turn(from: i32, to: i32) -> i32 {
+    let turned = (to - from).rem_euclid(12);
+    if turned >= 6 { turned - 12 } else { turned }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maze::read_maze;
+
+    // the same island as the wall follower's tests: the start (3, 2) is on a ring around the
+    // pillar at (2, 2) that traps a left hand on the wall
+    const ISLAND: &str = "\
+*********
+*   *****
+* *    **
+*   ** **
+* **** **
+*********
+";
+
+    #[test]
+    fn leaves_the_island_the_wall_follower_circles() {
+        let file = read_maze(ISLAND).expect("valid maze text");
+        let walked = pledge(&file.grid, &file.links, (3, 2), (6, 4));
+        assert_eq!(walked.path, Ok(vec![(3, 2), (4, 2), (5, 2), (6, 2), (6, 3), (6, 4)]));
+    }
+
+    #[test]
+    fn leaves_the_wall_once_the_turns_add_up_to_zero() {
+        // heading west it runs into the wall at (4, 2), follows it up and round the corner,
+        // and walks on west once the right turn is undone by a left one
+        let file = read_maze("\
+*******
+*     *
+*   * *
+*******
+").expect("valid maze text");
+        let walked = pledge(&file.grid, &file.links, (5, 2), (1, 1));
+        assert_eq!(walked.path, Ok(vec![(5, 2), (5, 1), (4, 1), (3, 1), (2, 1), (1, 1)]));
+    }
+
+    // the room of the wall follower's tests, two walls reaching in from the edges
+    const COMB: &str = "\
+***********
+*    *    *
+***  *  * *
+****    * *
+***********
+";
+
+    fn assert_steps_along(topology: Topology, walked: &Walked) {
+        let mut cells = vec![(1, 1)];
+        cells.extend(walked.steps.iter().map(|&(cell, _)| cell));
+        for pair in cells.windows(2) {
+            let (x, y) = (pair[1].0 as i32 - pair[0].0 as i32, pair[1].1 as i32 - pair[0].1 as i32);
+            assert!(topology.directions(pair[0]).contains(&(x, y)), "{:?} to {:?}", pair[0], pair[1]);
+        }
+    }
+
+    #[test]
+    fn walks_hex_and_triangle_cells() {
+        for topology in [Topology::Hex, Topology::Triangle] {
+            let mut file = read_maze(COMB).expect("valid maze text");
+            file.links.topology = topology;
+            let walked = pledge(&file.grid, &file.links, (1, 1), (9, 1));
+            let path = walked.path.clone().unwrap_or_else(|reason| panic!("{:?}: {}", topology, reason));
+            assert_eq!((path[0], path[path.len() - 1]), ((1, 1), (9, 1)));
+            assert_steps_along(topology, &walked);
+        }
+    }
+
+    #[test]
+    fn triangles_zigzag_along_a_straight_line() {
+        // no wall in the way, so it never leaves the row, turning a sixth of a turn at every step
+        let mut file = read_maze("*********\n*       *\n*********\n").expect("valid maze text");
+        file.links.topology = Topology::Triangle;
+        let walked = pledge(&file.grid, &file.links, (1, 1), (7, 1));
+        assert_eq!(walked.path, Ok((1..=7).map(|x| (x, 1)).collect()));
+        assert_eq!(preferred_heading(Topology::Triangle, (1, 1), (7, 1)), 0);
+        assert_eq!(preferred_heading(Topology::Triangle, (7, 1), (1, 1)), 6);
+    }
+
+    #[test]
+    fn turns_are_signed_the_short_way_round() {
+        assert_eq!(turn(0, 3), 3);
+        assert_eq!(turn(3, 0), -3);
+        assert_eq!(turn(0, 6), -6);
+        assert_eq!(turn(11, 1), 2);
+        assert_eq!(turn(1, 11), -2);
+        // on a tie between two lines the one across the rows wins, as on square cells
+        assert_eq!(preferred_heading(Topology::Square, (1, 1), (3, 3)), 0);
+        assert_eq!(preferred_heading(Topology::Square, (3, 3), (1, 1)), 6);
+        assert_eq!(preferred_heading(Topology::Square, (1, 3), (2, 1)), 3);
+    }
+
+    #[test]
+    fn walled_off_target_is_given_up() {
+        let sealed = ISLAND.replace("*   ** **", "*   *****");
+        let file = read_maze(&sealed).expect("valid maze text");
+        let walked = pledge(&file.grid, &file.links, (3, 2), (6, 4));
+        assert!(walked.path.expect_err("no way in").starts_with("gave up after"));
+    }
+}
@@ -0,0 +1,292 @@
+use wasm_bindgen::prelude::*;
+use rand::seq::SliceRandom;
+use std::cmp::Reverse;
+use std::collections::VecDeque;
+use crate::maze::MazeState;
+use super::{open_step, Links};
+use super::bfs::distances_from;
+use super::grid::{Grid, Scratch, NO_PARENT};
+
+
+// how the target moves while it is being chased
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TargetBehaviour {
+    Static,
+    Wander, // a random open neighbour, or stay put
+    Flee,   // the neighbour furthest (by maze distance) from the computer
+}
+
+impl TargetBehaviour {
+    pub fn from_name(name: &str) -> Option<TargetBehaviour> {
+        match name {
+            "Static" => Some(TargetBehaviour::Static),
+            "Wander" => Some(TargetBehaviour::Wander),
+            "Flee" => Some(TargetBehaviour::Flee),
+            _ => None,
+        }
+    }
+}
+
+
+// moving target search with mt-adaptive a*: every a* search teaches the heuristic
+// (h(s) = g(goal) - g(s) for everything it expanded), and when the target moves the learned
+// values are corrected (h(s) = max(H(s, new goal), h(s) - h(new goal))) instead of thrown away,
+// so later searches expand far fewer cells than starting over would
+pub struct PursuitSolver {
+    pub behaviour: TargetBehaviour,
+    pub move_every: usize, // the target moves once per this many computer steps
+}
+
+impl PursuitSolver {
+    pub async fn find_path(
+        &mut self,
+        match_state: &mut MazeState,
+        delay_ms: i32,
+    ) -> Result<JsValue, JsValue> {
+        let grid = match_state.maze_grid.clone();
+        let links = match_state.links.clone();
+        let move_every = self.move_every.max(1);
+        let mut chase = Chase::new(&grid, &links, match_state.computer_player, match_state.target);
+        let max_steps = grid.open_cells().count() * 20;
+        let mut rng = rand::thread_rng();
+
+        while chase.agent != chase.target {
+            if chase.steps >= max_steps {
+                return Ok(JsValue::from_str(&format!("Target escaped! --> {} steps, {} searches, {} cells expanded", chase.steps, chase.searches, chase.expanded)));
+            }
+
+            // the computer takes one step along its plan
+            let from = chase.agent;
+            if !chase.step_agent() {
+                return Ok(JsValue::from_str("Target not reachable!"));
+            }
+            match_state.color_cell(from.0, from.1, match_state.theme.frontier.clone());
+            // the state follows the chase, so the canvas and the next solve start from here
+            match_state.computer_player = chase.agent;
+            match_state.fill_cell(chase.agent.0, chase.agent.1, &match_state.theme.computer.clone());
+            if chase.agent == chase.target {
+                break;
+            }
+
+            // then, every `move_every` steps, the target gets its turn
+            if self.behaviour != TargetBehaviour::Static && chase.steps.is_multiple_of(move_every) {
+                let moved_to = self.next_target_cell(&grid, &links, chase.agent, chase.target, &mut rng);
+                if moved_to != chase.target {
+                    self.repaint(match_state, chase.target);
+                    chase.move_target(moved_to);
+                    match_state.target = moved_to;
+                    match_state.fill_cell(moved_to.0, moved_to.1, &match_state.theme.target.clone());
+                }
+            }
+
+            if delay_ms > 0 {
+                crate::maze::sleep(delay_ms).await?;
+            }
+        }
+
+        let agent = chase.agent;
+        match_state.fill_cell(agent.0, agent.1, &match_state.theme.computer.clone());
+        Ok(JsValue::from_str(&format!("Target caught! --> {} steps, {} searches, {} cells expanded", chase.steps, chase.searches, chase.expanded)))
+    }
+
+
+    pub fn next_target_cell(
+        &self,
+        grid: &Grid,
+        links: &Links,
+        agent: (usize, usize),
+        target: (usize, usize),
+        rng: &mut impl rand::Rng,
+    ) -> (usize, usize) {
+        // the target only walks, it never takes a portal itself
+        let mut options: Vec<(usize, usize)> = links.topology.directions(target).iter()
+            .filter_map(|&dir| open_step(grid, target, dir))
+            .filter(|&cell| cell != agent)
+            .collect();
+        options.push(target); // staying put is always allowed
+
+        match self.behaviour {
+            TargetBehaviour::Static => target,
+            TargetBehaviour::Wander => *options.choose(rng).unwrap_or(&target),
+            TargetBehaviour::Flee => {
+                let distance = distances_from(grid, links, agent);
+                options.shuffle(rng); // break ties randomly so it does not always favour one way
+                options.into_iter()
+                    .max_by_key(|&cell| distance[grid.index(cell)])
+                    .unwrap_or(target)
+            },
+        }
+    }
+
+    // puts back what was under the target before it moved on
+    fn repaint(&self, match_state: &mut MazeState, cell: (usize, usize)) {
+        match_state.clear_cell(cell.0, cell.1);
+    }
+}
+
+
+// the chase without the drawing: where the computer and the target are, the computer's plan,
+// and the heuristic learned so far, one value per grid cell
+pub struct Chase<'a> {
+    grid: &'a Grid,
+    links: &'a Links,
+    pub h: Vec<f64>,
+    pub agent: (usize, usize),
+    pub target: (usize, usize),
+    path: VecDeque<(usize, usize)>,
+    scratch: Scratch,
+    pub steps: usize,
+    pub searches: usize,
+    pub expanded: usize,
+}
+
+impl<'a> Chase<'a> {
+    pub fn new(grid: &'a Grid, links: &'a Links, agent: (usize, usize), target: (usize, usize)) -> Chase<'a> {
+        let h = (0..grid.cell_count()).map(|index| links.heuristic(&grid.cell(index), &target)).collect();
+        Chase {
+            grid,
+            links,
+            h,
+            agent,
+            target,
+            path: VecDeque::new(),
+            scratch: Scratch::default(),
+            steps: 0,
+            searches: 0,
+            expanded: 0,
+        }
+    }
+
+    // the computer takes one step along its plan, searching for a new one first if it has
+    // none. false when the target cannot be reached
+    pub fn step_agent(&mut self) -> bool {
+        if self.path.is_empty() {
+            let Some((found, goal_cost, closed)) = search(self.grid, self.links, &mut self.scratch, &self.h, self.agent, self.target) else {
+                return false;
+            };
+            self.searches += 1;
+            self.expanded += closed.len();
+            // adaptive a* update, the cost of the path just found bounds the closed cells' h
+            for (index, g) in closed {
+                self.h[index] = goal_cost - g;
+            }
+            self.path = found.into_iter().skip(1).collect();
+        }
+        let Some(next) = self.path.pop_front() else {
+            return false;
+        };
+        self.agent = next;
+        self.steps += 1;
+        true
+    }
+
+    // the target moved to `cell`: mt-adaptive a* correction of the learned heuristic for the
+    // new goal, and the old plan is dropped since it led to where the target used to be
+    pub fn move_target(&mut self, cell: (usize, usize)) {
+        let h_new_goal = self.h[self.grid.index(cell)];
+        for (index, value) in self.h.iter_mut().enumerate() {
+            *value = self.links.heuristic(&self.grid.cell(index), &cell).max(*value - h_new_goal);
+        }
+        self.target = cell;
+        self.path.clear();
+    }
+}
+
+
+// the grid index of every cell an a* search expanded, with its g-value
+type Expanded = Vec<(usize, f64)>;
+
+// what a search found: the path, what it costs, and what was expanded on the way
+type Found = (Vec<(usize, usize)>, f64, Expanded);
+
+// a* with a learned heuristic table (by grid index), returns the path, its cost,
+// and the g-value of every expanded cell
+fn search(
+    grid: &Grid,
+    links: &Links,
+    scratch: &mut Scratch,
+    h: &[f64],
+    start: (usize, usize),
+    target: (usize, usize),
+) -> Option<Found> {
+    scratch.begin(grid.cell_count());
+    let mut expanded = Vec::new();
+    scratch.reach(grid.index(start), NO_PARENT, 0);
+    scratch.heap.push(Reverse((h[grid.index(start)] as i64, start)));
+
+    while let Some(Reverse((_, current))) = scratch.heap.pop() {
+        let index = grid.index(current);
+        if scratch.is_closed(index) {
+            continue;
+        }
+        let g = scratch.cost[index];
+        if current == target {
+            return Some((scratch.path(grid, index), g as f64, expanded));
+        }
+        scratch.close(index);
+        expanded.push((index, g as f64));
+
+        for (next, cost) in grid.neighbours(links, index) {
+            let next_g = g + cost as u32;
+            if !scratch.seen(next) || next_g < scratch.cost[next] {
+                scratch.reach(next, index as u32, next_g);
+                scratch.heap.push(Reverse(((next_g as f64 + h[next]) as i64, grid.cell(next))));
+            }
+        }
+    }
+    None
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maze::pathfinding::generate::generate;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    // plays a chase the way the canvas solver does, calling `check` after every turn.
+    // whether the target was caught within the solver's step limit
+    fn chase(grid: &Grid, behaviour: TargetBehaviour, move_every: usize, seed: u64, mut check: impl FnMut(&Chase)) -> bool {
+        let links = Links::default();
+        let solver = PursuitSolver { behaviour, move_every };
+        let mut rng = StdRng::seed_from_u64(seed);
+        let far_corner = (grid.width() - 2, grid.height() - 2);
+        let mut chase = Chase::new(grid, &links, (1, 1), far_corner);
+        let max_steps = grid.open_cells().count() * 20;
+        while chase.agent != chase.target && chase.steps < max_steps {
+            assert!(chase.step_agent(), "the whole maze is connected");
+            check(&chase);
+            if chase.agent != chase.target && chase.steps.is_multiple_of(move_every) {
+                let moved_to = solver.next_target_cell(grid, &links, chase.agent, chase.target, &mut rng);
+                if moved_to != chase.target {
+                    chase.move_target(moved_to);
+                    check(&chase);
+                }
+            }
+        }
+        chase.agent == chase.target
+    }
+
+    #[test]
+    fn learned_heuristic_stays_admissible() {
+        for seed in 0..4 {
+            let grid = generate(15, 11, 20, &mut StdRng::seed_from_u64(seed));
+            chase(&grid, TargetBehaviour::Flee, 2, seed, |chase| {
+                let distance = distances_from(&grid, &Links::default(), chase.target);
+                for index in grid.open_cells().map(|cell| grid.index(cell)) {
+                    assert!(chase.h[index] <= distance[index] as f64, "h overestimates at {:?}", grid.cell(index));
+                }
+            });
+        }
+    }
+
+    #[test]
+    fn fleeing_target_is_caught_in_a_perfect_maze() {
+        // without loops the target runs into a dead end sooner or later
+        for seed in 0..8 {
+            let grid = generate(21, 15, 0, &mut StdRng::seed_from_u64(seed));
+            assert!(chase(&grid, TargetBehaviour::Flee, 1, seed, |_| ()), "seed {} escaped", seed);
+        }
+    }
+}
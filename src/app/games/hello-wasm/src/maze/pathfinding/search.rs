@@ -0,0 +1,126 @@
+use std::cmp::Reverse;
+use super::Links;
+use super::grid::{Grid, Scratch, NO_PARENT};
+
+
+// the frontier searches (breadth first, depth first, best first and a*) as one loop that draws
+// nothing. it is stepped one cell at a time: the canvas solvers colour what each step found and
+// sleep in between, headless callers (the cli, the maze breeder) just run it to the end, so
+// both see the same cells in the same order
+
+// how the frontier is ordered, which is all that tells the four searches apart
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Frontier {
+    Queue,     // the cells in the order they were found
+    Stack,     // the last found first
+    Heuristic, // the one that looks closest to the target
+    Cost,      // g + heuristic, and a cell reached more cheaply is found again
+}
+
+// what a step did
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Step {
+    Expanded(usize), // took the cell at this index off the frontier, the cells it found are in scratch.next
+    Arrived(usize),  // took the target off the frontier, its path is in scratch
+    Exhausted,       // the frontier ran out
+}
+
+
+pub struct Search<'a> {
+    grid: &'a Grid,
+    links: &'a Links,
+    frontier: Frontier,
+    target: (usize, usize),
+}
+
+impl<'a> Search<'a> {
+    // starts a search in `scratch` with `start` on the frontier
+    pub fn new(
+        grid: &'a Grid,
+        links: &'a Links,
+        scratch: &mut Scratch,
+        frontier: Frontier,
+        start: (usize, usize),
+        target: (usize, usize),
+    ) -> Search<'a> {
+        let search = Search { grid, links, frontier, target };
+        scratch.begin(grid.cell_count());
+        scratch.reach(grid.index(start), NO_PARENT, 0);
+        search.push(scratch, grid.index(start));
+        search
+    }
+
+    fn push(&self, scratch: &mut Scratch, index: usize) {
+        let cell = self.grid.cell(index);
+        match self.frontier {
+            Frontier::Queue | Frontier::Stack => scratch.queue.push_back(index),
+            Frontier::Heuristic => scratch.heap.push(Reverse((self.links.heuristic(&cell, &self.target) as i64, cell))),
+            Frontier::Cost => {
+                let f_score = scratch.cost[index] as f64 + self.links.heuristic(&cell, &self.target);
+                scratch.heap.push(Reverse((f_score as i64, cell)));
+            }
+        }
+    }
+
+    fn pop(&self, scratch: &mut Scratch) -> Option<usize> {
+        match self.frontier {
+            Frontier::Queue => scratch.queue.pop_front(),
+            Frontier::Stack => scratch.queue.pop_back(),
+            Frontier::Heuristic | Frontier::Cost => scratch.heap.pop().map(|Reverse((_, cell))| self.grid.index(cell)),
+        }
+    }
+
+    // takes the next cell off the frontier and, unless it is the target, puts the cells it
+    // finds on it. those are left in scratch.next as (index, cost of the move), their g score
+    // in scratch.cost
+    pub fn step(&self, scratch: &mut Scratch) -> Step {
+        let Some(index) = self.pop(scratch) else {
+            return Step::Exhausted;
+        };
+        if index == self.grid.index(self.target) {
+            return Step::Arrived(index);
+        }
+
+        scratch.next.clear();
+        scratch.next.extend(self.grid.neighbours(self.links, index));
+        let mut kept = 0;
+        for at in 0..scratch.next.len() {
+            let (next, cost) = scratch.next[at];
+            let g = scratch.cost[index] + cost as u32;
+            let found = match self.frontier {
+                Frontier::Cost => !scratch.seen(next) || g < scratch.cost[next],
+                _ => !scratch.seen(next),
+            };
+            if found {
+                scratch.reach(next, index as u32, g);
+                self.push(scratch, next);
+                scratch.next[kept] = (next, cost);
+                kept += 1;
+            }
+        }
+        scratch.next.truncate(kept);
+        Step::Expanded(index)
+    }
+}
+
+
+// runs a search to the end: how many cells it found on the way (the cells a canvas solver
+// colours) and the path, None when the target cannot be reached
+pub fn run(
+    grid: &Grid,
+    links: &Links,
+    scratch: &mut Scratch,
+    frontier: Frontier,
+    start: (usize, usize),
+    target: (usize, usize),
+) -> Option<(usize, Vec<(usize, usize)>)> {
+    let search = Search::new(grid, links, scratch, frontier, start, target);
+    let mut found = 0;
+    loop {
+        match search.step(scratch) {
+            Step::Expanded(_) => found += scratch.next.len(),
+            Step::Arrived(index) => return Some((found, scratch.path(grid, index))),
+            Step::Exhausted => return None,
+        }
+    }
+}
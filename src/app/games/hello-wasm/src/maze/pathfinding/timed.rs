@@ -0,0 +1,296 @@
+use wasm_bindgen::prelude::*;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use crate::maze::MazeState;
+use super::{neighbours, Links};
+use super::grid::Grid;
+
+
+// obstacles that come and go, described by `@` lines in the maze text:
+//   @gate X Y PERIOD [OFFSET]      open for PERIOD ticks, then shut for PERIOD ticks, OFFSET ticks in
+//   @patrol X0 Y0 X1 Y1 [X2 Y2..]  walks the straight legs between the points and back, a cell per tick
+// only the space-time solver plans around them, the others see the maze as it is drawn at tick 0
+
+// the longest schedule cycle the planner keeps track of; with a longer one two ticks in the
+// same phase are too far apart to merge their states, and the planner refuses the schedule
+pub const MAX_CYCLE: usize = 5040;
+
+
+#[derive(Clone, Debug)]
+pub struct Gate {
+    pub cell: (usize, usize),
+    pub period: usize,
+    pub offset: usize,
+}
+
+impl Gate {
+    pub fn is_open(&self, tick: usize) -> bool {
+        ((tick + self.offset) / self.period).is_multiple_of(2)
+    }
+}
+
+
+#[derive(Clone, Debug)]
+pub struct Patrol {
+    pub route: Vec<(usize, usize)>, // every cell of one way along the legs
+}
+
+impl Patrol {
+    // there and back again, so the walk needs no closed loop
+    pub fn position(&self, tick: usize) -> (usize, usize) {
+        let last = self.route.len() - 1;
+        if last == 0 {
+            return self.route[0];
+        }
+        let phase = tick % (2 * last);
+        self.route[if phase <= last { phase } else { 2 * last - phase }]
+    }
+
+    fn cycle(&self) -> usize {
+        (2 * (self.route.len() - 1)).max(1)
+    }
+}
+
+
+#[derive(Clone, Debug, Default)]
+pub struct Schedule {
+    pub gates: Vec<Gate>,
+    pub patrols: Vec<Patrol>,
+}
+
+impl Schedule {
+    pub fn is_empty(&self) -> bool {
+        self.gates.is_empty() && self.patrols.is_empty()
+    }
+
+    pub fn is_blocked(&self, cell: (usize, usize), tick: usize) -> bool {
+        self.gates.iter().any(|gate| gate.cell == cell && !gate.is_open(tick)) ||
+        self.patrols.iter().any(|patrol| patrol.position(tick) == cell)
+    }
+
+    // a patrol walking from `to` into `from` while we go the other way would pass through us
+    fn is_swap(&self, from: (usize, usize), to: (usize, usize), tick: usize) -> bool {
+        self.patrols.iter().any(|patrol| patrol.position(tick) == to && patrol.position(tick + 1) == from)
+    }
+
+    // after this many ticks every gate and patrol is back where it started,
+    // None when that is more than MAX_CYCLE ticks
+    pub fn cycle(&self) -> Option<usize> {
+        let gcd = |mut a: usize, mut b: usize| { while b != 0 { (a, b) = (b, a % b); } a };
+        self.gates.iter().map(|gate| 2 * gate.period)
+            .chain(self.patrols.iter().map(Patrol::cycle))
+            .try_fold(1, |cycle: usize, length| (cycle / gcd(cycle, length)).checked_mul(length).filter(|&cycle| cycle <= MAX_CYCLE))
+    }
+}
+
+
+// a cell at a tick
+type Timed = ((usize, usize), usize);
+
+
+// a* in space-time: a state is a cell at a tick, waiting in place is a move like any other,
+// and a cell can only be entered at a tick when no gate or patrol is in it.
+// two states in the same cell whose ticks are a whole schedule cycle apart see the same future,
+// so only the earlier one is expanded. a portal jump takes `portals.cost` ticks, all but the
+// last spent in the cell it jumps from.
+// returns the cell the agent is in at every tick from 0 until it arrives, None if it never
+// can, and an error when the schedule cycle is too long to merge states by it.
+// `on_expand` is called with the cell of every state expanded
+pub fn plan(
+    grid: &Grid,
+    links: &Links,
+    schedule: &Schedule,
+    start: (usize, usize),
+    target: (usize, usize),
+    mut on_expand: impl FnMut((usize, usize)),
+) -> Result<Option<Vec<(usize, usize)>>, String> {
+    let cycle = schedule.cycle()
+        .ok_or_else(|| format!("The gates and patrols take more than {} ticks to repeat!", MAX_CYCLE))?;
+    let max_ticks = grid.open_cells().count() * cycle;
+
+    let mut closed: HashSet<Timed> = HashSet::new();
+    let mut parent: HashMap<Timed, Timed> = HashMap::new();
+    let mut pq = BinaryHeap::new();
+    pq.push(Reverse((links.heuristic(&start, &target) as usize, 0, start)));
+
+    while let Some(Reverse((_, tick, cell))) = pq.pop() {
+        if !closed.insert((cell, tick % cycle)) {
+            continue;
+        }
+        on_expand(cell);
+        if cell == target {
+            let mut route = vec![cell];
+            let mut current = (cell, tick);
+            while let Some(&prev) = parent.get(&current) {
+                // a portal jump spends its ticks in the cell it jumped from
+                for _ in prev.1..current.1 - 1 {
+                    route.push(prev.0);
+                }
+                route.push(prev.0);
+                current = prev;
+            }
+            route.reverse();
+            return Ok(Some(route));
+        }
+        if tick >= max_ticks {
+            continue;
+        }
+
+        let moves = neighbours(grid, links, cell).chain(std::iter::once((cell, 1)));
+        for (next, ticks) in moves {
+            let arrival = tick + ticks;
+            // a portal jump stays in `cell` until the tick before it lands, and must not be run into there
+            if schedule.is_blocked(next, arrival) ||
+                (tick + 1..arrival).any(|parked| schedule.is_blocked(cell, parked)) ||
+                schedule.is_swap(cell, next, arrival - 1) {
+                continue;
+            }
+            if closed.contains(&(next, arrival % cycle)) {
+                continue;
+            }
+            parent.entry((next, arrival)).or_insert((cell, tick));
+            pq.push(Reverse((arrival + links.heuristic(&next, &target) as usize, arrival, next)));
+        }
+    }
+    Ok(None)
+}
+
+
+pub struct SpaceTimeSolver ;
+
+impl SpaceTimeSolver {
+    pub async fn find_path(
+        &mut self,
+        match_state: &mut MazeState,
+        delay_ms: i32,
+    ) -> Result<JsValue, JsValue> {
+        let start = match_state.computer_player;
+        let target = match_state.target;
+        let planned = plan(&match_state.maze_grid, &match_state.links, &match_state.schedule, start, target, |_| ())
+            .map_err(|message| JsValue::from_str(&message))?;
+        let Some(route) = planned else {
+            return Ok(JsValue::from_str("Target not reachable!"));
+        };
+
+        // walk the plan one tick at a time, with the gates and patrols moving around it.
+        // each tick only adds the step just taken, and moves the schedule on
+        let waits = route.windows(2).filter(|pair| pair[0] == pair[1]).count();
+        match_state.tick = 0;
+        match_state.clear_visualization()?;
+        let mut walked = HashSet::new();
+        for tick in 0..route.len() {
+            if tick > 0 {
+                let (x, y) = route[tick - 1];
+                walked.insert((x, y));
+                match_state.tick = tick;
+                match_state.move_schedule(&walked);
+                if (x, y) != start {
+                    match_state.path_cell(x, y);
+                }
+                match_state.draw_jumps(&route[tick - 1..=tick]);
+            }
+            match_state.fill_cell(route[tick].0, route[tick].1, &match_state.theme.computer.clone());
+            if delay_ms > 0 {
+                crate::maze::sleep(delay_ms).await?;
+            }
+        }
+        match_state.tick = 0;
+        match_state.last_path = route.clone();
+
+        Ok(JsValue::from_str(&format!("Path found! --> {} ticks, {} of them spent waiting", route.len() - 1, waits)))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maze::CellType;
+
+    fn gate(x: usize, period: usize) -> Gate {
+        Gate { cell: (x, 1), period, offset: 0 }
+    }
+
+    fn patrol(length: usize) -> Patrol {
+        Patrol { route: (1..=length).map(|x| (x, 1)).collect() }
+    }
+
+    #[test]
+    fn cycle_is_the_least_common_multiple() {
+        assert_eq!(Schedule::default().cycle(), Some(1));
+        assert_eq!(Schedule { gates: vec![gate(1, 2)], patrols: vec![] }.cycle(), Some(4));
+        assert_eq!(Schedule { gates: vec![gate(1, 2), gate(2, 3)], patrols: vec![] }.cycle(), Some(12));
+        assert_eq!(Schedule { gates: vec![], patrols: vec![patrol(4)] }.cycle(), Some(6));
+        assert_eq!(Schedule { gates: vec![gate(1, 2)], patrols: vec![patrol(4)] }.cycle(), Some(12));
+        // a patrol that stands still repeats every tick
+        assert_eq!(Schedule { gates: vec![], patrols: vec![patrol(1)] }.cycle(), Some(1));
+    }
+
+    #[test]
+    fn cycle_longer_than_the_limit_is_refused() {
+        assert_eq!(Schedule { gates: vec![gate(1, MAX_CYCLE / 2)], patrols: vec![] }.cycle(), Some(MAX_CYCLE));
+        assert_eq!(Schedule { gates: vec![gate(1, MAX_CYCLE / 2 + 1)], patrols: vec![] }.cycle(), None);
+        // each fits, together they do not
+        let gates = vec![gate(1, 997), gate(2, 991)];
+        assert_eq!(Schedule { gates, patrols: vec![] }.cycle(), None);
+    }
+
+    #[test]
+    fn everything_is_back_where_it_started_after_a_cycle() {
+        let schedule = Schedule {
+            gates: vec![Gate { cell: (2, 1), period: 3, offset: 1 }, gate(4, 2)],
+            patrols: vec![patrol(5), Patrol { route: vec![(1, 2), (1, 3)] }],
+        };
+        let cycle = schedule.cycle().expect("a short cycle");
+        for tick in 0..cycle {
+            for x in 0..6 {
+                for y in 0..4 {
+                    assert_eq!(schedule.is_blocked((x, y), tick), schedule.is_blocked((x, y), tick + cycle));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn patrol_walks_there_and_back() {
+        let positions: Vec<usize> = (0..8).map(|tick| patrol(4).position(tick).0).collect();
+        assert_eq!(positions, vec![1, 2, 3, 4, 3, 2, 1, 2]);
+    }
+
+    #[test]
+    fn plan_waits_for_a_gate() {
+        let mut grid = Grid::new(5, 3);
+        for x in 1..4 {
+            grid.set((x, 1), CellType::Path);
+        }
+        // shut for ticks 0 and 1, so the agent has to wait a tick before stepping in
+        let schedule = Schedule { gates: vec![Gate { cell: (2, 1), period: 2, offset: 2 }], patrols: vec![] };
+        let route = plan(&grid, &Links::default(), &schedule, (1, 1), (3, 1), |_| ())
+            .expect("a short cycle")
+            .expect("the gate opens again");
+        assert_eq!(route, vec![(1, 1), (1, 1), (2, 1), (3, 1)]);
+    }
+
+    #[test]
+    fn plan_is_not_run_into_while_jumping() {
+        let mut grid = Grid::new(8, 5);
+        for cell in [(1, 1), (2, 1), (3, 1), (3, 2), (3, 3), (5, 1), (6, 1)] {
+            grid.set(cell, CellType::Path);
+        }
+        let mut links = Links::default();
+        links.portals.pairs.insert((3, 1), (5, 1));
+        links.portals.pairs.insert((5, 1), (3, 1));
+        links.portals.cost = 3;
+        // in the portal cell at ticks 0, 4, 8.., so stepping in at tick 2 and jumping straight
+        // away would be caught at tick 4 before landing at tick 5
+        let schedule = Schedule { gates: vec![], patrols: vec![Patrol { route: vec![(3, 1), (3, 2), (3, 3)] }] };
+        let route = plan(&grid, &links, &schedule, (1, 1), (6, 1), |_| ())
+            .expect("a short cycle")
+            .expect("the patrol leaves the portal for long enough");
+        for (tick, &cell) in route.iter().enumerate() {
+            assert!(!schedule.is_blocked(cell, tick), "run into at {:?} at tick {}", cell, tick);
+        }
+        // steps in at tick 5, the first of three free ticks in a row, and lands at tick 8
+        assert_eq!(route[5..], [(3, 1), (3, 1), (3, 1), (5, 1), (6, 1)]);
+    }
+}
@@ -0,0 +1,329 @@
+// how the cells of the grid tile the plane. the maze text is always a grid of rows, the
+// topology decides which cells of it are neighbours and what shape they are drawn in:
+//   Square    4 neighbours, the classic maze
+//   Hex       6 neighbours, pointy-top hexagons with every odd row shifted half a cell right
+//             (odd-r offset rows, converted to axial coordinates for distances)
+//   Triangle  3 neighbours, triangles pointing up where x + y is even and down where it is odd
+
+
+// the four moves in the order the searches have always tried them (up, down, left, right)
+const SQUARE: [(i32, i32); 4] = [(0, -1), (0, 1), (-1, 0), (1, 0)];
+
+// east, west, north-east, south-west, north-west, south-east, the same axial direction
+// at the same index in both tables, so stepping twice with one index walks in a straight line
+const HEX_EVEN_ROW: [(i32, i32); 6] = [(1, 0), (-1, 0), (0, -1), (-1, 1), (-1, -1), (0, 1)];
+const HEX_ODD_ROW: [(i32, i32); 6] = [(1, 0), (-1, 0), (1, -1), (0, 1), (0, -1), (1, 1)];
+
+// west, east, and the neighbour across the flat side
+const TRIANGLE_UP: [(i32, i32); 3] = [(-1, 0), (1, 0), (0, 1)];
+const TRIANGLE_DOWN: [(i32, i32); 3] = [(-1, 0), (1, 0), (0, -1)];
+
+// the same moves going clockwise round the cell, each with its heading on the canvas in
+// twelfths of a turn anticlockwise from east. turning is then index arithmetic within a cell,
+// and the heading carries over from one cell to the next, which for triangles turn over
+const SQUARE_CLOCKWISE: [((i32, i32), i32); 4] = [((0, -1), 3), ((1, 0), 0), ((0, 1), 9), ((-1, 0), 6)];
+const HEX_EVEN_CLOCKWISE: [((i32, i32), i32); 6] = [((0, -1), 2), ((1, 0), 0), ((0, 1), 10), ((-1, 1), 8), ((-1, 0), 6), ((-1, -1), 4)];
+const HEX_ODD_CLOCKWISE: [((i32, i32), i32); 6] = [((1, -1), 2), ((1, 0), 0), ((1, 1), 10), ((0, 1), 8), ((-1, 0), 6), ((0, -1), 4)];
+const TRIANGLE_UP_CLOCKWISE: [((i32, i32), i32); 3] = [((1, 0), 1), ((0, 1), 9), ((-1, 0), 5)];
+const TRIANGLE_DOWN_CLOCKWISE: [((i32, i32), i32); 3] = [((0, -1), 3), ((1, 0), 11), ((-1, 0), 7)];
+
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Topology {
+    #[default]
+    Square,
+    Hex,
+    Triangle,
+}
+
+impl Topology {
+    pub fn from_name(name: &str) -> Option<Topology> {
+        match name {
+            "Square" | "square" => Some(Topology::Square),
+            "Hex" | "hex" => Some(Topology::Hex),
+            "Triangle" | "triangle" => Some(Topology::Triangle),
+            _ => None,
+        }
+    }
+
+    // the offsets of every neighbour of `cell`, which for hex and triangle cells depends on the cell
+    pub fn directions(self, (x, y): (usize, usize)) -> &'static [(i32, i32)] {
+        match self {
+            Topology::Square => &SQUARE,
+            Topology::Hex if y.is_multiple_of(2) => &HEX_EVEN_ROW,
+            Topology::Hex => &HEX_ODD_ROW,
+            Topology::Triangle if (x + y).is_multiple_of(2) => &TRIANGLE_UP,
+            Topology::Triangle => &TRIANGLE_DOWN,
+        }
+    }
+
+    // the moves of `cell` in clockwise order, with their headings
+    pub fn clockwise(self, (x, y): (usize, usize)) -> &'static [((i32, i32), i32)] {
+        match self {
+            Topology::Square => &SQUARE_CLOCKWISE,
+            Topology::Hex if y.is_multiple_of(2) => &HEX_EVEN_CLOCKWISE,
+            Topology::Hex => &HEX_ODD_CLOCKWISE,
+            Topology::Triangle if (x + y).is_multiple_of(2) => &TRIANGLE_UP_CLOCKWISE,
+            Topology::Triangle => &TRIANGLE_DOWN_CLOCKWISE,
+        }
+    }
+
+    // the headings of the straight lines through the cells are the multiples of this.
+    // a triangle walks such a line in a zigzag, a twelfth either side of it
+    pub fn straight_heading(self) -> i32 {
+        match self {
+            Topology::Square => 3,
+            Topology::Hex | Topology::Triangle => 2,
+        }
+    }
+
+    // how far apart the centres of two cells are, counted in steps between neighbouring centres
+    pub fn gap(self, a: (usize, usize), b: (usize, usize)) -> f64 {
+        // triangle centres are a third of the way across, closer than the side length
+        let size = if self == Topology::Triangle { 3f64.sqrt() } else { 1.0 };
+        let ((x0, y0), (x1, y1)) = (self.centre(a, size), self.centre(b, size));
+        (x1 - x0).hypot(y1 - y0)
+    }
+
+    // pairs of direction indices pointing opposite ways, a wall between the two is a wall
+    // across a straight corridor
+    pub fn axes(self) -> &'static [(usize, usize)] {
+        match self {
+            Topology::Square => &[(0, 1), (2, 3)],
+            Topology::Hex => &[(0, 1), (2, 3), (4, 5)],
+            Topology::Triangle => &[(0, 1)],
+        }
+    }
+
+    // odd-r offset position to axial (q, r)
+    pub fn to_axial((x, y): (usize, usize)) -> (i32, i32) {
+        let (x, y) = (x as i32, y as i32);
+        (x - (y - (y & 1)) / 2, y)
+    }
+
+    // fewest moves from `a` to `b` on an open floor, so never more than the real distance
+    pub fn distance(self, a: (usize, usize), b: (usize, usize)) -> f64 {
+        let dx = a.0.abs_diff(b.0);
+        let dy = a.1.abs_diff(b.1);
+        match self {
+            Topology::Square => (dx + dy) as f64,
+            Topology::Hex => {
+                let (q1, r1) = Topology::to_axial(a);
+                let (q2, r2) = Topology::to_axial(b);
+                let (dq, dr) = (q1 - q2, r1 - r2);
+                ((dq.abs() + dr.abs() + (dq + dr).abs()) / 2) as f64
+            },
+            // every row change goes across a flat side, and two of those the same way
+            // need a sideways move in between since the triangle has turned over
+            Topology::Triangle => (dy + dx.max(dy.saturating_sub(1))) as f64,
+        }
+    }
+
+
+    // the outline of a cell on the canvas, `size` being the block size
+    pub fn corners(self, (x, y): (usize, usize), size: f64) -> Vec<(f64, f64)> {
+        let pointing_up = (x + y).is_multiple_of(2);
+        let (cx, cy) = self.centre((x, y), size);
+        let (x, y) = (x as f64, y as f64);
+        match self {
+            Topology::Square => vec![
+                (x * size, y * size), ((x + 1.0) * size, y * size),
+                ((x + 1.0) * size, (y + 1.0) * size), (x * size, (y + 1.0) * size),
+            ],
+            Topology::Hex => {
+                let radius = size / 3f64.sqrt();
+                (0..6).map(|corner| {
+                    let angle = (60.0 * corner as f64 - 30.0).to_radians();
+                    (cx + radius * angle.cos(), cy + radius * angle.sin())
+                }).collect()
+            },
+            Topology::Triangle => {
+                let height = size * 3f64.sqrt() / 2.0;
+                let left = x * size / 2.0;
+                let (top, bottom) = (y * height, (y + 1.0) * height);
+                if pointing_up {
+                    vec![(left + size / 2.0, top), (left + size, bottom), (left, bottom)]
+                } else {
+                    vec![(left, top), (left + size, top), (left + size / 2.0, bottom)]
+                }
+            },
+        }
+    }
+
+    // the middle of a cell on the canvas, where labels and arcs are anchored
+    pub fn centre(self, (x, y): (usize, usize), size: f64) -> (f64, f64) {
+        match self {
+            Topology::Square => ((x as f64 + 0.5) * size, (y as f64 + 0.5) * size),
+            Topology::Hex => {
+                let row_height = size * 3f64.sqrt() / 2.0;
+                let shift = if y.is_multiple_of(2) { 0.0 } else { 0.5 };
+                ((x as f64 + 0.5 + shift) * size, y as f64 * row_height + size / 3f64.sqrt())
+            },
+            Topology::Triangle => {
+                let height = size * 3f64.sqrt() / 2.0;
+                let third = if (x + y).is_multiple_of(2) { 2.0 / 3.0 } else { 1.0 / 3.0 };
+                ((x as f64 + 1.0) * size / 2.0, (y as f64 + third) * height)
+            },
+        }
+    }
+
+    // the cell whose outline contains the canvas point, which may lie outside the grid.
+    // None only left of or above the first cells
+    pub fn cell_at(self, (px, py): (f64, f64), size: f64) -> Option<(usize, usize)> {
+        let (col, row) = match self {
+            Topology::Square => (px / size, py / size),
+            Topology::Hex => (px / size, py / (size * 3f64.sqrt() / 2.0)),
+            Topology::Triangle => (2.0 * px / size, py / (size * 3f64.sqrt() / 2.0)),
+        };
+        if col < -1.0 || row < -1.0 {
+            return None;
+        }
+        // the estimate is off by at most a cell or two where the shapes interlock
+        let (col, row) = (col.floor() as i64, row.floor() as i64);
+        (row - 1..=row + 1).flat_map(|y| (col - 2..=col + 2).map(move |x| (x, y)))
+            .filter(|&(x, y)| x >= 0 && y >= 0)
+            .map(|(x, y)| (x as usize, y as usize))
+            .find(|&cell| contains(&self.corners(cell, size), (px, py)))
+    }
+
+    // how big the canvas must be to show `cols` x `rows` cells
+    pub fn canvas_size(self, cols: usize, rows: usize, size: f64) -> (f64, f64) {
+        let (cols, rows) = (cols as f64, rows as f64);
+        match self {
+            Topology::Square => (cols * size, rows * size),
+            Topology::Hex => ((cols + 0.5) * size, (rows - 1.0) * size * 3f64.sqrt() / 2.0 + 2.0 * size / 3f64.sqrt()),
+            Topology::Triangle => ((cols + 1.0) * size / 2.0, rows * size * 3f64.sqrt() / 2.0),
+        }
+    }
+}
+
+
+// whether the point is inside the convex outline, edges included
+pub fn contains(corners: &[(f64, f64)], (px, py): (f64, f64)) -> bool {
+    let mut sign = 0.0;
+    for (index, &(x0, y0)) in corners.iter().enumerate() {
+        let (x1, y1) = corners[(index + 1) % corners.len()];
+        let cross = (x1 - x0) * (py - y0) - (y1 - y0) * (px - x0);
+        if cross != 0.0 {
+            if sign * cross < 0.0 {
+                return false;
+            }
+            sign = cross;
+        }
+    }
+    true
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maze::CellType;
+    use crate::maze::pathfinding::Links;
+    use crate::maze::pathfinding::bfs::distances_from;
+    use crate::maze::pathfinding::grid::Grid;
+
+    const ALL: [Topology; 3] = [Topology::Square, Topology::Hex, Topology::Triangle];
+
+    fn offset((x, y): (usize, usize), (dx, dy): (i32, i32)) -> (usize, usize) {
+        ((x as i32 + dx) as usize, (y as i32 + dy) as usize)
+    }
+
+    #[test]
+    fn neighbours_share_a_side_both_ways() {
+        for topology in ALL {
+            for y in 1..5 {
+                for x in 1..5 {
+                    for &dir in topology.directions((x, y)) {
+                        let next = offset((x, y), dir);
+                        let back = topology.directions(next).iter().any(|&dir| offset(next, dir) == (x, y));
+                        assert!(back, "{:?} {:?} to {:?} has no way back", topology, (x, y), next);
+                        // two corners in common, so a side and not just a point
+                        let corners = topology.corners(next, 10.0);
+                        let shared = topology.corners((x, y), 10.0).iter()
+                            .filter(|&&(cx, cy)| corners.iter().any(|&(nx, ny)| (cx - nx).abs() + (cy - ny).abs() < 1e-9))
+                            .count();
+                        assert_eq!(shared, 2, "{:?} {:?} and {:?}", topology, (x, y), next);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn triangles_pointing_up_and_down_take_turns() {
+        // up where x + y is even: across the flat side is the row below, and every neighbour points down
+        assert_eq!(Topology::Triangle.directions((2, 2)), &[(-1, 0), (1, 0), (0, 1)]);
+        assert_eq!(Topology::Triangle.directions((3, 2)), &[(-1, 0), (1, 0), (0, -1)]);
+        for y in 1..5 {
+            for x in 1..5 {
+                for &dir in Topology::Triangle.directions((x, y)) {
+                    let (nx, ny) = offset((x, y), dir);
+                    assert_ne!((x + y) % 2, (nx + ny) % 2);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn clockwise_moves_are_the_directions_with_their_headings() {
+        for topology in ALL {
+            for y in 1..5 {
+                for x in 1..5 {
+                    let around = topology.clockwise((x, y));
+                    let mut moves: Vec<(i32, i32)> = around.iter().map(|&(dir, _)| dir).collect();
+                    let mut directions = topology.directions((x, y)).to_vec();
+                    moves.sort();
+                    directions.sort();
+                    assert_eq!(moves, directions);
+
+                    // the heading points at the neighbour's centre, and they go round clockwise
+                    let (x0, y0) = topology.centre((x, y), 1.0);
+                    for (index, &(dir, heading)) in around.iter().enumerate() {
+                        let (x1, y1) = topology.centre(offset((x, y), dir), 1.0);
+                        let angle = (y0 - y1).atan2(x1 - x0).to_degrees().rem_euclid(360.0);
+                        assert!((angle - 30.0 * heading as f64).abs() < 1e-9, "{:?} {:?} {:?}", topology, (x, y), dir);
+                        let next = around[(index + 1) % around.len()].1;
+                        assert!((heading - next).rem_euclid(12) < 6);
+                    }
+                }
+            }
+        }
+    }
+
+    // exact for squares and hexes; triangles can need more moves than the estimate,
+    // depending on which way up the two ends point
+    #[test]
+    fn distance_is_the_fewest_moves_on_an_open_floor() {
+        let mut grid = Grid::new(9, 8);
+        for y in 0..8 {
+            for x in 0..9 {
+                grid.set((x, y), CellType::Path);
+            }
+        }
+        for topology in ALL {
+            let links = Links { topology, ..Links::default() };
+            for from in [(0, 0), (4, 3), (8, 7), (3, 6)] {
+                let moves = distances_from(&grid, &links, from);
+                for cell in grid.open_cells() {
+                    let (estimate, moves) = (topology.distance(from, cell), moves[grid.index(cell)] as f64);
+                    if topology == Topology::Triangle {
+                        assert!(estimate <= moves, "{:?} to {:?}", from, cell);
+                    } else {
+                        assert_eq!(estimate, moves, "{:?} {:?} to {:?}", topology, from, cell);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn gap_counts_steps_between_centres() {
+        for topology in ALL {
+            for &dir in topology.directions((2, 2)) {
+                assert!((topology.gap((2, 2), offset((2, 2), dir)) - 1.0).abs() < 1e-9);
+            }
+        }
+        assert_eq!(Topology::Square.gap((1, 1), (4, 5)), 5.0);
+    }
+}
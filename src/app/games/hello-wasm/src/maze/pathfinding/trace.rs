@@ -0,0 +1,300 @@
+// a recording of one solver run as the ordered list of what it did, enough to play the run
+// back on the canvas without running the solver again:
+//   push   a cell went onto the frontier
+//   pop    a cell came off it to be expanded
+//   visit  a cell was coloured as reached, in one of the trace's colours
+//   relax  a cell's best known cost went down
+//   path   a cell was drawn as part of the final path
+// the classical solvers report all five, the others only what they draw (visit and path)
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    Push((usize, usize)),
+    Pop((usize, usize)),
+    Visit((usize, usize), usize), // the index of its colour in Trace::colors
+    Relax((usize, usize), usize), // the new cost
+    Path((usize, usize)),
+}
+
+impl Event {
+    pub fn cell(&self) -> (usize, usize) {
+        match *self {
+            Event::Push(cell) | Event::Pop(cell) | Event::Visit(cell, _) | Event::Relax(cell, _) | Event::Path(cell) => cell,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Event::Push(_) => "push",
+            Event::Pop(_) => "pop",
+            Event::Visit(..) => "visit",
+            Event::Relax(..) => "relax",
+            Event::Path(_) => "path",
+        }
+    }
+
+    // the colour index of a visit or the cost of a relax
+    fn value(&self) -> Option<usize> {
+        match *self {
+            Event::Visit(_, value) | Event::Relax(_, value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn from_parts(name: &str, cell: (usize, usize), value: Option<usize>) -> Option<Event> {
+        match (name, value) {
+            ("push", None) => Some(Event::Push(cell)),
+            ("pop", None) => Some(Event::Pop(cell)),
+            ("visit", Some(color)) => Some(Event::Visit(cell, color)),
+            ("relax", Some(cost)) => Some(Event::Relax(cell, cost)),
+            ("path", None) => Some(Event::Path(cell)),
+            _ => None,
+        }
+    }
+}
+
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Trace {
+    pub solver: String,
+    pub start: (usize, usize),
+    pub target: (usize, usize),
+    pub colors: Vec<String>, // every colour a visit used, each stored once
+    pub events: Vec<Event>,
+    pub recording: bool,
+}
+
+// the first bytes of a binary trace, the last one being the format version
+const MAGIC: &[u8; 4] = b"MZT1";
+
+impl Trace {
+    // an empty trace that records until stopped
+    pub fn begin(solver: &str, start: (usize, usize), target: (usize, usize)) -> Trace {
+        Trace { solver: solver.to_string(), start, target, recording: true, ..Trace::default() }
+    }
+
+    pub fn record(&mut self, event: Event) {
+        if self.recording {
+            self.events.push(event);
+        }
+    }
+
+    pub fn record_visit(&mut self, cell: (usize, usize), color: &str) {
+        if !self.recording {
+            return;
+        }
+        let index = match self.colors.iter().position(|known| known == color) {
+            Some(index) => index,
+            None => {
+                self.colors.push(color.to_string());
+                self.colors.len() - 1
+            },
+        };
+        self.events.push(Event::Visit(cell, index));
+    }
+
+    // {"solver": .., "start": [x, y], "target": [x, y], "colors": [..], "events": [["visit", x, y, 0], ..]}
+    pub fn to_json(&self) -> String {
+        let quote = |text: &str| format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""));
+        let colors: Vec<String> = self.colors.iter().map(|color| quote(color)).collect();
+        let events: Vec<String> = self.events.iter().map(|event| {
+            let (x, y) = event.cell();
+            match event.value() {
+                Some(value) => format!("[\"{}\",{},{},{}]", event.name(), x, y, value),
+                None => format!("[\"{}\",{},{}]", event.name(), x, y),
+            }
+        }).collect();
+        format!(
+            "{{\"solver\":{},\"start\":[{},{}],\"target\":[{},{}],\"colors\":[{}],\"events\":[{}]}}",
+            quote(&self.solver), self.start.0, self.start.1, self.target.0, self.target.1,
+            colors.join(","), events.join(","),
+        )
+    }
+
+    // MAGIC, then everything as unsigned LEB128 numbers: the solver name, start, target,
+    // the colours, and the events as a tag (0 push, 1 pop, 2 visit, 3 relax, 4 path),
+    // the cell and the colour or cost where there is one. strings are a length and utf-8 bytes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        write_text(&mut bytes, &self.solver);
+        for number in [self.start.0, self.start.1, self.target.0, self.target.1, self.colors.len()] {
+            write_number(&mut bytes, number);
+        }
+        for color in self.colors.iter() {
+            write_text(&mut bytes, color);
+        }
+        write_number(&mut bytes, self.events.len());
+        for event in self.events.iter() {
+            let tag = match event {
+                Event::Push(_) => 0,
+                Event::Pop(_) => 1,
+                Event::Visit(..) => 2,
+                Event::Relax(..) => 3,
+                Event::Path(_) => 4,
+            };
+            bytes.push(tag);
+            write_number(&mut bytes, event.cell().0);
+            write_number(&mut bytes, event.cell().1);
+            if let Some(value) = event.value() {
+                write_number(&mut bytes, value);
+            }
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<Trace> {
+        let mut reader = Reader { bytes: bytes.strip_prefix(MAGIC)?, at: 0 };
+        let solver = reader.text()?;
+        let start = (reader.number()?, reader.number()?);
+        let target = (reader.number()?, reader.number()?);
+        let colors = (0..reader.number()?).map(|_| reader.text()).collect::<Option<Vec<String>>>()?;
+        let count = reader.number()?;
+        let mut events = Vec::with_capacity(count.min(bytes.len()));
+        for _ in 0..count {
+            let tag = reader.byte()?;
+            let cell = (reader.number()?, reader.number()?);
+            events.push(match tag {
+                0 => Event::Push(cell),
+                1 => Event::Pop(cell),
+                2 => Event::Visit(cell, reader.number()?),
+                3 => Event::Relax(cell, reader.number()?),
+                4 => Event::Path(cell),
+                _ => return None,
+            });
+        }
+        let trace = Trace { solver, start, target, colors, events, recording: false };
+        trace.is_consistent().then_some(trace)
+    }
+
+    // every visit names a colour the trace has
+    pub fn is_consistent(&self) -> bool {
+        self.events.iter().all(|event| !matches!(event, Event::Visit(_, color) if *color >= self.colors.len()))
+    }
+}
+
+
+fn write_number(bytes: &mut Vec<u8>, mut number: usize) {
+    loop {
+        let low = (number & 0x7f) as u8;
+        number >>= 7;
+        if number == 0 {
+            bytes.push(low);
+            return;
+        }
+        bytes.push(low | 0x80);
+    }
+}
+
+fn write_text(bytes: &mut Vec<u8>, text: &str) {
+    write_number(bytes, text.len());
+    bytes.extend_from_slice(text.as_bytes());
+}
+
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl Reader<'_> {
+    fn byte(&mut self) -> Option<u8> {
+        let byte = *self.bytes.get(self.at)?;
+        self.at += 1;
+        Some(byte)
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let mut number = 0usize;
+        for shift in (0..usize::BITS).step_by(7) {
+            let byte = self.byte()?;
+            number |= ((byte & 0x7f) as usize) << shift;
+            if byte & 0x80 == 0 {
+                return Some(number);
+            }
+        }
+        None
+    }
+
+    fn text(&mut self) -> Option<String> {
+        let length = self.number()?;
+        let text = self.bytes.get(self.at..self.at.checked_add(length)?)?;
+        self.at += length;
+        String::from_utf8(text.to_vec()).ok()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(number: usize) -> Option<usize> {
+        let mut bytes = Vec::new();
+        write_number(&mut bytes, number);
+        let mut reader = Reader { bytes: &bytes, at: 0 };
+        let read = reader.number();
+        assert_eq!(reader.at, bytes.len(), "{} left bytes unread", number);
+        read
+    }
+
+    #[test]
+    fn numbers_round_trip() {
+        for number in [0, 1, 127, 128, 255, 300, 16383, 16384, u32::MAX as usize, usize::MAX - 1, usize::MAX] {
+            assert_eq!(round_trip(number), Some(number));
+        }
+    }
+
+    #[test]
+    fn numbers_take_seven_bits_a_byte() {
+        let encode = |number: usize| {
+            let mut bytes = Vec::new();
+            write_number(&mut bytes, number);
+            bytes
+        };
+        assert_eq!(encode(0), vec![0]);
+        assert_eq!(encode(127), vec![0x7f]);
+        assert_eq!(encode(128), vec![0x80, 0x01]);
+        assert_eq!(encode(300), vec![0xac, 0x02]);
+    }
+
+    #[test]
+    fn cut_off_numbers_are_refused() {
+        assert_eq!(Reader { bytes: &[0x80], at: 0 }.number(), None);
+        // more continuation bytes than a usize has room for
+        assert_eq!(Reader { bytes: &[0xff; 11], at: 0 }.number(), None);
+    }
+
+    fn sample() -> Trace {
+        let mut trace = Trace::begin("AStar", (1, 1), (300, 2));
+        trace.record(Event::Push((1, 1)));
+        trace.record(Event::Pop((1, 1)));
+        trace.record_visit((2, 1), "gray");
+        trace.record_visit((200, 1), "#ffd700");
+        trace.record_visit((3, 1), "gray");
+        trace.record(Event::Relax((200, 1), 70000));
+        trace.record(Event::Path((300, 2)));
+        trace.recording = false;
+        trace
+    }
+
+    #[test]
+    fn traces_round_trip() {
+        let trace = sample();
+        assert_eq!(trace.colors, vec!["gray".to_string(), "#ffd700".to_string()]);
+        assert_eq!(Trace::from_bytes(&trace.to_bytes()), Some(trace));
+        assert_eq!(Trace::from_bytes(&Trace::default().to_bytes()), Some(Trace::default()));
+    }
+
+    #[test]
+    fn damaged_traces_are_refused() {
+        let bytes = sample().to_bytes();
+        assert_eq!(Trace::from_bytes(&bytes[..bytes.len() - 1]), None);
+        assert_eq!(Trace::from_bytes(&bytes[1..]), None);
+
+        // a visit in a colour the trace does not have
+        let mut trace = sample();
+        trace.events.push(Event::Visit((1, 1), 2));
+        assert_eq!(Trace::from_bytes(&trace.to_bytes()), None);
+    }
+}
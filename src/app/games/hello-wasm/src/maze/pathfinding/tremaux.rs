@@ -0,0 +1,175 @@
+use wasm_bindgen::prelude::*;
+use crate::maze::MazeState;
+use super::{Links, Walked};
+use super::grid::{Grid, Scratch, NO_PARENT};
+
+
+// trémaux's algorithm: chalk a mark on every passage each time it is walked, never walk one
+// that already has two marks, and turn back when arriving somewhere old along a fresh passage.
+// it always finds the target if one is reachable, and the passages marked exactly once
+// form the route from the start
+pub struct TremauxSolver ;
+
+// the marks on the passage between two neighbouring cells (or the two ends of a portal):
+// two bits in the search cost of the lower indexed end, at the place of the other end among
+// its neighbours. a cell the search has not seen has no marks on any of its passages
+fn passage(grid: &Grid, links: &Links, a: usize, b: usize) -> (usize, u32) {
+    let (low, high) = if a <= b { (a, b) } else { (b, a) };
+    let slot = grid.neighbours(links, low).position(|(next, _)| next == high).unwrap_or(0);
+    (low, 2 * slot as u32)
+}
+
+fn marks(scratch: &Scratch, (index, shift): (usize, u32)) -> u32 {
+    if scratch.seen(index) { (scratch.cost[index] >> shift) & 3 } else { 0 }
+}
+
+// adds a mark to the passage and returns how many it has now
+fn add_mark(scratch: &mut Scratch, (index, shift): (usize, u32)) -> u32 {
+    let all = if scratch.seen(index) { scratch.cost[index] } else { 0 };
+    let mark = marks(scratch, (index, shift)) + 1;
+    scratch.reach(index, NO_PARENT, (all & !(3 << shift)) | (mark << shift));
+    mark
+}
+
+impl TremauxSolver {
+    pub async fn find_path(
+        &mut self,
+        match_state: &mut MazeState,
+        delay_ms: i32,
+    ) -> Result<JsValue, JsValue> {
+        let (grid, mut scratch) = match_state.take_search_buffers();
+        let walked = tremaux(&grid, &match_state.links, &mut scratch, match_state.computer_player, match_state.target);
+        match_state.give_search_buffers(scratch);
+        match_state.draw_walk(walked, delay_ms).await
+    }
+}
+
+
+// the walk itself, without drawing; the steps that walk a passage for the second time are
+// the ones backing out of it
+pub fn tremaux(
+    grid: &Grid,
+    links: &Links,
+    scratch: &mut Scratch,
+    start: (usize, usize),
+    target: (usize, usize),
+) -> Walked {
+    scratch.begin(grid.cell_count());
+    let start = grid.index(start);
+    let target = grid.index(target);
+
+    let mut current = start;
+    let mut previous: Option<usize> = None;
+    let mut steps = Vec::new();
+    let mut neighbours = Vec::new();
+
+    while current != target {
+        neighbours.clear();
+        neighbours.extend(grid.neighbours(links, current).map(|(next, _)| next));
+        let mark_of = |next: usize| marks(scratch, passage(grid, links, current, next));
+
+        let mut next = None;
+        if let Some(prev) = previous {
+            // arrived somewhere we have been before along a fresh passage: go back the way we came
+            let been_here = neighbours.iter().any(|&cell| cell != prev && mark_of(cell) > 0);
+            if been_here && mark_of(prev) == 1 {
+                next = Some(prev);
+            }
+        }
+        if next.is_none() {
+            // otherwise the least walked passage, never one with two marks,
+            // and the one we came in by only if nothing else is left
+            next = neighbours.iter()
+                .copied()
+                .filter(|&cell| mark_of(cell) < 2)
+                .min_by_key(|&cell| (mark_of(cell), Some(cell) == previous));
+        }
+
+        let Some(cell) = next else {
+            let reason = format!("every passage marked twice after {} steps", steps.len());
+            return Walked { steps, path: Err(reason) };
+        };
+
+        let mark = add_mark(scratch, passage(grid, links, current, cell));
+        previous = Some(current);
+        current = cell;
+        steps.push((grid.cell(current), mark > 1));
+    }
+
+    // follow the passages marked exactly once back from the target
+    let mut path = vec![target];
+    let mut cell = target;
+    while cell != start {
+        let Some(prev) = grid.neighbours(links, cell)
+            .map(|(next, _)| next)
+            .find(|&n| marks(scratch, passage(grid, links, cell, n)) == 1 && !path.contains(&n))
+        else {
+            break;
+        };
+        path.push(prev);
+        cell = prev;
+    }
+    let path = path.into_iter().rev().map(|index| grid.cell(index)).collect();
+    Walked { steps, path: Ok(path) }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maze::read_maze;
+
+    // a corridor along the top with a dead end hanging off each end of it
+    const BRANCHES: &str = "\
+*******
+*     *
+* *** *
+* *   *
+*******
+";
+
+    // two ways round a block from (1, 1) to (5, 1)
+    const LOOP: &str = "\
+*******
+*   * *
+* * * *
+* *   *
+* *** *
+*     *
+*******
+";
+
+    fn walk(text: &str, start: (usize, usize), target: (usize, usize)) -> Walked {
+        let file = read_maze(text).expect("valid maze text");
+        tremaux(&file.grid, &file.links, &mut Scratch::default(), start, target)
+    }
+
+    #[test]
+    fn backs_out_of_a_dead_end() {
+        let walked = walk(BRANCHES, (1, 1), (5, 1));
+        // down is tried before right, so it walks into the dead end at (1, 3) first
+        assert_eq!(&walked.steps[..4], &[((1, 2), false), ((1, 3), false), ((1, 2), true), ((1, 1), true)]);
+        assert_eq!(walked.path, Ok(vec![(1, 1), (2, 1), (3, 1), (4, 1), (5, 1)]));
+    }
+
+    #[test]
+    fn route_round_a_loop_is_a_simple_path() {
+        let walked = walk(LOOP, (1, 1), (5, 1));
+        let path = walked.path.expect("the target is reachable");
+        assert_eq!(path.first(), Some(&(1, 1)));
+        assert_eq!(path.last(), Some(&(5, 1)));
+        for pair in path.windows(2) {
+            assert_eq!(pair[0].0.abs_diff(pair[1].0) + pair[0].1.abs_diff(pair[1].1), 1);
+        }
+        for (index, cell) in path.iter().enumerate() {
+            assert!(!path[index + 1..].contains(cell));
+        }
+    }
+
+    #[test]
+    fn walled_off_target_is_reported() {
+        let sealed = BRANCHES.replace("* *** *", "* *****");
+        let walked = walk(&sealed, (1, 1), (3, 3));
+        assert!(walked.path.expect_err("no way in").starts_with("every passage marked twice"));
+    }
+}
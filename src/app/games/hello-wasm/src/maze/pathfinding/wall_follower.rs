@@ -0,0 +1,161 @@
+use wasm_bindgen::prelude::*;
+use crate::maze::MazeState;
+use super::{entered_by, open_step, Links, Walked};
+use super::grid::{Grid, Scratch, NO_PARENT};
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Hand {
+    Left,
+    Right,
+}
+
+// keeps one hand on the wall and walks until it bumps into the target,
+// it only ever looks at the cells around it, so it walks straight over portals
+pub struct WallFollowerSolver {
+    pub hand: Hand,
+}
+
+impl WallFollowerSolver {
+    pub async fn find_path(
+        &mut self,
+        match_state: &mut MazeState,
+        delay_ms: i32,
+    ) -> Result<JsValue, JsValue> {
+        let (grid, mut scratch) = match_state.take_search_buffers();
+        let links = match_state.links.clone();
+        let walked = follow_wall(&grid, &links, &mut scratch, self.hand, match_state.computer_player, match_state.target);
+        match_state.give_search_buffers(scratch);
+        match_state.draw_walk(walked, delay_ms).await
+    }
+}
+
+
+// the walk itself, without drawing. the start and target are open cells, the cells around
+// are those of `links.topology`
+pub fn follow_wall(
+    grid: &Grid,
+    links: &Links,
+    scratch: &mut Scratch,
+    hand: Hand,
+    start: (usize, usize),
+    target: (usize, usize),
+) -> Walked {
+    scratch.begin(grid.cell_count());
+
+    let topology = links.topology;
+    let mut current = start;
+    // the side it came in by, as an index into the clockwise moves of the cell. the start is
+    // entered from half way round, so on square cells the walk sets off facing up
+    let mut back = topology.clockwise(start).len() / 2;
+    let mut walk = vec![start];
+    // seeing the same cell entered by the same side twice means we are circling an island.
+    // the sides each cell was entered by are bits of its search cost
+    while current != target {
+        let index = grid.index(current);
+        let sides = if scratch.seen(index) { scratch.cost[index] } else { 0 };
+        scratch.reach(index, NO_PARENT, sides | (1 << back));
+        if sides & (1 << back) != 0 {
+            let steps = walk.len() - 1;
+            return Walked::gave_up(&walk, format!("circled back after {} steps", steps));
+        }
+
+        // going round from the side it came in by, clockwise for the left hand and the other
+        // way for the right: the hand side first, then on to the other side, and back last
+        let around = topology.clockwise(current);
+        let sides = around.len();
+        let next = (1..=sides)
+            .map(|turn| match hand {
+                Hand::Left => (back + turn) % sides,
+                Hand::Right => (back + sides - turn) % sides,
+            })
+            .find_map(|side| open_step(grid, current, around[side].0).map(|cell| (around[side].1, cell)));
+
+        let Some((heading, cell)) = next else {
+            return Walked::gave_up(&walk, "walled in".to_string());
+        };
+        back = entered_by(topology, cell, heading);
+        current = cell;
+        walk.push(current);
+    }
+    Walked::arrived(&walk)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maze::pathfinding::topology::Topology;
+    use crate::maze::read_maze;
+
+    // the start (3, 2) is on a ring around the pillar at (2, 2), the way out leads east and
+    // down to the target (6, 4). (1, 4) is a dead end off the ring
+    const ISLAND: &str = "\
+*********
+*   *****
+* *    **
+*   ** **
+* **** **
+*********
+";
+
+    fn walk(text: &str, hand: Hand, target: (usize, usize)) -> Walked {
+        let file = read_maze(text).expect("valid maze text");
+        follow_wall(&file.grid, &file.links, &mut Scratch::default(), hand, (3, 2), target)
+    }
+
+    #[test]
+    fn left_hand_circles_the_island() {
+        // the pillar is on the left hand all the way round, so the way out is never tried
+        let walked = walk(ISLAND, Hand::Left, (6, 4));
+        assert_eq!(walked.path, Err("circled back after 8 steps".to_string()));
+        assert!(walked.steps.iter().all(|&((x, _), _)| x <= 3));
+    }
+
+    #[test]
+    fn right_hand_leaves_the_island() {
+        let walked = walk(ISLAND, Hand::Right, (6, 4));
+        assert_eq!(walked.path, Ok(vec![(3, 2), (4, 2), (5, 2), (6, 2), (6, 3), (6, 4)]));
+    }
+
+    // a room with two walls reaching in from the edges, no islands whatever the cell shape.
+    // the start (1, 1) is a dead end with every tiling, so the hand starts out on a wall
+    const COMB: &str = "\
+***********
+*    *    *
+***  *  * *
+****    * *
+***********
+";
+
+    // every step of the walk goes to a neighbour of the tiling
+    fn assert_steps_along(topology: Topology, walked: &Walked) {
+        let mut cells = vec![(1, 1)];
+        cells.extend(walked.steps.iter().map(|&(cell, _)| cell));
+        for pair in cells.windows(2) {
+            let (x, y) = (pair[1].0 as i32 - pair[0].0 as i32, pair[1].1 as i32 - pair[0].1 as i32);
+            assert!(topology.directions(pair[0]).contains(&(x, y)), "{:?} to {:?}", pair[0], pair[1]);
+        }
+    }
+
+    #[test]
+    fn follows_the_wall_round_hex_and_triangle_cells() {
+        for topology in [Topology::Hex, Topology::Triangle] {
+            let mut file = read_maze(COMB).expect("valid maze text");
+            file.links.topology = topology;
+            for hand in [Hand::Left, Hand::Right] {
+                let walked = follow_wall(&file.grid, &file.links, &mut Scratch::default(), hand, (1, 1), (9, 1));
+                let path = walked.path.clone().unwrap_or_else(|reason| panic!("{:?} {:?}: {}", topology, hand, reason));
+                assert_eq!((path[0], path[path.len() - 1]), ((1, 1), (9, 1)));
+                assert_steps_along(topology, &walked);
+            }
+        }
+    }
+
+    #[test]
+    fn walled_off_target_is_given_up() {
+        let sealed = ISLAND.replace("*   ** **", "*   *****");
+        let walked = walk(&sealed, Hand::Right, (6, 4));
+        assert!(walked.path.is_err());
+    }
+}
@@ -0,0 +1,334 @@
+// a small PNG encoder for the exported figures: 8 bit RGBA, no filtering, one IDAT chunk
+// compressed with a single fixed huffman deflate block. the figures are large areas of a few
+// flat colours, which the LZ77 matches alone bring down to a fraction of the raw size
+
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+
+const WINDOW: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: usize = 15;
+const MAX_CHAIN: usize = 16; // earlier positions tried for every match
+
+const LENGTH_BASE: [usize; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u32; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [usize; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DISTANCE_EXTRA: [u32; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+
+
+// `rgba` is width x height pixels, row by row
+pub fn encode(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+    let stride = width as usize * 4;
+    let mut raw = Vec::with_capacity((stride + 1) * height as usize);
+    for row in rgba.chunks(stride.max(1)).take(height as usize) {
+        raw.push(0); // filter type none
+        raw.extend_from_slice(row);
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    header.extend_from_slice(&[8, 6, 0, 0, 0]); // 8 bits, rgba, deflate, no filter method, no interlace
+
+    let mut zlib = vec![0x78, 0x01];
+    zlib.extend(deflate(&raw));
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut png = SIGNATURE.to_vec();
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"IDAT", &zlib);
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+
+// bits go out least significant first, huffman codes most significant first
+struct Bits {
+    bytes: Vec<u8>,
+    buffer: u64,
+    count: u32,
+}
+
+impl Bits {
+    fn put(&mut self, value: u32, count: u32) {
+        self.buffer |= (value as u64) << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn code(&mut self, code: u32, length: u32) {
+        self.put(code.reverse_bits() >> (32 - length), length);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+// the fixed huffman code of a literal or length symbol
+fn literal(bits: &mut Bits, symbol: usize) {
+    let symbol = symbol as u32;
+    match symbol {
+        0..=143 => bits.code(0x30 + symbol, 8),
+        144..=255 => bits.code(0x190 + symbol - 144, 9),
+        256..=279 => bits.code(symbol - 256, 7),
+        _ => bits.code(0xc0 + symbol - 280, 8),
+    }
+}
+
+fn length_and_distance(bits: &mut Bits, length: usize, distance: usize) {
+    let code = LENGTH_BASE.iter().rposition(|&base| base <= length).expect("matches are at least 3 long");
+    literal(bits, 257 + code);
+    bits.put((length - LENGTH_BASE[code]) as u32, LENGTH_EXTRA[code]);
+    let code = DISTANCE_BASE.iter().rposition(|&base| base <= distance).expect("distances are at least 1");
+    bits.code(code as u32, 5);
+    bits.put((distance - DISTANCE_BASE[code]) as u32, DISTANCE_EXTRA[code]);
+}
+
+fn hash(data: &[u8], at: usize) -> usize {
+    let value = (data[at] as usize) << 16 | (data[at + 1] as usize) << 8 | data[at + 2] as usize;
+    (value.wrapping_mul(2654435761) >> 8) & ((1 << HASH_BITS) - 1)
+}
+
+// one final fixed huffman block, greedy matches from hash chains
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut bits = Bits { bytes: Vec::new(), buffer: 0, count: 0 };
+    bits.put(1, 1); // final block
+    bits.put(1, 2); // fixed huffman codes
+
+    // the chains only ever go back a window, so their links are kept for the last window's
+    // positions alone: the slot of a position older than that has been taken by a newer one
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut previous = vec![usize::MAX; WINDOW];
+
+    let mut at = 0;
+    while at < data.len() {
+        let mut best = (0, 0);
+        if at + MIN_MATCH <= data.len() {
+            let mut candidate = head[hash(data, at)];
+            for _ in 0..MAX_CHAIN {
+                if candidate == usize::MAX || at - candidate > WINDOW {
+                    break;
+                }
+                let limit = MAX_MATCH.min(data.len() - at);
+                let length = (0..limit).take_while(|&offset| data[candidate + offset] == data[at + offset]).count();
+                if length > best.0 {
+                    best = (length, at - candidate);
+                }
+                if length == limit {
+                    break;
+                }
+                candidate = previous[candidate % WINDOW];
+            }
+        }
+
+        if best.0 >= MIN_MATCH {
+            length_and_distance(&mut bits, best.0, best.1);
+            for position in at..at + best.0 {
+                insert(data, &mut head, &mut previous, position);
+            }
+            at += best.0;
+        } else {
+            literal(&mut bits, data[at] as usize);
+            insert(data, &mut head, &mut previous, at);
+            at += 1;
+        }
+    }
+    literal(&mut bits, 256);
+    bits.finish()
+}
+
+// makes `at` the latest position of its hash, chained to the one before
+fn insert(data: &[u8], head: &mut [usize], previous: &mut [usize], at: usize) {
+    if at + MIN_MATCH <= data.len() {
+        let slot = hash(data, at);
+        previous[at % WINDOW] = head[slot];
+        head[slot] = at;
+    }
+}
+
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { 0xedb88320 ^ (crc >> 1) } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+
+    // just enough of an inflater for one fixed huffman block, written from the format
+    // rather than from the encoder above
+    struct Input<'a> {
+        bytes: &'a [u8],
+        at: usize, // in bits
+    }
+
+    impl Input<'_> {
+        fn bit(&mut self) -> u32 {
+            let bit = (self.bytes[self.at / 8] >> (self.at % 8)) & 1;
+            self.at += 1;
+            bit as u32
+        }
+
+        // least significant first
+        fn bits(&mut self, count: u32) -> u32 {
+            (0..count).map(|shift| self.bit() << shift).sum()
+        }
+
+        // most significant first
+        fn code(&mut self, length: u32) -> u32 {
+            (0..length).fold(0, |code, _| code << 1 | self.bit())
+        }
+
+        fn symbol(&mut self) -> usize {
+            let code = self.code(7);
+            if code <= 0x17 {
+                return 256 + code as usize;
+            }
+            let code = code << 1 | self.bit();
+            match code {
+                0x30..=0xbf => (code - 0x30) as usize,
+                0xc0..=0xc7 => (280 + code - 0xc0) as usize,
+                _ => (144 + (code << 1 | self.bit()) - 0x190) as usize,
+            }
+        }
+    }
+
+    fn inflate(bytes: &[u8]) -> Vec<u8> {
+        let mut input = Input { bytes, at: 0 };
+        assert_eq!(input.bits(1), 1, "a single final block");
+        assert_eq!(input.bits(2), 1, "fixed huffman codes");
+        let mut out: Vec<u8> = Vec::new();
+        loop {
+            let symbol = input.symbol();
+            match symbol {
+                0..=255 => out.push(symbol as u8),
+                256 => return out,
+                _ => {
+                    let code = symbol - 257;
+                    let length = LENGTH_BASE[code] + input.bits(LENGTH_EXTRA[code]) as usize;
+                    let code = input.code(5) as usize;
+                    let distance = DISTANCE_BASE[code] + input.bits(DISTANCE_EXTRA[code]) as usize;
+                    assert!(distance <= out.len() && distance <= WINDOW);
+                    for _ in 0..length {
+                        out.push(out[out.len() - distance]);
+                    }
+                },
+            }
+        }
+    }
+
+    fn samples() -> Vec<Vec<u8>> {
+        let mut rng = StdRng::seed_from_u64(5);
+        let far: Vec<u8> = (0..40000).map(|_| rng.r#gen()).collect();
+        vec![
+            Vec::new(),
+            b"a".to_vec(),
+            b"abcabcabcabcabcabcabc".to_vec(),
+            vec![7; 1000], // runs longer than the longest match
+            (0..5000).map(|_| rng.gen_range(0..4)).collect(),
+            far.repeat(2), // repeats from further back than the window
+            (0..256).map(|byte| byte as u8).collect(),
+        ]
+    }
+
+    #[test]
+    fn deflate_round_trips() {
+        for data in samples() {
+            assert_eq!(inflate(&deflate(&data)), data);
+        }
+    }
+
+    #[test]
+    fn deflate_compresses_repetition() {
+        assert!(deflate(&[7; 1000]).len() < 100);
+    }
+
+    #[test]
+    fn checksums_match_the_reference_values() {
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e60398);
+        assert_eq!(adler32(&[]), 1);
+    }
+
+    // the chunks of a png as (kind, data), checking every crc on the way
+    fn chunks(png: &[u8]) -> Vec<([u8; 4], Vec<u8>)> {
+        assert_eq!(png[..8], SIGNATURE);
+        let mut chunks = Vec::new();
+        let mut at = 8;
+        while at < png.len() {
+            let length = u32::from_be_bytes(png[at..at + 4].try_into().unwrap()) as usize;
+            let body = &png[at + 4..at + 8 + length];
+            let crc = u32::from_be_bytes(png[at + 8 + length..at + 12 + length].try_into().unwrap());
+            assert_eq!(crc32(body), crc);
+            chunks.push((body[..4].try_into().unwrap(), body[4..].to_vec()));
+            at += 12 + length;
+        }
+        chunks
+    }
+
+    #[test]
+    fn encode_writes_the_pixels_row_by_row() {
+        let (width, height) = (3u32, 2u32);
+        let rgba: Vec<u8> = (0..width * height * 4).map(|byte| byte as u8).collect();
+        let png = encode(width, height, &rgba);
+
+        let chunks = chunks(&png);
+        let kinds: Vec<&[u8; 4]> = chunks.iter().map(|(kind, _)| kind).collect();
+        assert_eq!(kinds, vec![b"IHDR", b"IDAT", b"IEND"]);
+        assert_eq!(chunks[0].1, vec![0, 0, 0, 3, 0, 0, 0, 2, 8, 6, 0, 0, 0]);
+
+        let zlib = &chunks[1].1;
+        assert_eq!(zlib[..2], [0x78, 0x01]);
+        assert_eq!((zlib[0] as u32 * 256 + zlib[1] as u32) % 31, 0, "the zlib header check bits");
+        let raw = inflate(&zlib[2..zlib.len() - 4]);
+        assert_eq!(zlib[zlib.len() - 4..], adler32(&raw).to_be_bytes());
+
+        let mut expected = Vec::new();
+        for row in rgba.chunks(12) {
+            expected.push(0);
+            expected.extend_from_slice(row);
+        }
+        assert_eq!(raw, expected);
+    }
+}
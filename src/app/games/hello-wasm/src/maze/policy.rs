@@ -0,0 +1,94 @@
+use wasm_bindgen::prelude::*;
+use crate::maze::{MazeState, PlayerType};
+use crate::maze::pathfinding::learning::{greedy, value, Learning, LearningSolver, Method};
+
+// the reinforcement learning agent: its settings, training, and drawing what it has learned
+// as a value heatmap with the greedy move out of every cell as an arrow
+
+
+#[wasm_bindgen]
+impl MazeState {
+
+    // learning rate, discount and exploration rate for the next train_agent, each between 0 and 1
+    pub fn set_learning(&mut self, alpha: f64, gamma: f64, epsilon: f64) -> Result<(), JsValue> {
+        if [alpha, gamma, epsilon].iter().any(|rate| !(0.0..=1.0).contains(rate)) || alpha == 0.0 {
+            return Err(JsValue::from_str("Learning rates must be between 0 and 1!"));
+        }
+        self.learning = Learning { method: self.learning.method, alpha, gamma, epsilon };
+        Ok(())
+    }
+
+    // trains a fresh "QLearning" or "Sarsa" agent for `episodes` episodes from the computer
+    // player to the target, then draws what it learned and walks its greedy policy
+    pub async fn train_agent(&mut self, method: &str, episodes: usize, delay_ms: i32) -> Result<JsValue, JsValue> {
+        self.learning.method = Method::from_name(method)
+            .ok_or_else(|| JsValue::from_str("Invalid learning method!"))?;
+        let mut learning_solver = LearningSolver { learning: self.learning, episodes };
+        learning_solver.find_path(self, delay_ms).await
+    }
+
+    // how many steps each training episode took, to plot against the shortest path
+    pub fn get_learning_curve(&self) -> Vec<u32> {
+        self.learning_curve.iter().map(|&steps| steps as u32).collect()
+    }
+
+    // every cell the agent has been to coloured by its learned value, hot near the target,
+    // with an arrow towards the move it would make there
+    pub fn draw_learned_values(&mut self) -> Result<(), JsValue> {
+        let mut cells: Vec<((usize, usize), f64)> = self.q_table.keys()
+            .filter_map(|&cell| value(&self.q_table, cell).map(|worth| (cell, worth)))
+            .collect();
+        cells.sort_by_key(|&(cell, _)| cell);
+        let best = cells.iter().map(|&(_, worth)| worth).fold(f64::NEG_INFINITY, f64::max);
+        let worst = cells.iter().map(|&(_, worth)| worth).fold(f64::INFINITY, f64::min);
+        let spread = (best - worst).max(f64::EPSILON);
+
+        self.draw_maze()?;
+        for &((x, y), worth) in cells.iter() {
+            let hue = 240.0 * (best - worth) / spread;
+            self.fill_cell(x, y, &format!("hsl({:.0}, 85%, 55%)", hue));
+        }
+
+        self.pen().set_stroke_style_str(&self.theme.ink);
+        self.pen().set_line_width((self.block_size / 10.0).max(1.0));
+        for &(cell, _) in cells.iter() {
+            if cell == self.target {
+                continue;
+            }
+            let next = greedy(&self.q_table, cell).and_then(|action| self.neighbours(cell).get(action).map(|&(next, _)| next));
+            if let Some(next) = next {
+                self.draw_arrow(cell, next);
+            }
+        }
+        self.draw_player(self.computer_player.0, self.computer_player.1, PlayerType::Computer);
+        self.draw_player(self.target.0, self.target.1, PlayerType::Target);
+        Ok(())
+    }
+}
+
+
+impl MazeState {
+    // a short arrow from the middle of `from` pointing at `to`; a jump through a portal or
+    // a stair has no direction on the canvas, so it gets a ring instead
+    fn draw_arrow(&mut self, from: (usize, usize), to: (usize, usize)) {
+        let (x0, y0) = self.cell_centre(from.0, from.1);
+        let reach = self.block_size * 0.35;
+        self.pen().begin_path();
+        if !self.is_adjacent(from, to) {
+            self.pen().arc(x0, y0, reach / 2.0, 0.0, std::f64::consts::TAU).ok();
+            self.cell_context(from.0, from.1).stroke();
+            return;
+        }
+        let (x1, y1) = self.cell_centre(to.0, to.1);
+        let angle = (y1 - y0).atan2(x1 - x0);
+        let (tip_x, tip_y) = (x0 + reach * angle.cos(), y0 + reach * angle.sin());
+        self.pen().move_to(x0 - reach * angle.cos(), y0 - reach * angle.sin());
+        self.pen().line_to(tip_x, tip_y);
+        for side in [-1.0, 1.0] {
+            let barb = angle + side * 2.5;
+            self.pen().move_to(tip_x, tip_y);
+            self.pen().line_to(tip_x + reach * 0.6 * barb.cos(), tip_y + reach * 0.6 * barb.sin());
+        }
+        self.cell_context(from.0, from.1).stroke();
+    }
+}
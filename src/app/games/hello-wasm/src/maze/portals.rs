@@ -0,0 +1,82 @@
+use wasm_bindgen::prelude::*;
+use crate::maze::MazeState;
+
+// teleporters: two cells marked with the same digit in the maze text are linked, and
+// stepping onto one lets a solver continue from the other for `portals.cost` steps
+
+
+#[wasm_bindgen]
+impl MazeState {
+
+    // how many steps a jump through a portal costs, at least 1
+    pub fn set_portal_cost(&mut self, cost: usize) -> Result<(), JsValue> {
+        if cost == 0 {
+            return Err(JsValue::from_str("Portal cost must be at least 1!"));
+        }
+        self.links.portals.cost = cost;
+        self.links.changed();
+        Ok(())
+    }
+
+    // the portal pairs as flat [x0, y0, x1, y1, ...], both ends of a pair next to each other
+    pub fn get_portals(&self) -> Vec<usize> {
+        let mut pairs: Vec<((usize, usize), (usize, usize))> = self.links.portals.pairs.iter()
+            .filter(|&(from, to)| from < to)
+            .map(|(&from, &to)| (from, to))
+            .collect();
+        pairs.sort();
+        pairs.into_iter().flat_map(|((x0, y0), (x1, y1))| [x0, y0, x1, y1]).collect()
+    }
+}
+
+
+impl MazeState {
+    // every portal cell with its digit written on top
+    pub(super) fn draw_portals(&mut self) {
+        let mut cells: Vec<((usize, usize), char)> = self.portal_labels.iter().map(|(&cell, &digit)| (cell, digit)).collect();
+        cells.sort();
+        for (cell, digit) in cells {
+            self.draw_portal(cell, digit);
+        }
+    }
+
+    pub(super) fn draw_portal(&mut self, (x, y): (usize, usize), digit: char) {
+        self.label_cell(x, y, &self.theme.portal.clone(), &digit.to_string(), &self.theme.light_ink.clone());
+    }
+
+    // an arc between both ends of every portal the path jumps through, so the jump
+    // does not look like the path breaking off
+    pub(super) fn draw_jumps(&mut self, path: &[(usize, usize)]) {
+        for pair in path.windows(2) {
+            let (from, to) = (pair[0], pair[1]);
+            if self.links.portals.partner(from) != Some(to) || self.is_adjacent(from, to) {
+                continue;
+            }
+            for end in [from, to] {
+                if let Some(&digit) = self.portal_labels.get(&end) {
+                    self.draw_portal(end, digit);
+                }
+            }
+
+            self.draw_arc(from, to, &self.theme.final_path.clone());
+        }
+        self.draw_floor_changes(path);
+    }
+
+    // a dashed arc from the centre of one cell to the centre of another
+    pub(super) fn draw_arc(&mut self, from: (usize, usize), to: (usize, usize), color: &str) {
+        let (x0, y0) = self.cell_centre(from.0, from.1);
+        let (x1, y1) = self.cell_centre(to.0, to.1);
+        // bend the arc sideways by a third of its length, off the straight line
+        let (control_x, control_y) = ((x0 + x1) / 2.0 - (y1 - y0) / 3.0, (y0 + y1) / 2.0 + (x1 - x0) / 3.0);
+
+        self.pen().set_stroke_style_str(color);
+        self.pen().set_line_width((self.block_size / 3.0).max(1.0));
+        self.pen().set_line_dash(&js_sys::Array::of2(&JsValue::from_f64(self.block_size), &JsValue::from_f64(self.block_size / 2.0))).ok();
+        self.pen().begin_path();
+        self.pen().move_to(x0, y0);
+        self.pen().quadratic_curve_to(control_x, control_y, x1, y1);
+        self.context_over(&[(x0, y0), (control_x, control_y), (x1, y1)], self.block_size / 2.0).stroke();
+        self.pen().set_line_dash(&js_sys::Array::new()).ok();
+    }
+}
@@ -0,0 +1,90 @@
+use wasm_bindgen::prelude::*;
+use crate::maze::MazeState;
+use crate::maze::pathfinding::components::NO_REGION;
+
+// the connected regions of the maze on the canvas, for spotting parts of a level no one can
+// get to. the labels are kept in the state and only worked out again after the maze changes,
+// so asking whether a cell can reach the target costs a lookup
+
+
+#[wasm_bindgen]
+impl MazeState {
+
+    // the number of open cells in each region, region 0 first. regions are numbered in the
+    // order their first cell comes row by row
+    pub fn components(&mut self) -> Vec<u32> {
+        self.sync_components();
+        self.components.sizes.iter().map(|&size| size as u32).collect()
+    }
+
+    // the region of every cell, row by row over the widest row, 4294967295 (u32::MAX) on walls.
+    // js sees a Uint32Array
+    pub fn component_labels(&mut self) -> Vec<u32> {
+        self.sync_components();
+        self.components.labels.clone()
+    }
+
+    // the region of (x, y), -1 for a wall or a cell outside the maze
+    pub fn component_at(&mut self, x: usize, y: usize) -> i32 {
+        self.sync_components();
+        self.components.region((x, y)).map_or(-1, |region| region as i32)
+    }
+
+    // whether the target can be reached from (x, y)
+    pub fn is_reachable_from(&mut self, x: usize, y: usize) -> bool {
+        self.reachable((x, y), self.target)
+    }
+
+    // paints every region in a colour of its own, walls left as they are
+    pub fn draw_components(&mut self) -> Result<(), JsValue> {
+        self.sync_components();
+        self.draw_maze()?;
+        let width = self.components.width();
+        let colors: Vec<String> = (0..self.components.sizes.len()).map(region_color).collect();
+        let cells: Vec<(usize, u32)> = self.components.labels.iter().copied().enumerate()
+            .filter(|&(_, label)| label != NO_REGION)
+            .collect();
+        for (index, label) in cells {
+            self.fill_cell(index % width, index / width, &colors[label as usize]);
+        }
+        self.fill_cell(self.computer_player.0, self.computer_player.1, &self.theme.computer.clone());
+        self.fill_cell(self.target.0, self.target.1, &self.theme.target.clone());
+        Ok(())
+    }
+
+    // when on, set_target refuses cells the computer player cannot reach, and reset places the
+    // computer player only where it can reach the target
+    pub fn set_reachable_only(&mut self, reachable_only: bool) {
+        self.reachable_only = reachable_only;
+    }
+
+    pub fn get_reachable_only(&self) -> bool {
+        self.reachable_only
+    }
+}
+
+
+impl MazeState {
+    // labels the regions of maze_grid again if it changed since they were last labelled
+    pub(super) fn sync_components(&mut self) {
+        self.components.sync(&self.maze_grid, &self.links);
+    }
+
+    // whether `to` is an open cell that can be reached from `from`
+    pub(super) fn reachable(&mut self, from: (usize, usize), to: (usize, usize)) -> bool {
+        self.sync_components();
+        self.components.connected(from, to)
+    }
+
+    // whether `cell` is open and some other cell can be reached from it
+    pub(super) fn has_company(&mut self, cell: (usize, usize)) -> bool {
+        self.sync_components();
+        self.components.region(cell).is_some_and(|region| self.components.sizes[region] > 1)
+    }
+}
+
+
+// hues a golden angle apart, so neighbouring region numbers never look alike
+fn region_color(region: usize) -> String {
+    format!("hsl({:.0}, 70%, 60%)", (region as f64 * 137.508) % 360.0)
+}
@@ -0,0 +1,150 @@
+use wasm_bindgen::prelude::*;
+use crate::maze::MazeState;
+use crate::maze::pathfinding::trace::{Event, Trace};
+
+// exporting the trace of the last solver run, loading one back, and playing it on the
+// canvas forwards or backwards without running the solver again
+
+
+#[wasm_bindgen]
+impl MazeState {
+
+    pub fn get_trace_json(&self) -> String {
+        self.trace.to_json()
+    }
+
+    pub fn get_trace_binary(&self) -> Vec<u8> {
+        self.trace.to_bytes()
+    }
+
+    // loads a trace written by get_trace_json and puts the players where it started,
+    // returns how many events it has
+    pub fn load_trace_json(&mut self, json: &str) -> Result<usize, JsValue> {
+        let invalid = || JsValue::from_str("Invalid trace!");
+        let value = js_sys::JSON::parse(json).map_err(|_| invalid())?;
+        let field = |name: &str| js_sys::Reflect::get(&value, &JsValue::from_str(name)).map_err(|_| invalid());
+        let number = |value: JsValue| value.as_f64().filter(|number| *number >= 0.0).map(|number| number as usize).ok_or_else(invalid);
+        let pair = |value: JsValue| -> Result<(usize, usize), JsValue> {
+            let array = js_sys::Array::from(&value);
+            Ok((number(array.get(0))?, number(array.get(1))?))
+        };
+
+        let mut trace = Trace {
+            solver: field("solver")?.as_string().ok_or_else(invalid)?,
+            start: pair(field("start")?)?,
+            target: pair(field("target")?)?,
+            ..Trace::default()
+        };
+        for color in js_sys::Array::from(&field("colors")?).iter() {
+            trace.colors.push(color.as_string().ok_or_else(invalid)?);
+        }
+        for event in js_sys::Array::from(&field("events")?).iter() {
+            let parts = js_sys::Array::from(&event);
+            let name = parts.get(0).as_string().ok_or_else(invalid)?;
+            let cell = (number(parts.get(1))?, number(parts.get(2))?);
+            let value = if parts.length() > 3 { Some(number(parts.get(3))?) } else { None };
+            trace.events.push(Event::from_parts(&name, cell, value).ok_or_else(invalid)?);
+        }
+        if !trace.is_consistent() {
+            return Err(invalid());
+        }
+        self.load_trace(trace)
+    }
+
+    // the same from get_trace_binary
+    pub fn load_trace_binary(&mut self, bytes: &[u8]) -> Result<usize, JsValue> {
+        let trace = Trace::from_bytes(bytes).ok_or_else(|| JsValue::from_str("Invalid trace!"))?;
+        self.load_trace(trace)
+    }
+
+    pub fn get_trace_length(&self) -> usize {
+        self.trace.events.len()
+    }
+
+    // the canvas as it was after the first `step` events, with the cell of the last one outlined
+    pub fn show_trace_step(&mut self, step: usize) -> Result<(), JsValue> {
+        let step = step.min(self.trace.events.len());
+        self.clear_visualization()?;
+        for index in 0..step {
+            self.draw_event(self.trace.events[index]);
+        }
+        self.draw_trace_jumps(step);
+        if step > 0 {
+            let (x, y) = self.trace.events[step - 1].cell();
+            self.pen().set_stroke_style_str(&self.theme.current);
+            self.pen().set_line_width((self.block_size / 8.0).max(1.0));
+            self.outline_cell(x, y);
+        }
+        Ok(())
+    }
+
+    // plays the whole trace, `steps_per_frame` events every `delay_ms`; backwards starts from
+    // the finished run and takes events away again
+    pub async fn replay_trace(&mut self, delay_ms: i32, steps_per_frame: usize, backwards: bool) -> Result<JsValue, JsValue> {
+        let length = self.trace.events.len();
+        let stride = steps_per_frame.max(1);
+        if backwards {
+            // a canvas cannot be unpainted, so every frame is drawn again from the start
+            let mut step = length;
+            loop {
+                self.show_trace_step(step)?;
+                if step == 0 {
+                    break;
+                }
+                step = step.saturating_sub(stride);
+                if delay_ms > 0 {
+                    crate::maze::sleep(delay_ms).await?;
+                }
+            }
+        } else {
+            self.clear_visualization()?;
+            for index in 0..length {
+                self.draw_event(self.trace.events[index]);
+                if (index + 1) % stride == 0 && delay_ms > 0 {
+                    crate::maze::sleep(delay_ms).await?;
+                }
+            }
+            self.draw_trace_jumps(length);
+        }
+        Ok(JsValue::from_str(&format!("Replayed {} events of {}", length, self.trace.solver)))
+    }
+}
+
+
+impl MazeState {
+    fn load_trace(&mut self, trace: Trace) -> Result<usize, JsValue> {
+        let fits = |cell: (usize, usize)| self.maze_grid.contains(cell);
+        if !fits(trace.start) || !fits(trace.target) || !trace.events.iter().all(|event| fits(event.cell())) {
+            return Err(JsValue::from_str("The trace does not fit this maze!"));
+        }
+        self.computer_player = trace.start;
+        self.target = trace.target;
+        self.trace = trace;
+        self.clear_visualization()?;
+        Ok(self.trace.events.len())
+    }
+
+    // what the solver drew for the event; pushes, pops and relaxes are only data
+    fn draw_event(&mut self, event: Event) {
+        match event {
+            Event::Visit((x, y), color) => {
+                let color = self.trace.colors[color].clone();
+                self.color_cell(x, y, color);
+            },
+            Event::Path((x, y)) => self.path_cell(x, y),
+            _ => {},
+        }
+    }
+
+    // the portal and stair arcs of the path drawn in the first `step` events
+    fn draw_trace_jumps(&mut self, step: usize) {
+        let path: Vec<(usize, usize)> = self.trace.events[..step].iter()
+            .filter_map(|event| match event {
+                Event::Path(cell) => Some(*cell),
+                _ => None,
+            })
+            .collect();
+        // reconstructed paths are recorded from the target back, but a jump works both ways
+        self.draw_jumps(&path);
+    }
+}
@@ -0,0 +1,338 @@
+use wasm_bindgen::prelude::*;
+use std::collections::{HashMap, HashSet};
+use crate::maze::MazeState;
+use crate::maze::pathfinding::trace::Event;
+use crate::maze::pathfinding::grid::Grid;
+
+// the colours everything is drawn in, and the heatmap mode that colours the cells a search
+// expands by when it expanded them or how far from the start it found them
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    pub wall: String,
+    pub path: String,       // open floor, "transparent" leaves the page showing through
+    pub frontier: String,   // cells a search has found but not expanded yet
+    pub visited: String,    // cells it has expanded
+    pub final_path: String,
+    pub computer: String,
+    pub human: String,
+    pub target: String,
+    pub waypoint: String,   // tour stops
+    pub filled: String,     // dead ends walled up by dead-end filling
+    pub unknown: String,    // fog of war: cells the explorer knows nothing about,
+    pub known_open: String, // the ones it remembers,
+    pub known_wall: String,
+    pub seen_open: String,  // and the ones in sight
+    pub seen_wall: String,
+    pub portal: String,
+    pub stair: String,
+    pub gate: String,
+    pub patrol: String,
+    pub cluster: String,    // the hierarchical solver's clusters and abstract edges
+    pub entrance: String,
+    pub current: String,    // the cell of the last event when stepping through a trace
+    pub ink: String,        // arrows, and labels on light cells
+    pub light_ink: String,  // labels on dark cells
+}
+
+impl Theme {
+    pub fn light() -> Theme {
+        Theme {
+            wall: "black".to_string(),
+            path: "transparent".to_string(),
+            frontier: "lightcoral".to_string(),
+            visited: "mistyrose".to_string(),
+            final_path: "red".to_string(),
+            computer: "red".to_string(),
+            human: "blue".to_string(),
+            target: "lime".to_string(),
+            waypoint: "orange".to_string(),
+            filled: "gray".to_string(),
+            unknown: "dimgray".to_string(),
+            known_open: "gainsboro".to_string(),
+            known_wall: "#333333".to_string(),
+            seen_open: "white".to_string(),
+            seen_wall: "black".to_string(),
+            portal: "mediumpurple".to_string(),
+            stair: "deepskyblue".to_string(),
+            gate: "slategray".to_string(),
+            patrol: "darkviolet".to_string(),
+            cluster: "steelblue".to_string(),
+            entrance: "gold".to_string(),
+            current: "blue".to_string(),
+            ink: "black".to_string(),
+            light_ink: "white".to_string(),
+        }
+    }
+
+    pub fn dark() -> Theme {
+        Theme {
+            wall: "#d4d4d4".to_string(),
+            path: "#1b1b1f".to_string(),
+            frontier: "#b5544f".to_string(),
+            visited: "#4a2c2c".to_string(),
+            final_path: "#ff5f56".to_string(),
+            computer: "#ff5f56".to_string(),
+            human: "#5b9cff".to_string(),
+            target: "#5af78e".to_string(),
+            waypoint: "#e5a50a".to_string(),
+            filled: "#3a3a40".to_string(),
+            unknown: "#0e0e10".to_string(),
+            known_open: "#2e2e34".to_string(),
+            known_wall: "#8c8c8c".to_string(),
+            seen_open: "#45454f".to_string(),
+            seen_wall: "#d4d4d4".to_string(),
+            portal: "#a78bfa".to_string(),
+            stair: "#38bdf8".to_string(),
+            gate: "#94a3b8".to_string(),
+            patrol: "#c084fc".to_string(),
+            cluster: "#4f83b3".to_string(),
+            entrance: "#e5c07b".to_string(),
+            current: "#5b9cff".to_string(),
+            ink: "#111111".to_string(),
+            light_ink: "#f5f5f5".to_string(),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Theme> {
+        match name {
+            "Light" => Some(Theme::light()),
+            "Dark" => Some(Theme::dark()),
+            _ => None,
+        }
+    }
+
+    fn role(&mut self, role: &str) -> Option<&mut String> {
+        match role {
+            "wall" => Some(&mut self.wall),
+            "path" => Some(&mut self.path),
+            "frontier" => Some(&mut self.frontier),
+            "visited" => Some(&mut self.visited),
+            "final_path" => Some(&mut self.final_path),
+            "computer" => Some(&mut self.computer),
+            "human" => Some(&mut self.human),
+            "target" => Some(&mut self.target),
+            "waypoint" => Some(&mut self.waypoint),
+            "filled" => Some(&mut self.filled),
+            "unknown" => Some(&mut self.unknown),
+            "known_open" => Some(&mut self.known_open),
+            "known_wall" => Some(&mut self.known_wall),
+            "seen_open" => Some(&mut self.seen_open),
+            "seen_wall" => Some(&mut self.seen_wall),
+            "portal" => Some(&mut self.portal),
+            "stair" => Some(&mut self.stair),
+            "gate" => Some(&mut self.gate),
+            "patrol" => Some(&mut self.patrol),
+            "cluster" => Some(&mut self.cluster),
+            "entrance" => Some(&mut self.entrance),
+            "current" => Some(&mut self.current),
+            "ink" => Some(&mut self.ink),
+            "light_ink" => Some(&mut self.light_ink),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::light()
+    }
+}
+
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum HeatMode {
+    #[default]
+    Off,
+    Order,  // early expansions hot, late ones cold
+    GScore, // cells found close to the start hot, far ones cold
+}
+
+impl HeatMode {
+    pub fn from_name(name: &str) -> Option<HeatMode> {
+        match name {
+            "Off" => Some(HeatMode::Off),
+            "Order" => Some(HeatMode::Order),
+            "GScore" => Some(HeatMode::GScore),
+            _ => None,
+        }
+    }
+}
+
+
+// what the heatmap needs from the current search: the cells in the order they were
+// expanded, and the cost at which each was last found
+#[derive(Clone, Debug, Default)]
+pub struct Heat {
+    pub order: Vec<(usize, usize)>,
+    pub g: HashMap<(usize, usize), usize>,
+    open_cells: usize, // the scale while the search runs, before the real one is known
+}
+
+impl Heat {
+    pub fn new(grid: &Grid) -> Heat {
+        Heat { open_cells: grid.open_cells().count(), ..Heat::default() }
+    }
+}
+
+
+#[wasm_bindgen]
+impl MazeState {
+
+    // "Light" or "Dark", redrawn straight away
+    pub fn set_theme(&mut self, name: &str) -> Result<(), JsValue> {
+        self.theme = Theme::from_name(name).ok_or_else(|| JsValue::from_str("Unknown theme!"))?;
+        self.clear_visualization()
+    }
+
+    // changes one colour of the theme to any css colour: "wall", "path", "frontier", "visited",
+    // "final_path", "computer", "human", "target", "waypoint", "filled", the fog of war's
+    // "unknown", "known_open", "known_wall", "seen_open" and "seen_wall", "portal", "stair",
+    // "gate", "patrol", "cluster", "entrance", "current", "ink" or "light_ink"
+    pub fn set_theme_color(&mut self, role: &str, color: &str) -> Result<(), JsValue> {
+        *self.theme.role(role).ok_or_else(|| JsValue::from_str("Unknown theme colour!"))? = color.to_string();
+        self.clear_visualization()
+    }
+
+    // "Off", "Order" or "GScore": what the cells expanded by BFS, DFS, BestFirst and AStar
+    // are coloured by, instead of the visited colour
+    pub fn set_heatmap_mode(&mut self, mode: &str) -> Result<(), JsValue> {
+        self.heat_mode = HeatMode::from_name(mode).ok_or_else(|| JsValue::from_str("Unknown heatmap mode!"))?;
+        Ok(())
+    }
+}
+
+
+impl MazeState {
+    // a search found `cell` from `parent` for `cost` more
+    pub(super) fn frontier_cell(&mut self, cell: (usize, usize), parent: (usize, usize), cost: usize) {
+        self.trace.record(Event::Push(cell));
+        // only the g score heatmap needs them, and hashing every push slows big searches
+        if self.heat_mode == HeatMode::GScore {
+            let g = self.heat.g.get(&parent).copied().unwrap_or(0) + cost;
+            self.heat.g.insert(cell, g);
+        }
+        self.color_cell(cell.0, cell.1, self.theme.frontier.clone());
+    }
+
+    // a search took `cell` off its frontier to expand it
+    pub(super) fn expand_cell(&mut self, cell: (usize, usize)) {
+        self.trace.record(Event::Pop(cell));
+        if self.heat_mode != HeatMode::Off {
+            self.heat.order.push(cell);
+        }
+        if cell == self.computer_player {
+            return;
+        }
+        // the final scale is only known once the search is over, see finish_heatmap
+        let color = match self.heat_mode {
+            HeatMode::Off => self.theme.visited.clone(),
+            HeatMode::Order => heat_color(self.heat.order.len() - 1, self.heat.open_cells),
+            HeatMode::GScore => heat_color(self.heat.g.get(&cell).copied().unwrap_or(0), self.heat.open_cells),
+        };
+        self.color_cell(cell.0, cell.1, color);
+    }
+
+    // repaints the expanded cells on the scale of the finished search, leaving the path on top
+    pub(super) fn finish_heatmap(&mut self) {
+        if self.heat_mode == HeatMode::Off || self.heat.order.is_empty() {
+            return;
+        }
+        let on_path: HashSet<(usize, usize)> = self.trace.events.iter()
+            .filter_map(|event| match event {
+                Event::Path(cell) => Some(*cell),
+                _ => None,
+            })
+            .collect();
+        let g_of = |cell: &(usize, usize)| self.heat.g.get(cell).copied().unwrap_or(0);
+        let scale = match self.heat_mode {
+            HeatMode::GScore => self.heat.order.iter().map(g_of).max().unwrap_or(0),
+            _ => self.heat.order.len() - 1,
+        };
+        let repaint: Vec<((usize, usize), String)> = self.heat.order.iter().enumerate()
+            .filter(|&(_, cell)| !on_path.contains(cell) && *cell != self.computer_player && *cell != self.target)
+            .map(|(index, cell)| {
+                let value = if self.heat_mode == HeatMode::GScore { g_of(cell) } else { index };
+                (*cell, heat_color(value, scale))
+            })
+            .collect();
+        for ((x, y), color) in repaint {
+            self.fill_cell(x, y, &color);
+        }
+    }
+}
+
+
+// red for 0 through to blue for `scale`, like the distance heatmap
+fn heat_color(value: usize, scale: usize) -> String {
+    let hue = 240.0 * value.min(scale) as f64 / scale.max(1) as f64;
+    format!("hsl({:.0}, 85%, 55%)", hue)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maze::framebuffer::parse_color;
+
+    const ROLES: [&str; 24] = [
+        "wall", "path", "frontier", "visited", "final_path", "computer", "human", "target", "waypoint",
+        "filled", "unknown", "known_open", "known_wall", "seen_open", "seen_wall", "portal", "stair",
+        "gate", "patrol", "cluster", "entrance", "current", "ink", "light_ink",
+    ];
+
+    #[test]
+    fn every_theme_colour_parses() {
+        // so the framebuffer can draw every one of them without falling back to the canvas
+        for mut theme in [Theme::light(), Theme::dark()] {
+            for role in ROLES {
+                let color = theme.role(role).expect("a known role").clone();
+                assert!(parse_color(&color).is_some(), "{} is {}", role, color);
+            }
+        }
+        assert_eq!(parse_color("black"), Some([0, 0, 0, 255]));
+        assert_eq!(parse_color("transparent"), Some([0, 0, 0, 0]));
+        assert_eq!(parse_color("#333333"), Some([51, 51, 51, 255]));
+        assert_eq!(parse_color("#5af78e"), Some([0x5a, 0xf7, 0x8e, 255]));
+    }
+
+    #[test]
+    fn parse_color_reads_the_css_forms() {
+        assert_eq!(parse_color("#fa0"), Some([255, 170, 0, 255]));
+        assert_eq!(parse_color(" #FFAA00 "), Some([255, 170, 0, 255]));
+        assert_eq!(parse_color("rgb(1, 2, 300)"), Some([1, 2, 255, 255]));
+        assert_eq!(parse_color("hsl(0, 100%, 50%)"), Some([255, 0, 0, 255]));
+        assert_eq!(parse_color("hsl(120, 100%, 25%)"), Some([0, 128, 0, 255]));
+        assert_eq!(parse_color("hsl(240, 100%, 50%)"), Some([0, 0, 255, 255]));
+        for bad in ["#ff", "#ggg", "rgb(1, 2)", "rgb(1, 2, 3", "hsl(x, 1%, 1%)", "chartreuse", ""] {
+            assert_eq!(parse_color(bad), None, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn every_role_is_a_distinct_colour_to_set() {
+        let mut theme = Theme::light();
+        for (index, role) in ROLES.iter().enumerate() {
+            *theme.role(role).expect("a known role") = format!("#{:06x}", index);
+        }
+        // no two names lead to the same field, or the earlier one would read back the later colour
+        for (index, role) in ROLES.iter().enumerate() {
+            assert_eq!(theme.role(role).cloned(), Some(format!("#{:06x}", index)));
+        }
+        assert_ne!(theme, Theme::light());
+        assert!(theme.role("background").is_none());
+    }
+
+    #[test]
+    fn heat_runs_from_red_to_blue_and_parses() {
+        assert_eq!(heat_color(0, 10), "hsl(0, 85%, 55%)");
+        assert_eq!(heat_color(10, 10), "hsl(240, 85%, 55%)");
+        assert_eq!(heat_color(50, 10), "hsl(240, 85%, 55%)");
+        // a search that found nothing past the start has all its cells hot
+        assert_eq!(heat_color(3, 0), "hsl(0, 85%, 55%)");
+        for value in 0..=10 {
+            assert!(parse_color(&heat_color(value, 10)).is_some());
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use wasm_bindgen::prelude::*;
+use crate::maze::MazeState;
+use crate::maze::pathfinding::grid_step;
+use crate::maze::pathfinding::topology::Topology;
+
+// square, hex or triangle cells: switching the tiling, and the drawing helpers that
+// follow the cell shape instead of assuming squares
+
+
+#[wasm_bindgen]
+impl MazeState {
+
+    // reads the same maze text as "Square", "Hex" or "Triangle" cells and redraws it
+    pub fn set_topology(&mut self, name: &str) -> Result<(), JsValue> {
+        self.links.topology = Topology::from_name(name)
+            .ok_or_else(|| JsValue::from_str("Unknown topology!"))?;
+        self.links.changed();
+        self.clear_visualization()
+    }
+
+    pub fn get_topology(&self) -> String {
+        format!("{:?}", self.links.topology)
+    }
+
+    // [width, height] the canvas needs to show the whole maze in the current tiling
+    pub fn get_canvas_size(&self) -> Vec<f64> {
+        let (width, height) = self.links.topology.canvas_size(self.maze_grid.width(), self.maze_grid.height(), self.block_size);
+        vec![width.ceil(), height.ceil()]
+    }
+}
+
+
+impl MazeState {
+    // starts a canvas path along the outline of the cell
+    pub(super) fn trace_cell(&mut self, x: usize, y: usize) {
+        let corners = self.links.topology.corners((x, y), self.block_size);
+        self.pen().begin_path();
+        self.pen().move_to(corners[0].0, corners[0].1);
+        for &(corner_x, corner_y) in corners.iter().skip(1) {
+            self.pen().line_to(corner_x, corner_y);
+        }
+        self.pen().close_path();
+    }
+
+    // the cell's outline in the current stroke style
+    pub(super) fn outline_cell(&mut self, x: usize, y: usize) {
+        if self.links.topology == Topology::Square {
+            self.cell_context(x, y).stroke_rect(
+                x as f64 * self.block_size,
+                y as f64 * self.block_size,
+                self.block_size,
+                self.block_size,
+            );
+        } else {
+            self.trace_cell(x, y);
+            self.cell_context(x, y).stroke();
+        }
+    }
+
+    // back to the empty canvas underneath, without touching the cells around it
+    pub(super) fn clear_cell(&mut self, x: usize, y: usize) {
+        if self.buffer_cell(x, y, "transparent", true) {
+            // cleared in the pixel buffer
+        } else if self.links.topology == Topology::Square {
+            self.cell_context(x, y).clear_rect(
+                x as f64 * self.block_size,
+                y as f64 * self.block_size,
+                self.block_size,
+                self.block_size,
+            );
+        } else {
+            let (centre_x, centre_y) = self.cell_centre(x, y);
+            self.pen().save();
+            self.trace_cell(x, y);
+            self.pen().clip();
+            self.cell_context(x, y).clear_rect(centre_x - self.block_size, centre_y - self.block_size, 2.0 * self.block_size, 2.0 * self.block_size);
+            self.pen().restore();
+        }
+        // a dark theme paints the floor, so cleared floor goes back to its colour
+        if self.theme.path != "transparent" {
+            self.fill_cell(x, y, &self.theme.path.clone());
+        }
+    }
+
+    pub(super) fn cell_centre(&self, x: usize, y: usize) -> (f64, f64) {
+        self.links.topology.centre((x, y), self.block_size)
+    }
+
+    // whether two cells share a side, as opposed to being linked by a portal
+    pub(super) fn is_adjacent(&self, a: (usize, usize), b: (usize, usize)) -> bool {
+        self.links.topology.directions(a).iter().any(|&dir| grid_step(&self.maze_grid, a, dir) == Some(b))
+    }
+}
@@ -0,0 +1,293 @@
+use wasm_bindgen::prelude::*;
+use crate::maze::MazeState;
+use crate::maze::fields::{distance_field, flow_field};
+use crate::maze::pathfinding::UNREACHABLE;
+
+// visiting several waypoints in the shortest order: a travelling salesman problem over the
+// maze distances between the stops. small tours are solved exactly with held-karp bitmask dp,
+// larger ones with nearest neighbour followed by 2-opt
+
+// beyond this many waypoints the 2^n table of held-karp gets too big
+const EXACT_LIMIT: usize = 12;
+
+
+// held-karp over the stops, stop 0 is the fixed start; with `round_trip` the tour ends back there.
+// returns the visiting order of the stops, starting with 0
+pub fn held_karp(dist: &[Vec<usize>], round_trip: bool) -> Vec<usize> {
+    let n = dist.len();
+    if n <= 1 {
+        return (0..n).collect();
+    }
+    let others = n - 1; // stop i (1..n) is bit i - 1
+    let full = 1usize << others;
+    // best[mask][last]: cheapest way to leave the start, visit exactly `mask` and end at stop `last + 1`
+    let mut best = vec![vec![usize::MAX; others]; full];
+    let mut came_from = vec![vec![usize::MAX; others]; full];
+    for last in 0..others {
+        best[1 << last][last] = dist[0][last + 1];
+    }
+
+    for mask in 1..full {
+        for last in 0..others {
+            let cost = best[mask][last];
+            if mask & (1 << last) == 0 || cost == usize::MAX {
+                continue;
+            }
+            for next in 0..others {
+                if mask & (1 << next) != 0 {
+                    continue;
+                }
+                let next_mask = mask | (1 << next);
+                let next_cost = cost.saturating_add(dist[last + 1][next + 1]);
+                if next_cost < best[next_mask][next] {
+                    best[next_mask][next] = next_cost;
+                    came_from[next_mask][next] = last;
+                }
+            }
+        }
+    }
+
+    let closing = |last: usize| if round_trip { dist[last + 1][0] } else { 0 };
+    let mut last = (0..others)
+        .min_by_key(|&last| best[full - 1][last].saturating_add(closing(last)))
+        .unwrap_or(0);
+
+    let mut order = Vec::with_capacity(n);
+    let mut mask = full - 1;
+    while last != usize::MAX {
+        order.push(last + 1);
+        let prev = came_from[mask][last];
+        mask &= !(1 << last);
+        last = prev;
+    }
+    order.push(0);
+    order.reverse();
+    order
+}
+
+
+// greedy tour from the start: always walk to the closest stop not visited yet
+pub fn nearest_neighbour(dist: &[Vec<usize>]) -> Vec<usize> {
+    let n = dist.len();
+    let mut visited = vec![false; n];
+    let mut order = vec![0];
+    visited[0] = true;
+    for _ in 1..n {
+        let last = *order.last().unwrap_or(&0);
+        let Some(next) = (0..n).filter(|&stop| !visited[stop]).min_by_key(|&stop| dist[last][stop]) else { break };
+        visited[next] = true;
+        order.push(next);
+    }
+    order
+}
+
+
+// 2-opt: keep reversing a stretch of the tour while that makes it shorter.
+// the start never moves, and a one way tour has no closing leg to consider
+pub fn two_opt(dist: &[Vec<usize>], order: &mut [usize], round_trip: bool) {
+    let n = order.len();
+    let leg = |a: usize, b: usize| dist[a][b];
+    let mut improved = true;
+    while improved {
+        improved = false;
+        for i in 1..n.saturating_sub(1) {
+            for j in (i + 1)..n {
+                // reversing order[i..=j] swaps the legs (i-1, i) and (j, j+1)
+                let before = leg(order[i - 1], order[i]);
+                let after = leg(order[i - 1], order[j]);
+                let (old_end, new_end) = if j + 1 < n {
+                    (leg(order[j], order[j + 1]), leg(order[i], order[j + 1]))
+                } else if round_trip {
+                    (leg(order[j], order[0]), leg(order[i], order[0]))
+                } else {
+                    (0, 0)
+                };
+                if after + new_end < before + old_end {
+                    order[i..=j].reverse();
+                    improved = true;
+                }
+            }
+        }
+    }
+}
+
+
+#[wasm_bindgen]
+impl MazeState {
+
+    // adds a waypoint for solve_tour, walls, players and repeats are refused
+    pub fn add_waypoint(&mut self, x: usize, y: usize) -> Result<(), JsValue> {
+        if !self.maze_grid.is_path((x, y)) {
+            return Err(JsValue::from_str("Waypoint must be on an open cell!"));
+        }
+        if (x, y) == self.computer_player || self.waypoints.contains(&(x, y)) {
+            return Err(JsValue::from_str("Waypoint already taken!"));
+        }
+        self.waypoints.push((x, y));
+        self.fill_cell(x, y, &self.theme.waypoint.clone());
+        Ok(())
+    }
+
+    pub fn clear_waypoints(&mut self) -> Result<(), JsValue> {
+        self.waypoints.clear();
+        self.clear_visualization()
+    }
+
+    // the waypoints as flat [x0, y0, x1, y1, ...]
+    pub fn get_waypoints(&self) -> Vec<usize> {
+        self.waypoints.iter().flat_map(|&(x, y)| [x, y]).collect()
+    }
+
+    // finds the shortest order to visit every waypoint from the computer player,
+    // optionally coming back at the end, then draws the route with numbered stops
+    pub async fn solve_tour(&mut self, round_trip: bool, delay_ms: i32) -> Result<JsValue, JsValue> {
+        if self.waypoints.is_empty() {
+            return Err(JsValue::from_str("No waypoints placed!"));
+        }
+        self.clear_visualization()?;
+
+        let mut stops = vec![self.computer_player];
+        stops.extend(self.waypoints.iter().copied());
+
+        // one dijkstra field per stop gives the cost from all the others to it (portals included),
+        // and its flow field the route there
+        let mut to_stop = Vec::with_capacity(stops.len());
+        for &stop in stops.iter() {
+            let distance = distance_field(&self.maze_grid, &self.links, stop);
+            if stops.iter().any(|&cell| distance[self.maze_grid.index(cell)] == UNREACHABLE) {
+                return Err(JsValue::from_str("Waypoint not reachable!"));
+            }
+            let flow = flow_field(&self.maze_grid, &self.links, &distance);
+            to_stop.push((distance, flow));
+        }
+        let dist: Vec<Vec<usize>> = stops.iter()
+            .map(|&cell| to_stop.iter().map(|(distance, _)| distance[self.maze_grid.index(cell)] as usize).collect())
+            .collect();
+
+        let (order, method) = if self.waypoints.len() <= EXACT_LIMIT {
+            (held_karp(&dist, round_trip), "held-karp")
+        } else {
+            let mut order = nearest_neighbour(&dist);
+            two_opt(&dist, &mut order, round_trip);
+            (order, "nearest neighbour + 2-opt")
+        };
+
+        let mut legs: Vec<usize> = order.clone();
+        if round_trip {
+            legs.push(0);
+        }
+        let mut length = 0;
+        self.last_path = vec![stops[legs[0]]];
+        for pair in legs.windows(2) {
+            let (from, to) = (stops[pair[0]], stops[pair[1]]);
+            length += dist[pair[0]][pair[1]];
+            let flow = &to_stop[pair[1]].1;
+            let mut leg = vec![from];
+            while let Some(next) = flow[self.maze_grid.index(leg[leg.len() - 1])] {
+                leg.push(self.maze_grid.cell(next));
+            }
+            debug_assert_eq!(leg.last(), Some(&to));
+            self.draw_jumps(&leg);
+            self.last_path.extend_from_slice(&leg[1..]);
+            for &(x, y) in leg.iter() {
+                if !stops.contains(&(x, y)) {
+                    self.path_cell(x, y);
+                    if delay_ms > 0 {
+                        crate::maze::sleep(delay_ms).await?;
+                    }
+                }
+            }
+        }
+        self.draw_tour_stops(&stops, &order);
+
+        Ok(JsValue::from_str(&format!("Path found! --> tour of {} waypoints, length {} ({})", self.waypoints.len(), length, method)))
+    }
+}
+
+
+impl MazeState {
+    // the waypoints in visiting order, each with its number written on top
+    fn draw_tour_stops(&mut self, stops: &[(usize, usize)], order: &[usize]) {
+        for (number, &stop) in order.iter().enumerate().skip(1) {
+            let (x, y) = stops[stop];
+            self.label_cell(x, y, &self.theme.waypoint.clone(), &number.to_string(), &self.theme.ink.clone());
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+
+    fn random_distances(n: usize, rng: &mut impl Rng) -> Vec<Vec<usize>> {
+        (0..n).map(|a| (0..n).map(|b| if a == b { 0 } else { rng.gen_range(1..50) }).collect()).collect()
+    }
+
+    fn length(dist: &[Vec<usize>], order: &[usize], round_trip: bool) -> usize {
+        let legs: usize = order.windows(2).map(|leg| dist[leg[0]][leg[1]]).sum();
+        let closing = if round_trip && order.len() > 1 { dist[order[order.len() - 1]][order[0]] } else { 0 };
+        legs + closing
+    }
+
+    // every order of the stops after the start, tried one by one
+    fn brute_force(dist: &[Vec<usize>], round_trip: bool) -> usize {
+        fn visit(dist: &[Vec<usize>], order: &mut Vec<usize>, round_trip: bool, best: &mut usize) {
+            if order.len() == dist.len() {
+                *best = (*best).min(length(dist, order, round_trip));
+                return;
+            }
+            for stop in 1..dist.len() {
+                if !order.contains(&stop) {
+                    order.push(stop);
+                    visit(dist, order, round_trip, best);
+                    order.pop();
+                }
+            }
+        }
+        let mut best = usize::MAX;
+        visit(dist, &mut vec![0], round_trip, &mut best);
+        best
+    }
+
+    fn is_tour(order: &[usize], n: usize) -> bool {
+        let mut sorted = order.to_vec();
+        sorted.sort();
+        order.first() == Some(&0) && sorted == (0..n).collect::<Vec<usize>>()
+    }
+
+    #[test]
+    fn held_karp_matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for n in 1..=7 {
+            for _ in 0..20 {
+                let dist = random_distances(n, &mut rng);
+                for round_trip in [false, true] {
+                    let order = held_karp(&dist, round_trip);
+                    assert!(is_tour(&order, n), "{:?} is not a tour of {} stops", order, n);
+                    assert_eq!(length(&dist, &order, round_trip), brute_force(&dist, round_trip), "{:?}", dist);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn two_opt_never_makes_a_tour_longer() {
+        let mut rng = StdRng::seed_from_u64(8);
+        for n in 2..=10 {
+            let dist = random_distances(n, &mut rng);
+            // 2-opt assumes legs cost the same both ways
+            let dist: Vec<Vec<usize>> = (0..n).map(|a| (0..n).map(|b| dist[a.min(b)][a.max(b)]).collect()).collect();
+            for round_trip in [false, true] {
+                let mut order = nearest_neighbour(&dist);
+                assert!(is_tour(&order, n));
+                let before = length(&dist, &order, round_trip);
+                two_opt(&dist, &mut order, round_trip);
+                assert!(is_tour(&order, n));
+                assert!(length(&dist, &order, round_trip) <= before);
+            }
+        }
+    }
+}
@@ -0,0 +1,271 @@
+use rand::Rng;
+use rand::seq::SliceRandom;
+use std::collections::HashSet;
+use wasm_bindgen::prelude::*;
+use crate::maze::{CellType, MazeState};
+use crate::maze::pathfinding::grid_step;
+use crate::maze::pathfinding::grid::Grid;
+use crate::maze::pathfinding::topology::Topology;
+
+// grid transforms that turn a perfect maze (exactly one route between any two cells)
+// into a multi-path one, so that the solvers actually have something to disagree about.
+// directions are indices into the topology's table for the cell, so the same index from the
+// next cell carries on in the same direction
+
+
+// helper function to step from a cell in direction `index`, None if it leaves the grid
+fn step(grid: &Grid, topology: Topology, cell: (usize, usize), index: usize) -> Option<(usize, usize)> {
+    let dir = *topology.directions(cell).get(index)?;
+    grid_step(grid, cell, dir)
+}
+
+fn open_neighbour_count(grid: &Grid, topology: Topology, cell: (usize, usize)) -> usize {
+    topology.directions(cell).iter()
+        .filter_map(|&dir| grid_step(grid, cell, dir))
+        .filter(|&n| grid.is_path(n))
+        .count()
+}
+
+// border walls are never removed, otherwise the maze would leak off the canvas
+fn is_inner(grid: &Grid, cell: (usize, usize)) -> bool {
+    cell.1 > 0 && cell.1 + 1 < grid.height() && cell.0 > 0 && cell.0 + 1 < grid.width()
+}
+
+
+// a dead end is an open cell with exactly one open neighbour
+pub fn dead_ends(grid: &Grid, topology: Topology) -> Vec<(usize, usize)> {
+    grid.open_cells().filter(|&cell| open_neighbour_count(grid, topology, cell) == 1).collect()
+}
+
+
+// removes `percent` % of the dead ends by knocking through one of their walls into
+// the corridor behind it, preferring walls that also join another dead end;
+// `protected` cells are never knocked through
+pub fn braid(grid: &mut Grid, topology: Topology, percent: f64, protected: &HashSet<(usize, usize)>, rng: &mut impl Rng) -> usize {
+    let mut candidates = dead_ends(grid, topology);
+    candidates.shuffle(rng);
+    let quota = ((candidates.len() as f64) * percent.clamp(0.0, 100.0) / 100.0).ceil() as usize;
+
+    let mut removed = 0;
+    for cell in candidates.into_iter().take(quota) {
+        // an earlier knock-through may already have opened this one up
+        if open_neighbour_count(grid, topology, cell) != 1 {
+            continue;
+        }
+
+        let mut walls = Vec::new();
+        let mut preferred = Vec::new();
+        for dir in 0..topology.directions(cell).len() {
+            let Some(wall) = step(grid, topology, cell, dir) else { continue };
+            if grid.is_path(wall) || !is_inner(grid, wall) || protected.contains(&wall) {
+                continue;
+            }
+            // a triangle turns over from one cell to the next, so across its flat side
+            // the same index leads straight back
+            if let Some(beyond) = step(grid, topology, wall, dir) && beyond != cell && grid.is_path(beyond) {
+                if open_neighbour_count(grid, topology, beyond) == 1 {
+                    preferred.push(wall);
+                }
+                walls.push(wall);
+            }
+        }
+
+        let pool = if preferred.is_empty() { &walls } else { &preferred };
+        if let Some(&wall) = pool.choose(rng) {
+            grid.set(wall, CellType::Path);
+            removed += 1;
+        }
+    }
+    removed
+}
+
+
+// trims dead-end tails by walling them up, one cell per pass;
+// `protected` cells are never filled in
+pub fn sparsify(grid: &mut Grid, topology: Topology, passes: usize, protected: &HashSet<(usize, usize)>) -> usize {
+    let mut filled = 0;
+    for _ in 0..passes {
+        let tails: Vec<(usize, usize)> = dead_ends(grid, topology)
+            .into_iter()
+            .filter(|cell| !protected.contains(cell))
+            .collect();
+        if tails.is_empty() {
+            break;
+        }
+        for tail in tails {
+            grid.set(tail, CellType::Wall);
+            filled += 1;
+        }
+    }
+    filled
+}
+
+
+// opens up to `count` random inner walls that sit between two open cells
+// on opposite sides, which closes a loop in the corridor they separate;
+// `protected` walls are left alone
+pub fn add_loops(grid: &mut Grid, topology: Topology, count: usize, protected: &HashSet<(usize, usize)>, rng: &mut impl Rng) -> usize {
+    let mut walls: Vec<(usize, usize)> = (0..grid.cell_count())
+        .filter(|&index| !grid.is_open(index))
+        .map(|index| grid.cell(index))
+        .filter(|&cell| is_inner(grid, cell) && !protected.contains(&cell))
+        .collect();
+    walls.shuffle(rng);
+
+    let mut opened = 0;
+    for wall in walls {
+        if opened == count {
+            break;
+        }
+        let separates = |&&(a, b): &&(usize, usize)| {
+            matches!((step(grid, topology, wall, a), step(grid, topology, wall, b)),
+                (Some(first), Some(second)) if grid.is_path(first) && grid.is_path(second))
+        };
+        let axes = topology.axes().iter().filter(separates).count();
+        // exactly one axis, so we punch through a wall and never carve out a junction blob
+        if axes == 1 {
+            grid.set(wall, CellType::Path);
+            opened += 1;
+        }
+    }
+    opened
+}
+
+
+#[wasm_bindgen]
+impl MazeState {
+
+    // removes `percent` % of the dead ends, returns how many walls were knocked through
+    pub fn braid(&mut self, percent: f64) -> Result<usize, JsValue> {
+        let protected = self.fixed_cells();
+        let removed = braid(&mut self.original_maze_grid, self.links.topology, percent, &protected, &mut rand::thread_rng());
+        self.clear_visualization()?;
+        Ok(removed)
+    }
+
+    // trims `passes` cells off every dead-end tail, returns how many cells were filled
+    pub fn sparsify(&mut self, passes: usize) -> Result<usize, JsValue> {
+        let protected = self.fixed_cells();
+        let filled = sparsify(&mut self.original_maze_grid, self.links.topology, passes, &protected);
+        self.clear_visualization()?;
+        Ok(filled)
+    }
+
+    // opens up to `count` extra walls to create loops, returns how many were opened
+    pub fn add_loops(&mut self, count: usize) -> Result<usize, JsValue> {
+        let protected = self.fixed_cells();
+        let opened = add_loops(&mut self.original_maze_grid, self.links.topology, count, &protected, &mut rand::thread_rng());
+        self.clear_visualization()?;
+        Ok(opened)
+    }
+}
+
+
+impl MazeState {
+    // cells the transforms and evolve_maze leave as they are: the players, waypoints and agents,
+    // everything written into the maze text as a symbol, what the gates and patrols stand on,
+    // and between floors the all-wall columns that keep them apart. filling any of them in would
+    // leave a door that cannot be opened or a floor that cannot be reached
+    pub(super) fn fixed_cells(&self) -> HashSet<(usize, usize)> {
+        let mut fixed: HashSet<(usize, usize)> = [self.computer_player, self.target, self.human_player].into_iter().collect();
+        fixed.extend(self.waypoints.iter().copied());
+        fixed.extend(self.agents.iter().flat_map(|agent| [agent.start, agent.goal]));
+        fixed.extend(self.portal_labels.keys().copied());
+        fixed.extend(self.locks.keys.keys().copied());
+        fixed.extend(self.locks.doors.keys().copied());
+        fixed.extend(self.links.floors.stairs.keys().copied());
+        fixed.extend(self.schedule.gates.iter().map(|gate| gate.cell));
+        fixed.extend(self.schedule.patrols.iter().flat_map(|patrol| patrol.route.iter().copied()));
+
+        if self.links.floors.count > 1 {
+            let grid = &self.original_maze_grid;
+            for x in 0..grid.width() {
+                if (0..grid.height()).all(|y| !grid.is_path((x, y))) {
+                    fixed.extend((0..grid.height()).map(|y| (x, y)));
+                }
+            }
+        }
+        fixed
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maze::pathfinding::Links;
+    use crate::maze::pathfinding::bfs;
+    use crate::maze::pathfinding::generate::generate;
+    use crate::maze::pathfinding::grid::Scratch;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    fn perfect_maze(seed: u64) -> Grid {
+        generate(21, 15, 0, &mut StdRng::seed_from_u64(seed))
+    }
+
+    fn border_is_wall(grid: &Grid) -> bool {
+        (0..grid.height()).all(|y| (0..grid.width()).all(|x| is_inner(grid, (x, y)) || !grid.is_path((x, y))))
+    }
+
+    #[test]
+    fn braid_removes_dead_ends_by_opening_inner_walls() {
+        for seed in 0..10 {
+            let mut grid = perfect_maze(seed);
+            let before = dead_ends(&grid, Topology::Square).len();
+            let open_before = grid.open_cells().count();
+
+            let removed = braid(&mut grid, Topology::Square, 100.0, &HashSet::new(), &mut StdRng::seed_from_u64(seed));
+            assert!(removed > 0);
+            assert_eq!(grid.open_cells().count(), open_before + removed);
+            assert!(dead_ends(&grid, Topology::Square).len() < before);
+            assert!(border_is_wall(&grid));
+        }
+    }
+
+    #[test]
+    fn braid_of_nothing_changes_nothing() {
+        let mut grid = perfect_maze(1);
+        let original = grid.clone();
+        assert_eq!(braid(&mut grid, Topology::Square, 0.0, &HashSet::new(), &mut StdRng::seed_from_u64(1)), 0);
+        assert_eq!(grid, original);
+    }
+
+    #[test]
+    fn braid_leaves_protected_walls_alone() {
+        let mut grid = perfect_maze(2);
+        let protected: HashSet<(usize, usize)> = (0..grid.cell_count())
+            .map(|index| grid.cell(index))
+            .filter(|&cell| !grid.is_path(cell))
+            .collect();
+        assert_eq!(braid(&mut grid, Topology::Square, 100.0, &protected, &mut StdRng::seed_from_u64(2)), 0);
+    }
+
+    // in a perfect maze every dead end is off the one route between the two protected
+    // cells, so sparsifying for long enough leaves just that route
+    #[test]
+    fn sparsify_down_to_the_route_between_protected_cells() {
+        for seed in 0..10 {
+            let mut grid = perfect_maze(seed);
+            let (start, target) = ((1, 1), (grid.width() - 2, grid.height() - 2));
+            let links = Links::default();
+            let mut route = bfs::shortest_path(&grid, &links, &mut Scratch::default(), start, target).expect("a perfect maze joins every two cells");
+            let open_before = grid.open_cells().count();
+
+            let filled = sparsify(&mut grid, Topology::Square, usize::MAX, &[start, target].into_iter().collect());
+            assert_eq!(filled, open_before - route.len());
+            let mut left: Vec<(usize, usize)> = grid.open_cells().collect();
+            left.sort();
+            route.sort();
+            assert_eq!(left, route);
+        }
+    }
+
+    #[test]
+    fn sparsify_fills_one_cell_of_each_tail_per_pass() {
+        let mut grid = perfect_maze(3);
+        let tails = dead_ends(&grid, Topology::Square);
+        assert_eq!(sparsify(&mut grid, Topology::Square, 1, &HashSet::new()), tails.len());
+        assert!(tails.iter().all(|&cell| !grid.is_path(cell)));
+    }
+}
@@ -0,0 +1,174 @@
+use wasm_bindgen::prelude::*;
+use web_sys::HtmlCanvasElement;
+use crate::maze::MazeState;
+
+// which part of the maze the canvas shows: everything is still drawn in maze coordinates
+// (block_size per cell), and the canvas transform scales that by `zoom` and shifts it so
+// `origin` is at the top left corner. a canvas keeps no record of what was drawn, so
+// zooming and panning redraw the maze and the players but not an earlier search
+
+const MIN_ZOOM: f64 = 0.1;
+const MAX_ZOOM: f64 = 32.0;
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    pub zoom: f64,
+    pub origin: (f64, f64), // the maze point at the top left corner of the canvas
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Viewport { zoom: 1.0, origin: (0.0, 0.0) }
+    }
+}
+
+impl Viewport {
+    pub fn to_maze(self, (px, py): (f64, f64)) -> (f64, f64) {
+        (self.origin.0 + px / self.zoom, self.origin.1 + py / self.zoom)
+    }
+
+    pub fn to_canvas(self, (mx, my): (f64, f64)) -> (f64, f64) {
+        ((mx - self.origin.0) * self.zoom, (my - self.origin.1) * self.zoom)
+    }
+
+    // the view zoomed by `factor` (clamped to the zoom limits) around a canvas pixel,
+    // which stays over the same maze point
+    pub fn zoomed_at(self, pixel: (f64, f64), factor: f64) -> Viewport {
+        let (mx, my) = self.to_maze(pixel);
+        let zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        Viewport { zoom, origin: (mx - pixel.0 / zoom, my - pixel.1 / zoom) }
+    }
+}
+
+
+#[wasm_bindgen]
+impl MazeState {
+
+    // the [x, y] of the cell under a canvas pixel, or [] when there is no cell there
+    pub fn cell_at(&self, pixel_x: f64, pixel_y: f64) -> Vec<usize> {
+        let point = self.view.to_maze((pixel_x, pixel_y));
+        match self.links.topology.cell_at(point, self.block_size) {
+            Some((x, y)) if self.maze_grid.contains((x, y)) => vec![x, y],
+            _ => Vec::new(),
+        }
+    }
+
+    // zooms in (factor above 1) or out around a canvas pixel, which stays over the same spot
+    pub fn zoom_at(&mut self, pixel_x: f64, pixel_y: f64, factor: f64) -> Result<(), JsValue> {
+        if !(factor > 0.0 && factor.is_finite()) {
+            return Err(JsValue::from_str("Invalid zoom factor!"));
+        }
+        self.view = self.view.zoomed_at((pixel_x, pixel_y), factor);
+        self.clear_visualization()
+    }
+
+    // moves the maze by a number of canvas pixels, as when it is dragged
+    pub fn pan_by(&mut self, dx: f64, dy: f64) -> Result<(), JsValue> {
+        self.view.origin = (self.view.origin.0 - dx / self.view.zoom, self.view.origin.1 - dy / self.view.zoom);
+        self.clear_visualization()
+    }
+
+    // the block size that shows the whole maze on the canvas as it is sized now, with the
+    // zoom and pan reset; returns the new block size
+    pub fn fit_to_canvas(&mut self) -> Result<f64, JsValue> {
+        let canvas = self.canvas()?;
+        let (width, height) = self.links.topology.canvas_size(self.maze_grid.width(), self.maze_grid.height(), 1.0);
+        if width <= 0.0 || height <= 0.0 {
+            return Err(JsValue::from_str("The maze is empty!"));
+        }
+        self.block_size = (canvas.width() as f64 / width).min(canvas.height() as f64 / height);
+        self.view = Viewport::default();
+        self.clear_visualization()?;
+        Ok(self.block_size)
+    }
+
+    // [zoom, origin x, origin y]
+    pub fn get_viewport(&self) -> Vec<f64> {
+        vec![self.view.zoom, self.view.origin.0, self.view.origin.1]
+    }
+}
+
+
+impl MazeState {
+    pub(super) fn canvas(&self) -> Result<HtmlCanvasElement, JsValue> {
+        self.canvas_context.canvas()
+            .ok_or_else(|| JsValue::from_str("The context has no canvas!"))?
+            .dyn_into::<HtmlCanvasElement>()
+            .map_err(|_| JsValue::from_str("Not a canvas element!"))
+    }
+
+    // sets the canvas transform to the viewport, everything drawn after this lands in place
+    pub(super) fn apply_viewport(&mut self) {
+        let Viewport { zoom, origin } = self.view;
+        let _ = self.canvas_context.set_transform(zoom, 0.0, 0.0, zoom, -origin.0 * zoom, -origin.1 * zoom);
+    }
+
+    // whether any of the cell can be on the canvas; a cell reaches at most a block size
+    // from its centre in every tiling
+    pub(super) fn is_visible(&self, x: usize, y: usize, (width, height): (f64, f64)) -> bool {
+        let (cx, cy) = self.cell_centre(x, y);
+        let (left, top) = self.view.origin;
+        let (right, bottom) = (left + width / self.view.zoom, top + height / self.view.zoom);
+        cx + self.block_size >= left && cx - self.block_size <= right
+            && cy + self.block_size >= top && cy - self.block_size <= bottom
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maze::pathfinding::topology::Topology;
+
+    const ZOOMS: [f64; 5] = [0.1, 0.5, 1.0, 3.0, 32.0];
+
+    fn close((ax, ay): (f64, f64), (bx, by): (f64, f64)) -> bool {
+        (ax - bx).abs() < 1e-6 && (ay - by).abs() < 1e-6
+    }
+
+    #[test]
+    fn canvas_and_maze_points_round_trip() {
+        for zoom in ZOOMS {
+            let view = Viewport { zoom, origin: (-12.5, 40.0) };
+            for point in [(0.0, 0.0), (17.0, 3.5), (640.0, 480.0)] {
+                assert!(close(view.to_canvas(view.to_maze(point)), point));
+                assert!(close(view.to_maze(view.to_canvas(point)), point));
+            }
+        }
+    }
+
+    #[test]
+    fn zooming_keeps_the_point_under_the_pixel() {
+        let pixel = (123.0, 45.0);
+        let mut view = Viewport::default();
+        for factor in [2.0, 0.25, 1.5, 10.0, 0.01] {
+            let zoomed = view.zoomed_at(pixel, factor);
+            assert!(close(zoomed.to_maze(pixel), view.to_maze(pixel)));
+            assert!((MIN_ZOOM..=MAX_ZOOM).contains(&zoomed.zoom));
+            view = zoomed;
+        }
+        // and zooming back out by the same factor comes back to the same view
+        let there = Viewport::default().zoomed_at(pixel, 4.0).zoomed_at(pixel, 0.25);
+        assert!((there.zoom - 1.0).abs() < 1e-9 && close(there.origin, (0.0, 0.0)));
+    }
+
+    #[test]
+    fn the_cell_under_a_pixel_stays_under_it_through_a_zoom() {
+        let size = 20.0;
+        for topology in [Topology::Square, Topology::Hex, Topology::Triangle] {
+            for zoom in ZOOMS {
+                let view = Viewport { zoom, origin: (5.0, -7.0) };
+                for cell in [(0, 0), (3, 2), (4, 5), (9, 1)] {
+                    // the pixel over the middle of the cell finds the cell again
+                    let pixel = view.to_canvas(topology.centre(cell, size));
+                    assert_eq!(topology.cell_at(view.to_maze(pixel), size), Some(cell), "{:?} at zoom {}", topology, zoom);
+                    for factor in [0.5, 2.0] {
+                        let zoomed = view.zoomed_at(pixel, factor);
+                        assert_eq!(topology.cell_at(zoomed.to_maze(pixel), size), Some(cell));
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use wasm_bindgen::prelude::*;
+use crate::maze::{MazeState, PlayerType};
+
+// the computer player walking the path of the last solve, one cell per step, instead of
+// the whole path appearing at once. the walk can be run to the end with walk_path or
+// driven a step at a time with start_walk and walk_step. only the picture of the player
+// moves: computer_player stays on the start of the path, so the target can still be moved
+// and the next solve starts from the same cell
+
+
+#[derive(Clone, Debug, Default)]
+pub struct Walk {
+    pub path: Vec<(usize, usize)>,
+    pub step: usize,  // the index in path the player is at
+    pub trail: bool,  // leave the final path colour behind, otherwise the floor
+}
+
+impl Walk {
+    // one cell further along the path: the cell left and the cell moved to,
+    // None once the player is at the end
+    pub fn advance(&mut self) -> Option<((usize, usize), (usize, usize))> {
+        if self.step + 1 >= self.path.len() {
+            return None;
+        }
+        self.step += 1;
+        Some((self.path[self.step - 1], self.path[self.step]))
+    }
+
+    // the cell the player is on, None before there is a path
+    pub fn at(&self) -> Option<(usize, usize)> {
+        self.path.get(self.step).copied()
+    }
+}
+
+
+#[wasm_bindgen]
+impl MazeState {
+
+    // puts the player back at the start of the last path on a clean maze, returns how many
+    // steps the walk has
+    pub fn start_walk(&mut self, trail: bool) -> Result<usize, JsValue> {
+        let path = self.last_path.clone();
+        let Some(&start) = path.first() else {
+            return Err(JsValue::from_str("No path to walk, solve the maze first!"));
+        };
+        self.draw_player(start.0, start.1, PlayerType::Computer);
+        self.clear_visualization()?;
+        self.walk = Walk { path, step: 0, trail };
+        Ok(self.walk.path.len() - 1)
+    }
+
+    // moves the player one cell along the path, returns false once it has arrived
+    pub fn walk_step(&mut self) -> Result<bool, JsValue> {
+        let Some((from, to)) = self.walk.advance() else {
+            return Ok(false);
+        };
+        self.leave_cell(from);
+        self.fill_cell(to.0, to.1, &self.theme.computer.clone());
+        if to == self.target {
+            self.catch_target();
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    // the steps taken so far in the current walk
+    pub fn get_walk_step(&self) -> usize {
+        self.walk.step
+    }
+
+    // the whole walk from the start of the path, one step every `delay_ms`
+    pub async fn walk_path(&mut self, delay_ms: i32, trail: bool) -> Result<JsValue, JsValue> {
+        let steps = self.start_walk(trail)?;
+        while self.walk_step()? {
+            if delay_ms > 0 {
+                crate::maze::sleep(delay_ms).await?;
+            }
+        }
+        if self.walk.at() == Some(self.target) {
+            Ok(JsValue::from_str(&format!("Target caught! --> {} steps", steps)))
+        } else {
+            Ok(JsValue::from_str(&format!("Walked {} steps", steps)))
+        }
+    }
+}
+
+
+impl MazeState {
+    // what the cell looks like once the player has moved on: the trail or the floor, with
+    // whatever marker was on it drawn again
+    fn leave_cell(&mut self, (x, y): (usize, usize)) {
+        if self.walk.trail {
+            self.fill_cell(x, y, &self.theme.final_path.clone());
+        } else {
+            self.clear_cell(x, y);
+        }
+        if self.waypoints.contains(&(x, y)) {
+            self.fill_cell(x, y, &self.theme.waypoint.clone());
+        }
+        if let Some(&digit) = self.portal_labels.get(&(x, y)) {
+            self.draw_portal((x, y), digit);
+        }
+        if let Some(other_end) = self.links.floors.partner((x, y)) {
+            self.draw_stair((x, y), other_end);
+        }
+    }
+
+    // the player on the target, ringed in the target colour
+    fn catch_target(&mut self) {
+        let (x, y) = self.target;
+        self.pen().set_stroke_style_str(&self.theme.target.clone());
+        self.pen().set_line_width((self.block_size / 4.0).max(1.0));
+        self.outline_cell(x, y);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn advances_a_cell_at_a_time_and_stops_at_the_end() {
+        let mut walk = Walk { path: vec![(1, 1), (2, 1), (2, 2)], ..Walk::default() };
+        assert_eq!(walk.at(), Some((1, 1)));
+        assert_eq!(walk.advance(), Some(((1, 1), (2, 1))));
+        assert_eq!(walk.advance(), Some(((2, 1), (2, 2))));
+        assert_eq!((walk.step, walk.at()), (2, Some((2, 2))));
+        // at the end it stays put
+        assert_eq!(walk.advance(), None);
+        assert_eq!((walk.step, walk.at()), (2, Some((2, 2))));
+    }
+
+    #[test]
+    fn short_paths_have_nothing_to_walk() {
+        let mut walk = Walk::default();
+        assert_eq!((walk.advance(), walk.at()), (None, None));
+        let mut walk = Walk { path: vec![(3, 3)], ..Walk::default() };
+        assert_eq!((walk.advance(), walk.at()), (None, Some((3, 3))));
+        assert_eq!(walk.step, 0);
+    }
+}